@@ -2,12 +2,30 @@ use std::str::FromStr;
 
 use chrono::Utc;
 use clap::Parser;
+use dual_channel_bot::{
+    budget::BudgetReservation,
+    flatten::FlattenAccount,
+    funding::{FundingAction, FundingBias, FundingBiasConfig, FundingLedger},
+    margin::{DeRisk, HeldPosition, MarginConfig, MarginMonitor, MarginSnapshot},
+    pretrade::{OrderCheck, PreTradeChecker, PreTradeConfig, PreTradeRejection},
+    price_filter::{PriceFilter, PriceFilterConfig},
+    reconcile::{DriftPolicy, InternalState, ReconcileConfig, Reconciler},
+    reentry::{ReEntryConfig, ReEntryGuard},
+    risk::{KillSwitchConfig, RiskSupervisor},
+    schedule::{Schedule, ScheduleConfig},
+    shutdown::{close_out, ClosePolicy, OpenTrade, ShutdownConfig, ShutdownSignal, ShutdownState},
+    utils::{cancel_open_orders, CloseReason},
+    watchdog::{Staleness, Watchdog, WatchdogConfig, ALL_MIDS_FEED, WATCHDOG_INTERVAL_SECS},
+};
 use ethers::{signers::LocalWallet, types::H160};
 use hyperliquid_rust_sdk::{
     BaseUrl, ClientLimit, ClientOrder, ClientOrderRequest, ExchangeClient, ExchangeDataStatus,
     ExchangeResponseStatus, InfoClient, Message, Subscription, UserData,
 };
-use tokio::{signal, sync::mpsc::unbounded_channel};
+use tokio::{
+    sync::mpsc::{unbounded_channel, UnboundedSender},
+    time::interval,
+};
 use tracing::{debug, error, info, warn};
 
 const LEVERAGE: f64 = 3.0;
@@ -27,9 +45,61 @@ struct Args {
     #[arg(long, default_value_t = 15.0)]
     size: f64,
 
+    /// Long channel size [default: --size]
+    #[arg(long)]
+    long_size: Option<f64>,
+
+    /// Short channel size [default: --size]
+    #[arg(long)]
+    short_size: Option<f64>,
+
+    /// Don't trade the long channel
+    #[arg(long, default_value_t = false)]
+    disable_long: bool,
+
+    /// Don't trade the short channel
+    #[arg(long, default_value_t = false)]
+    disable_short: bool,
+
     /// Trading pair symbol
     #[arg(long, default_value = "HYPE")]
     symbol: String,
+
+    /// Cooldowns and entry caps applied before re-opening a channel
+    #[command(flatten)]
+    reentry: ReEntryConfig,
+
+    /// TOML file with the trading schedule (same keys as `[schedule]` in config.toml)
+    #[arg(long)]
+    schedule: Option<String>,
+
+    /// Daily loss and drawdown limits that halt new entries
+    #[command(flatten)]
+    kill_switch: KillSwitchConfig,
+
+    /// Reaction to market data that stops arriving
+    #[command(flatten)]
+    watchdog: WatchdogConfig,
+
+    /// Rejection of outlier mids before they reach the strategy
+    #[command(flatten)]
+    price_filter: PriceFilterConfig,
+
+    /// Limits every order is checked against before it is sent
+    #[command(flatten)]
+    pre_trade: PreTradeConfig,
+
+    /// Margin usage alerts and de-risking near liquidation
+    #[command(flatten)]
+    margin: MarginConfig,
+
+    /// Periodic comparison of the tracked position with the exchange
+    #[command(flatten)]
+    reconcile: ReconcileConfig,
+
+    /// What happens to positions and orders on Ctrl+C or SIGTERM
+    #[command(flatten)]
+    shutdown: ShutdownConfig,
 }
 
 #[derive(Debug)]
@@ -47,8 +117,10 @@ struct Trade {
 pub struct DualChannelTradingBot {
     /// The trading asset/coin symbol (e.g. "HYPE")
     asset: String,
-    /// The position size to use for each channel (long/short)
-    channel_size: f64,
+    /// The position size of the long channel, `None` when disabled
+    long_size: Option<f64>,
+    /// The position size of the short channel, `None` when disabled
+    short_size: Option<f64>,
     /// Current total position size across both channels
     current_position: f64,
     /// Latest mid price from the orderbook
@@ -63,20 +135,63 @@ pub struct DualChannelTradingBot {
     long_trade: Option<Trade>,
     /// Details of the current short trade if one exists
     short_trade: Option<Trade>,
+    /// Latest funding rate, used to shrink the channel paying funding
+    funding: FundingBias,
+    /// Estimated funding paid/received by the long channel
+    long_funding: FundingLedger,
+    /// Estimated funding paid/received by the short channel
+    short_funding: FundingLedger,
+    /// Re-entry throttling of the long channel
+    long_reentry: ReEntryGuard,
+    /// Re-entry throttling of the short channel
+    short_reentry: ReEntryGuard,
+    /// Windows in which new trades may be opened
+    schedule: Schedule,
+    /// Realized PnL (USD) of the long channel
+    long_pnl: f64,
+    /// Realized PnL (USD) of the short channel
+    short_pnl: f64,
+    /// Trades closed since start
+    closed_trades: usize,
+    /// Daily loss / drawdown kill switch
+    risk: RiskSupervisor,
+    /// Last mid price update, to pause and flatten on stale market data
+    watchdog: Watchdog,
+    /// Network the clients connect to, kept to reconnect
+    network: BaseUrl,
+    /// Drops outlier mids before they move `latest_mid_price`
+    price_filter: PriceFilter,
+    /// Validates every order before it is sent
+    pre_trade: PreTradeChecker,
+    /// Liquidation distance of the net position, polled from the clearinghouse state
+    margin: MarginMonitor,
+    /// Compares `current_position` with the exchange
+    reconciler: Reconciler,
 }
 
 impl DualChannelTradingBot {
+    #[allow(clippy::too_many_arguments)]
     pub async fn new(
         asset: String,
-        channel_size: f64,
+        long_size: Option<f64>,
+        short_size: Option<f64>,
+        reentry: ReEntryConfig,
+        schedule: Schedule,
+        kill_switch: KillSwitchConfig,
+        watchdog: WatchdogConfig,
+        price_filter: PriceFilterConfig,
+        pre_trade: PreTradeConfig,
+        margin: MarginConfig,
+        reconcile: ReconcileConfig,
         wallet: LocalWallet,
         user_address: String,
         network: BaseUrl,
     ) -> DualChannelTradingBot {
         debug!(
-            "Initializing bot with: asset={}, size={}, network={:?}",
+            "Initializing bot with: asset={}, long size={:?}, short size={:?}, network={:?}",
             asset,
-            channel_size,
+            long_size,
+            short_size,
             match network {
                 BaseUrl::Mainnet => "mainnet",
                 BaseUrl::Testnet => "testnet",
@@ -87,10 +202,16 @@ impl DualChannelTradingBot {
         let info_client = InfoClient::new(None, Some(network.clone())).await.unwrap();
         let exchange_client =
             ExchangeClient::new(None, wallet, Some(network.clone()), None, None).await.unwrap();
+        let pre_trade = PreTradeChecker::load(pre_trade, &info_client).await.unwrap();
 
         DualChannelTradingBot {
+            price_filter: PriceFilter::new(&asset, price_filter),
+            pre_trade,
+            margin: MarginMonitor::new(margin),
+            reconciler: Reconciler::new(reconcile),
             asset,
-            channel_size,
+            long_size,
+            short_size,
             current_position: 0.0,
             latest_mid_price: -1.0,
             info_client,
@@ -98,24 +219,32 @@ impl DualChannelTradingBot {
             user_address: H160::from_str(&user_address).unwrap(),
             long_trade: None,
             short_trade: None,
+            // Channels are only re-opened after a close, so a skipped channel would never come
+            // back: always skew instead.
+            funding: FundingBias::new(FundingBiasConfig {
+                action: FundingAction::Skew,
+                ..Default::default()
+            }),
+            long_funding: FundingLedger::default(),
+            short_funding: FundingLedger::default(),
+            long_reentry: ReEntryGuard::new(reentry.clone()),
+            short_reentry: ReEntryGuard::new(reentry),
+            schedule,
+            long_pnl: 0.0,
+            short_pnl: 0.0,
+            closed_trades: 0,
+            risk: RiskSupervisor::new(kill_switch),
+            watchdog: Watchdog::new(watchdog),
+            network,
         }
     }
 
     pub async fn start(&mut self) {
         info!("Starting dual channel bot for {}", self.asset);
-        debug!("Initial channel size: {}", self.channel_size);
+        debug!("Channel sizes: long {:?}, short {:?}", self.long_size, self.short_size);
 
         let (sender, mut receiver) = unbounded_channel();
-
-        // Subscribe to necessary feeds
-        debug!("Subscribing to user events for address: {}", self.user_address);
-        self.info_client
-            .subscribe(Subscription::UserEvents { user: self.user_address }, sender.clone())
-            .await
-            .unwrap();
-
-        debug!("Subscribing to market data");
-        self.info_client.subscribe(Subscription::AllMids, sender.clone()).await.unwrap();
+        self.subscribe_all(&sender).await.unwrap();
 
         info!("Waiting for initial price data...");
         // Wait for first valid price
@@ -123,8 +252,9 @@ impl DualChannelTradingBot {
             let message = receiver.recv().await.unwrap();
             if let Message::AllMids(all_mids) = message {
                 if let Some(mid) = all_mids.data.mids.get(&self.asset) {
-                    self.latest_mid_price = mid.parse().unwrap();
-                    if self.latest_mid_price > 0.0 {
+                    let price: f64 = mid.parse().unwrap();
+                    if self.price_filter.check(price, None).is_accepted() {
+                        self.latest_mid_price = price;
                         info!("Received initial price: {}", self.latest_mid_price);
                         break;
                     }
@@ -134,18 +264,55 @@ impl DualChannelTradingBot {
 
         // Initial trades
         debug!("Opening initial positions");
-        self.open_long_trade().await;
-        // self.open_short_trade().await;
+        let can_enter = self.schedule.can_enter(Utc::now());
+        if self.long_size.is_some() && can_enter {
+            self.open_long_trade().await;
+        }
+        if self.short_size.is_some() && can_enter {
+            self.open_short_trade().await;
+        }
 
         info!("Bot running - monitoring trades");
+        let mut watchdog_interval =
+            interval(std::time::Duration::from_secs(WATCHDOG_INTERVAL_SECS));
+        let mut margin_interval =
+            interval(std::time::Duration::from_secs(self.margin.config().margin_poll_secs.max(1)));
+        let mut reconcile_interval = interval(std::time::Duration::from_secs(
+            self.reconciler.config().reconcile_secs.max(1),
+        ));
+        let mut budget_interval = interval(std::time::Duration::from_secs(
+            self.pre_trade.config().budget.budget_publish_secs.max(1),
+        ));
         loop {
-            let message = receiver.recv().await.unwrap();
+            let message = tokio::select! {
+                message = receiver.recv() => message.unwrap(),
+                _ = watchdog_interval.tick() => {
+                    self.check_watchdog(&sender).await;
+                    continue;
+                }
+                _ = margin_interval.tick() => {
+                    self.check_margin().await;
+                    continue;
+                }
+                _ = reconcile_interval.tick() => {
+                    self.reconcile().await;
+                    continue;
+                }
+                _ = budget_interval.tick(), if self.pre_trade.budget().is_some() => {
+                    self.publish_budget().await;
+                    continue;
+                }
+            };
             match message {
                 Message::AllMids(all_mids) => {
                     let all_mids = all_mids.data.mids;
                     if let Some(mid) = all_mids.get(&self.asset) {
                         let new_price: f64 = mid.parse().unwrap();
                         debug!("Price update for {}: {}", self.asset, new_price);
+                        self.watchdog.touch(ALL_MIDS_FEED, &self.asset, Utc::now().timestamp());
+                        if !self.price_filter.check(new_price, None).is_accepted() {
+                            continue;
+                        }
                         self.latest_mid_price = new_price;
                         self.check_trades().await;
                     }
@@ -182,6 +349,11 @@ impl DualChannelTradingBot {
                         }
                     }
                 }
+                Message::ActiveAssetCtx(ctx) if ctx.data.coin == self.asset => {
+                    if let Err(e) = self.funding.update(&ctx.data.ctx) {
+                        warn!("Invalid asset context: {}", e);
+                    }
+                }
                 _ => {
                     debug!("Received unhandled message type");
                 }
@@ -189,41 +361,316 @@ impl DualChannelTradingBot {
         }
     }
 
+    /// Subscribe to user events, mids and the asset context
+    async fn subscribe_all(&mut self, sender: &UnboundedSender<Message>) -> eyre::Result<()> {
+        debug!("Subscribing to user events for address: {}", self.user_address);
+        self.info_client
+            .subscribe(Subscription::UserEvents { user: self.user_address }, sender.clone())
+            .await?;
+
+        debug!("Subscribing to market data");
+        self.info_client.subscribe(Subscription::AllMids, sender.clone()).await?;
+        self.info_client
+            .subscribe(Subscription::ActiveAssetCtx { coin: self.asset.clone() }, sender.clone())
+            .await?;
+        self.watchdog.watch(ALL_MIDS_FEED, &self.asset, Utc::now().timestamp());
+        Ok(())
+    }
+
+    /// Poll the clearinghouse state and reduce or close both channels when the liquidation
+    /// price of the net position got too close relative to the stop of the channel it follows
+    async fn check_margin(&mut self) {
+        if !self.margin.enabled() || self.current_position == 0.0 {
+            return;
+        }
+        let snapshot = match self.info_client.user_state(self.user_address).await {
+            Ok(state) => match MarginSnapshot::from_state(&state) {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    warn!("Invalid clearinghouse state: {}", e);
+                    return;
+                }
+            },
+            Err(e) => {
+                warn!("Failed to fetch clearinghouse state: {}", e);
+                return;
+            }
+        };
+        let net_trade =
+            if self.current_position > 0.0 { &self.long_trade } else { &self.short_trade };
+        let held = HeldPosition {
+            coin: &self.asset,
+            mark: self.latest_mid_price,
+            stop: net_trade.as_ref().map(|trade| trade.stop_loss),
+        };
+        match self.margin.check("ACCOUNT", &snapshot, &held) {
+            Some(DeRisk::Close) => {
+                self.close_trade(true).await;
+                self.close_trade(false).await;
+            }
+            Some(DeRisk::Reduce { fraction }) => {
+                self.reduce_trade(true, fraction).await;
+                self.reduce_trade(false, fraction).await;
+            }
+            None => {}
+        }
+    }
+
+    /// Compare `current_position` with the exchange's and apply the drift policy
+    async fn reconcile(&mut self) {
+        if !self.reconciler.enabled() {
+            return;
+        }
+        let state = match self.info_client.user_state(self.user_address).await {
+            Ok(state) => state,
+            Err(e) => {
+                warn!("Failed to fetch clearinghouse state: {}", e);
+                return;
+            }
+        };
+        // TP/SL order ids aren't tracked, so only the position is compared
+        let internal =
+            InternalState { coin: &self.asset, position: self.current_position, orders: None };
+        let event = match self.reconciler.check("ACCOUNT", &internal, &state, &[]) {
+            Ok(event) => event,
+            Err(e) => {
+                warn!("Invalid clearinghouse state: {}", e);
+                return;
+            }
+        };
+        if let Some(event) = event.filter(|event| event.policy == DriftPolicy::Correct) {
+            self.current_position = event.exchange;
+        }
+    }
+
+    /// Publish the account's positions to the budget shared with other processes
+    async fn publish_budget(&self) {
+        let Some(budget) = self.pre_trade.budget() else { return };
+        let published = async {
+            let state = self.info_client.user_state(self.user_address).await?;
+            let open_orders = self.info_client.open_orders(self.user_address).await?;
+            budget.publish(self.user_address, &state, &open_orders)
+        }
+        .await;
+        if let Err(e) = published {
+            warn!("Failed to publish positions to the shared budget: {}", e);
+        }
+    }
+
+    /// Close `fraction` of a channel's trade, keeping the rest open
+    async fn reduce_trade(&mut self, is_long: bool, fraction: f64) {
+        let Some(trade) = (if is_long { &self.long_trade } else { &self.short_trade }) else {
+            return;
+        };
+        let reduce = self.pre_trade.round_size(&self.asset, trade.position_size * fraction);
+        let remaining = trade.position_size - reduce;
+        if self.pre_trade.round_size(&self.asset, remaining) == 0.0 {
+            self.close_trade(is_long).await;
+            return;
+        }
+        if reduce == 0.0 {
+            return;
+        }
+        let entry_price = trade.entry_price;
+        let exit_price = self.round_price(self.latest_mid_price);
+        if !self.place_order(-reduce, exit_price).await {
+            return;
+        }
+        let pnl_usd = (exit_price - entry_price) * reduce;
+        if is_long {
+            self.long_pnl += pnl_usd;
+        } else {
+            self.short_pnl += pnl_usd;
+        }
+        if let Some(trade) =
+            if is_long { self.long_trade.as_mut() } else { self.short_trade.as_mut() }
+        {
+            trade.position_size = remaining;
+        }
+        info!(
+            "Reduced {} trade by {} to {} at {} (P&L ${:.2})",
+            if is_long { "long" } else { "short" },
+            reduce,
+            remaining,
+            exit_price,
+            pnl_usd
+        );
+    }
+
+    /// Resubscribe on a new connection when mids stop arriving, and close both channels at a
+    /// REST mid once they have been missing for too long
+    async fn check_watchdog(&mut self, sender: &UnboundedSender<Message>) {
+        let events = self.watchdog.check(Utc::now().timestamp());
+        if events.is_empty() {
+            return;
+        }
+
+        info!("Market data stale, resubscribing on a new connection");
+        match InfoClient::new(None, Some(self.network)).await {
+            Ok(info_client) => {
+                self.info_client = info_client;
+                if let Err(e) = self.subscribe_all(sender).await {
+                    error!("Failed to resubscribe: {}", e);
+                }
+            }
+            Err(e) => error!("Failed to reconnect: {}", e),
+        }
+
+        if !events.iter().any(|event| event.level == Staleness::Flatten) {
+            return;
+        }
+        let mid = match self.info_client.all_mids().await {
+            Ok(mids) => mids.get(&self.asset).and_then(|mid| mid.parse::<f64>().ok()),
+            Err(e) => {
+                error!("Failed to fetch mids: {}", e);
+                None
+            }
+        };
+        let Some(mid) = mid else {
+            error!("No REST price for {}, cannot flatten", self.asset);
+            return;
+        };
+        warn!("Flattening on stale market data at {}", mid);
+        self.latest_mid_price = mid;
+        self.close_trade(true).await;
+        self.close_trade(false).await;
+    }
+
     async fn check_trades(&mut self) {
         let current_time = Utc::now().timestamp();
+        self.accrue_funding(current_time);
 
         // Check long trade
         if let Some(trade) = &self.long_trade {
-            let should_close = self.should_close_trade(trade, current_time).await;
-            if should_close {
+            if let Some(reason) = self.should_close_trade(trade, current_time).await {
                 debug!(
                     "Closing long trade - Entry: {}, Current: {}, Time Open: {}s",
                     trade.entry_price,
                     self.latest_mid_price,
                     current_time - trade.entry_time
                 );
-                self.close_trade(true).await;
-                self.open_long_trade().await;
+                if let Some(pnl) = self.close_trade(true).await {
+                    self.long_reentry.on_close(pnl, reason, current_time);
+                }
             }
         }
 
         // Check short trade
         if let Some(trade) = &self.short_trade {
-            let should_close = self.should_close_trade(trade, current_time).await;
-            if should_close {
+            if let Some(reason) = self.should_close_trade(trade, current_time).await {
                 debug!(
                     "Closing short trade - Entry: {}, Current: {}, Time Open: {}s",
                     trade.entry_price,
                     self.latest_mid_price,
                     current_time - trade.entry_time
                 );
-                self.close_trade(false).await;
-                self.open_short_trade().await;
+                if let Some(pnl) = self.close_trade(false).await {
+                    self.short_reentry.on_close(pnl, reason, current_time);
+                }
             }
         }
+
+        // Flatten both channels when the trading window closes
+        if self.schedule.window_ended(Utc::now()) {
+            info!("Trading window ended, closing open trades");
+            self.close_trade(true).await;
+            self.close_trade(false).await;
+        }
+
+        self.check_risk().await;
+
+        // Re-open closed channels once their re-entry policy, the schedule, the kill switch,
+        // the market data watchdog and the reconciliation allow it
+        if !self.schedule.can_enter(Utc::now()) ||
+            !self.risk.can_enter(Utc::now()) ||
+            !self.watchdog.can_enter(&self.asset, current_time) ||
+            self.reconciler.is_halted()
+        {
+            return;
+        }
+        if self.long_trade.is_none() &&
+            self.long_size.is_some() &&
+            self.long_reentry.can_enter(current_time)
+        {
+            self.open_long_trade().await;
+        }
+        if self.short_trade.is_none() &&
+            self.short_size.is_some() &&
+            self.short_reentry.can_enter(current_time)
+        {
+            self.open_short_trade().await;
+        }
     }
 
-    async fn should_close_trade(&self, trade: &Trade, current_time: i64) -> bool {
+    /// Realized, funding and unrealized PnL (USD) of one channel
+    fn channel_pnl(&self, is_long: bool) -> f64 {
+        let (realized, funding, trade) = if is_long {
+            (self.long_pnl, &self.long_funding, &self.long_trade)
+        } else {
+            (self.short_pnl, &self.short_funding, &self.short_trade)
+        };
+        let unrealized = trade
+            .as_ref()
+            .map_or(0.0, |trade| (self.latest_mid_price - trade.entry_price) * trade.position_size);
+        realized + funding.total() + unrealized
+    }
+
+    /// Feed both channels and their total to the kill switch; on a breach cancel resting
+    /// orders and, if configured, close both channels
+    async fn check_risk(&mut self) {
+        let now = Utc::now();
+        let (long, short) = (self.channel_pnl(true), self.channel_pnl(false));
+        let mut breached = false;
+        for (label, pnl, aggregate) in
+            [("LONG", long, false), ("SHORT", short, false), ("ALL", long + short, true)]
+        {
+            breached |= self.risk.update(label, pnl, aggregate, now).is_some();
+        }
+        if !breached {
+            return;
+        }
+
+        match cancel_open_orders(
+            &self.info_client,
+            &self.exchange_client,
+            self.user_address,
+            &self.asset,
+        )
+        .await
+        {
+            Ok(cancelled) => info!("Cancelled {} resting orders", cancelled),
+            Err(e) => error!("Failed to cancel resting orders: {}", e),
+        }
+        if self.risk.config().flatten_on_breach {
+            self.close_trade(true).await;
+            self.close_trade(false).await;
+        }
+    }
+
+    /// Book funding settled since the last check on both channels
+    fn accrue_funding(&mut self, current_time: i64) {
+        for (trade, ledger, label) in [
+            (&self.long_trade, &mut self.long_funding, "Long"),
+            (&self.short_trade, &mut self.short_funding, "Short"),
+        ] {
+            let (signed_size, opened_at) = match trade {
+                Some(trade) => (trade.position_size, trade.entry_time),
+                None => (0.0, current_time),
+            };
+            let payment = ledger.accrue(&self.funding, signed_size, opened_at, current_time);
+            if payment != 0.0 {
+                info!(
+                    "{} funding {}: {:.4} (total {:.4})",
+                    label,
+                    if payment > 0.0 { "received" } else { "paid" },
+                    payment.abs(),
+                    ledger.total()
+                );
+            }
+        }
+    }
+
+    async fn should_close_trade(&self, trade: &Trade, current_time: i64) -> Option<CloseReason> {
         let time_open = current_time - trade.entry_time;
         let current_profit = if trade.is_long {
             (self.latest_mid_price - trade.entry_price) / trade.entry_price
@@ -247,7 +694,7 @@ impl DualChannelTradingBot {
                 if trade.is_long { "Long" } else { "Short" },
                 self.latest_mid_price
             );
-            return true;
+            return Some(CloseReason::StopLoss);
         }
 
         // Check take profit
@@ -259,7 +706,7 @@ impl DualChannelTradingBot {
                 if trade.is_long { "Long" } else { "Short" },
                 self.latest_mid_price
             );
-            return true;
+            return Some(CloseReason::TakeProfit);
         }
 
         // Check 30-minute profit
@@ -269,7 +716,7 @@ impl DualChannelTradingBot {
                 if trade.is_long { "Long" } else { "Short" },
                 current_profit * 100.0
             );
-            return true;
+            return Some(CloseReason::Timeout);
         }
 
         // Check max duration
@@ -279,15 +726,16 @@ impl DualChannelTradingBot {
                 if trade.is_long { "Long" } else { "Short" },
                 current_profit * 100.0
             );
-            return true;
+            return Some(CloseReason::Timeout);
         }
 
-        false
+        None
     }
 
     async fn open_long_trade(&mut self) {
         let entry_price = self.round_price(self.latest_mid_price);
-        let position_size = self.round_size(self.channel_size, entry_price);
+        let channel_size = self.long_size.unwrap_or_default() * self.funding.size_multiplier(true);
+        let position_size = self.round_size(channel_size, entry_price);
         let stop_loss = self.round_price(entry_price * (1.0 - SL_PERCENTAGE));
         let take_profit = self.round_price(entry_price * (1.0 + TP_PERCENTAGE));
 
@@ -296,8 +744,11 @@ impl DualChannelTradingBot {
             position_size, entry_price, stop_loss, take_profit
         );
 
-        self.place_order(position_size, entry_price).await;
+        if !self.place_order(position_size, entry_price).await {
+            return;
+        }
 
+        self.long_reentry.on_entry(Utc::now().timestamp());
         self.long_trade = Some(Trade {
             entry_price,
             position_size,
@@ -312,7 +763,9 @@ impl DualChannelTradingBot {
 
     async fn open_short_trade(&mut self) {
         let entry_price = self.round_price(self.latest_mid_price);
-        let position_size = self.round_size(-self.channel_size, entry_price);
+        let channel_size =
+            self.short_size.unwrap_or_default() * self.funding.size_multiplier(false);
+        let position_size = self.round_size(-channel_size, entry_price);
         let stop_loss = self.round_price(entry_price * (1.0 + SL_PERCENTAGE));
         let take_profit = self.round_price(entry_price * (1.0 - TP_PERCENTAGE));
 
@@ -321,8 +774,11 @@ impl DualChannelTradingBot {
             position_size, entry_price, stop_loss, take_profit
         );
 
-        self.place_order(position_size, entry_price).await;
+        if !self.place_order(position_size, entry_price).await {
+            return;
+        }
 
+        self.short_reentry.on_entry(Utc::now().timestamp());
         self.short_trade = Some(Trade {
             entry_price,
             position_size,
@@ -335,7 +791,8 @@ impl DualChannelTradingBot {
         info!("Opened short trade at {} with size {}", entry_price, position_size);
     }
 
-    async fn close_trade(&mut self, is_long: bool) {
+    /// Close a channel's trade, returning its P&L in percent (`None` if there was no trade)
+    async fn close_trade(&mut self, is_long: bool) -> Option<f64> {
         let trade = if is_long { self.long_trade.take() } else { self.short_trade.take() };
 
         if let Some(trade) = trade {
@@ -348,7 +805,22 @@ impl DualChannelTradingBot {
                 exit_price
             );
 
-            self.place_order(-trade.position_size, exit_price).await;
+            if !self.place_order(-trade.position_size, exit_price).await {
+                // Keep tracking the trade; the close is retried on the next price
+                if is_long {
+                    self.long_trade = Some(trade);
+                } else {
+                    self.short_trade = Some(trade);
+                }
+                return None;
+            }
+            let pnl_usd = (exit_price - trade.entry_price) * trade.position_size;
+            if is_long {
+                self.long_pnl += pnl_usd;
+            } else {
+                self.short_pnl += pnl_usd;
+            }
+            self.closed_trades += 1;
 
             let pnl = if is_long {
                 (exit_price - trade.entry_price) / trade.entry_price * 100.0
@@ -363,13 +835,21 @@ impl DualChannelTradingBot {
                 trade.entry_price,
                 pnl
             );
+            return Some(pnl);
         }
+        None
     }
 
     async fn place_tp_sl_orders(&self, size: f64, is_buy: bool, tp_price: f64, sl_price: f64) {
         // if tp_price == 0.0 || sl_price == 0.0 {
         //     return;
         // }
+        if [tp_price, sl_price]
+            .into_iter()
+            .any(|price| self.pre_check(!is_buy, price, size, true).is_err())
+        {
+            return;
+        }
 
         // Place Take Profit order
         let tp_order = self
@@ -408,8 +888,37 @@ impl DualChannelTradingBot {
         debug!("Stop Loss order response: {:?}", sl_order);
     }
 
-    async fn place_order(&self, size: f64, price: f64) {
+    /// Run the pre-trade checks on an order against the known position
+    fn pre_check(
+        &self,
+        is_buy: bool,
+        price: f64,
+        size: f64,
+        reduce_only: bool,
+    ) -> Result<Option<BudgetReservation>, PreTradeRejection> {
+        self.pre_trade.set_account_notional(
+            self.user_address,
+            self.current_position.abs() * self.latest_mid_price.max(0.0),
+        );
+        let check = OrderCheck {
+            asset: &self.asset,
+            is_buy,
+            price,
+            size,
+            reduce_only,
+            mid: Some(self.latest_mid_price),
+            position: self.current_position,
+        };
+        self.pre_trade.check(self.user_address, &check)
+    }
+
+    /// Place a limit order for `size` (negative to sell). Returns `false` if the pre-trade
+    /// checks rejected it; exchange errors are only logged.
+    async fn place_order(&self, size: f64, price: f64) -> bool {
         let is_buy = size > 0.0;
+        let Ok(reservation) = self.pre_check(is_buy, price, size, false) else {
+            return false;
+        };
 
         debug!(
             "Placing order - Side: {}, Size: {}, Price: {}",
@@ -450,7 +959,7 @@ impl DualChannelTradingBot {
                                     price
                                 );
                             }
-                            ExchangeDataStatus::Resting(_) => {
+                            ExchangeDataStatus::Resting(resting) => {
                                 info!(
                                     "Order resting: {} {} {} at {}",
                                     if is_buy { "Buy" } else { "Sell" },
@@ -458,9 +967,11 @@ impl DualChannelTradingBot {
                                     self.asset,
                                     price
                                 );
+                                self.pre_trade.on_resting(reservation, resting.oid);
                             }
                             ExchangeDataStatus::Error(e) => {
                                 error!("Error placing order: {}", e);
+                                self.pre_trade.release(reservation);
                             }
                             _ => {}
                         }
@@ -469,11 +980,14 @@ impl DualChannelTradingBot {
             }
             Ok(ExchangeResponseStatus::Err(e)) => {
                 error!("Error placing main order: {}", e);
+                self.pre_trade.release(reservation);
             }
             Err(e) => {
                 error!("Error placing main order: {}", e);
+                self.pre_trade.release(reservation);
             }
         }
+        true
     }
 
     /// Helper function to round price to tick size
@@ -482,37 +996,78 @@ impl DualChannelTradingBot {
         (price / tick_size).round() * tick_size
     }
 
-    /// Helper function to round size to sz_decimals
+    /// Helper function to round size to the asset's sz_decimals, keeping its sign
     fn round_size(&self, size: f64, price: f64) -> f64 {
-        let sz_decimals = 2; // Define sz_decimals here
-        ((size.abs() / price * LEVERAGE) * 10f64.powi(sz_decimals as i32)).round() /
-            10f64.powi(sz_decimals as i32)
+        self.pre_trade.round_size(&self.asset, size / price * LEVERAGE)
     }
 }
 
 /// Gracefully shuts down the bot, closes all open positions, and prints statistics
-async fn shutdown_bot(bot: &mut DualChannelTradingBot) {
-    info!("Shutting down the bot...");
-
-    // // Close any open long trade
-    // if bot.long_trade.is_some() {
-    //     info!("Closing open long position...");
-    //     bot.close_trade(true).await;
-    // }
-
-    // // Close any open short trade
-    // if bot.short_trade.is_some() {
-    //     info!("Closing open short position...");
-    //     bot.close_trade(false).await;
-    // }
-
-    // Print bot statistics
-    let total_trades = bot.long_trade.is_some() as u64 + bot.short_trade.is_some() as u64;
-    info!("Bot shutdown completed.");
+/// Apply the close policy to the account, then log the final statistics and persist the
+/// trades left behind
+async fn shutdown_bot(bot: &mut DualChannelTradingBot, config: &ShutdownConfig, reason: &str) {
+    info!("Shutting down the bot ({})...", reason);
+
+    // Stop orders are placed on every fill, so trades are left with the ones already resting
+    let mut open_trades: Vec<OpenTrade> = [&bot.long_trade, &bot.short_trade]
+        .into_iter()
+        .flatten()
+        .map(|trade| OpenTrade {
+            label: "ACCOUNT".to_string(),
+            coin: bot.asset.clone(),
+            size: trade.position_size,
+            entry_price: trade.entry_price,
+            stop: None,
+            take_profit: Some(trade.take_profit),
+        })
+        .collect();
+    let account = FlattenAccount {
+        label: "ACCOUNT",
+        user: bot.user_address,
+        exchange_client: &bot.exchange_client,
+    };
+    let completed = close_out(
+        &bot.info_client,
+        &bot.pre_trade,
+        &[account],
+        std::slice::from_ref(&bot.asset),
+        &open_trades,
+        config,
+    )
+    .await;
+
+    if completed && config.close_policy == ClosePolicy::Flatten {
+        // Book the flattened trades at the last mid, the fills aren't awaited
+        for (trade, pnl) in [
+            (bot.long_trade.take(), &mut bot.long_pnl),
+            (bot.short_trade.take(), &mut bot.short_pnl),
+        ] {
+            if let Some(trade) = trade {
+                *pnl += (bot.latest_mid_price - trade.entry_price) * trade.position_size;
+                bot.closed_trades += 1;
+            }
+        }
+        open_trades.clear();
+    }
+
     info!(
-        "Exiting with stats: Total Trades Executed: {}, Final Position: {}",
-        total_trades, bot.current_position
+        "Exiting with stats: {} trade(s) closed, {} left open, position {}",
+        bot.closed_trades,
+        open_trades.len(),
+        bot.current_position
     );
+    info!("Realized P&L: Long ${:.2}, Short ${:.2}", bot.long_pnl, bot.short_pnl);
+    info!("Funding: Long {:.4}, Short {:.4}", bot.long_funding.total(), bot.short_funding.total());
+
+    let mut state = ShutdownState::new("bot", reason, config.close_policy);
+    state.completed = completed;
+    state.open_trades = open_trades;
+    state.closed_trades = bot.closed_trades;
+    state.total_pnl = bot.long_pnl + bot.short_pnl;
+    if let Err(e) = state.save(&config.state_path("bot")) {
+        error!("Failed to write the shutdown state: {}", e);
+    }
+    info!("Bot shutdown completed.");
 }
 
 #[tokio::main]
@@ -537,20 +1092,50 @@ async fn main() -> eyre::Result<()> {
         args.size
     );
 
-    let mut bot =
-        DualChannelTradingBot::new(args.symbol, args.size, wallet, user_address, network).await;
+    let long_size = (!args.disable_long).then(|| args.long_size.unwrap_or(args.size));
+    let short_size = (!args.disable_short).then(|| args.short_size.unwrap_or(args.size));
+    if long_size.is_none() && short_size.is_none() {
+        return Err(eyre::eyre!("Both channels are disabled"));
+    }
+    let schedule = match &args.schedule {
+        Some(path) => {
+            Schedule::new(&toml::from_str::<ScheduleConfig>(&std::fs::read_to_string(path)?)?)?
+        }
+        None => Schedule::default(),
+    };
+
+    let mut bot = DualChannelTradingBot::new(
+        args.symbol,
+        long_size,
+        short_size,
+        args.reentry,
+        schedule,
+        args.kill_switch,
+        args.watchdog,
+        args.price_filter,
+        args.pre_trade,
+        args.margin,
+        args.reconcile,
+        wallet,
+        user_address,
+        network,
+    )
+    .await;
 
     // Spawn the bot and listen for shutdown signals
-    tokio::select! {
+    let mut shutdown_signal = ShutdownSignal::new()?;
+    let reason = tokio::select! {
         _ = bot.start() => {
             // If bot.start exits, we clean up
             info!("Bot stopped running.");
+            "stopped"
         }
-        _ = signal::ctrl_c() => {
-            info!("Received shutdown signal (Ctrl+C).");
-            shutdown_bot(&mut bot).await;
+        reason = shutdown_signal.recv() => {
+            info!("Received shutdown signal ({}).", reason);
+            reason
         }
-    }
+    };
+    shutdown_bot(&mut bot, &args.shutdown, reason).await;
 
     Ok(())
 }
@@ -1,28 +1,94 @@
-use std::str::FromStr;
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    sync::Arc,
+};
 
-use clap::Parser;
+use chrono::Utc;
+use clap::{Parser, Subcommand, ValueEnum};
 use dotenvy::dotenv;
 use ethers::{signers::LocalWallet, types::H160};
 use eyre::Ok;
 use hyperliquid_rust_sdk::{
     BaseUrl, ExchangeClient, InfoClient, Message, Subscription, TradeInfo, UserData,
 };
-use tokio::{select, signal, sync::mpsc::unbounded_channel, time::interval};
-use tracing::{debug, error, info};
+use serde::Deserialize;
+use tokio::{
+    select,
+    sync::mpsc::{unbounded_channel, UnboundedSender},
+    time::interval,
+};
+use tracing::{debug, error, info, warn};
 use tracing_subscriber::EnvFilter;
 
 use dual_channel_bot::{
-    get_price,
-    utils::{check_account_position, create_trade, print_statistics, BotParams, TradingAccount},
+    exposure::{ExposureAction, ExposureConfig, ExposureGuard},
+    flatten::{flatten_all, FlattenAccount, FlattenConfig},
+    funding::{FundingAction, FundingBias, FundingBiasConfig, FundingLedger},
+    grid::{Grid, GridParams, LevelState},
+    margin::{DeRisk, HeldPosition, MarginConfig, MarginMonitor, MarginSnapshot},
+    market_making::{MarketMaker, MarketMakingParams, QuoteReference},
+    pairs::{HedgeMode, HedgeRatio, PairAction, PairParams, PairSide, PairTrader},
+    pretrade::{PreTradeChecker, PreTradeConfig},
+    price_filter::{PriceFilter, PriceFilterConfig},
+    reconcile::{DriftEvent, DriftPolicy, InternalState, ReconcileConfig, Reconciler},
+    reentry::{ReEntryConfig, ReEntryGuard},
+    regime::{RegimeConfig, RegimeFilter},
+    risk::{KillSwitchConfig, RiskSupervisor},
+    schedule::{Schedule, ScheduleConfig},
+    shutdown::{close_out, ClosePolicy, OpenTrade, ShutdownConfig, ShutdownSignal, ShutdownState},
+    sizing::{PriceVol, Sizer, SizerArgs, SizingInput},
+    utils::{
+        account_equity, cancel_open_orders, check_account_position, close_position, create_trade,
+        print_statistics, reduce_position, BotParams, Direction, Trade, TradingAccount,
+    },
+    watchdog::{
+        Staleness, Watchdog, WatchdogConfig, ALL_MIDS_FEED, L2_BOOK_FEED, WATCHDOG_INTERVAL_SECS,
+    },
+    Candle, OrderBook,
 };
 
 /// Print stats every 5 minutes
 const STATS_INTERVAL_SECS: u64 = 60;
 
+/// Which strategy the bot runs on its accounts
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Strategy {
+    /// One long and one short TP/SL trade, re-opened after every close
+    DualChannel,
+    /// Post-only two-sided quotes on the long account
+    MarketMaking,
+    /// Grid of post-only levels around the start price on the long account
+    Grid,
+    /// Long one coin and short another on the spread z-score of the two `--asset` coins
+    Pair,
+}
+
+/// One-off commands run instead of the bot
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Cancel every open order and close every position of both accounts, then exit. Safe to
+    /// run while a bot trades the same accounts.
+    Flatten {
+        /// Only flatten these coins (default: every coin)
+        #[arg(long, value_delimiter = ',')]
+        coin: Vec<String>,
+
+        #[command(flatten)]
+        config: FlattenConfig,
+    },
+}
+
 /// CLI arguments
 #[derive(Parser, Debug)]
 struct Args {
-    #[arg(long, default_value_t = false)]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(long, value_enum, default_value_t = Strategy::DualChannel)]
+    strategy: Strategy,
+
+    #[arg(long, global = true, default_value_t = false)]
     mainnet: bool,
 
     #[arg(long, default_value_t = 10.0)]
@@ -40,24 +106,296 @@ struct Args {
     #[arg(long, default_value_t = 900)]
     timeout_sec: u64,
 
-    #[arg(long, default_value = "HYPE")]
-    asset: String,
+    /// Coins to trade, comma-separated [default: `assets` from --config, else HYPE].
+    /// Market making and grid trade a single coin.
+    #[arg(long, value_delimiter = ',')]
+    asset: Vec<String>,
+
+    /// TOML file with `[long]` / `[short]` channel blocks (same keys as the channel flags),
+    /// optionally overridden per coin in `[coins.<COIN>.long]` / `[coins.<COIN>.short]`
+    #[arg(long)]
+    config: Option<String>,
+
+    /// Don't trade the long channel
+    #[arg(long, default_value_t = false)]
+    disable_long: bool,
+
+    /// Long channel amount [default: --amount]
+    #[arg(long)]
+    long_amount: Option<f64>,
+
+    /// Long channel leverage [default: --leverage]
+    #[arg(long)]
+    long_leverage: Option<f64>,
+
+    /// Long channel take-profit [default: --tp-percent]
+    #[arg(long)]
+    long_tp_percent: Option<f64>,
+
+    /// Long channel stop-loss [default: --sl-percent]
+    #[arg(long)]
+    long_sl_percent: Option<f64>,
+
+    /// Long channel timeout [default: --timeout-sec]
+    #[arg(long)]
+    long_timeout_sec: Option<u64>,
+
+    /// Don't trade the short channel
+    #[arg(long, default_value_t = false)]
+    disable_short: bool,
+
+    /// Short channel amount [default: --amount]
+    #[arg(long)]
+    short_amount: Option<f64>,
+
+    /// Short channel leverage [default: --leverage]
+    #[arg(long)]
+    short_leverage: Option<f64>,
+
+    /// Short channel take-profit [default: --tp-percent]
+    #[arg(long)]
+    short_tp_percent: Option<f64>,
+
+    /// Short channel stop-loss [default: --sl-percent]
+    #[arg(long)]
+    short_sl_percent: Option<f64>,
+
+    /// Short channel timeout [default: --timeout-sec]
+    #[arg(long)]
+    short_timeout_sec: Option<u64>,
+
+    /// Market making: bid/ask distance in bps of the reference price
+    #[arg(long, default_value_t = 10.0)]
+    mm_spread_bps: f64,
+
+    /// Market making: quote shift at full inventory, in bps
+    #[arg(long, default_value_t = 5.0)]
+    mm_skew_bps: f64,
+
+    /// Market making: reference move that triggers a requote, in bps
+    #[arg(long, default_value_t = 5.0)]
+    mm_requote_bps: f64,
+
+    /// Market making: inventory cap in USD notional
+    #[arg(long, default_value_t = 100.0)]
+    mm_max_inventory: f64,
+
+    /// Market making: price the quotes are centred on
+    #[arg(long, value_enum, default_value_t = QuoteReference::Mid)]
+    mm_reference: QuoteReference,
+
+    /// Grid: number of levels on each side of the start price
+    #[arg(long, default_value_t = 5)]
+    grid_levels: usize,
+
+    /// Grid: distance between levels and to each take-profit (e.g. 0.005 = 0.5%)
+    #[arg(long, default_value_t = 0.005)]
+    grid_step_percent: f64,
+
+    /// Grid: file the level state is persisted to [default: .cache/grid_<asset>.json]
+    #[arg(long)]
+    grid_state_path: Option<String>,
+
+    /// Pair: how the hedge ratio of the first coin on the second is estimated
+    #[arg(long, value_enum, default_value_t = HedgeMode::RollingOls)]
+    pair_hedge: HedgeMode,
+
+    /// Pair: hedge ratio with `--pair-hedge fixed`
+    #[arg(long, default_value_t = 1.0)]
+    pair_beta: f64,
+
+    /// Pair: samples in the rolling OLS regression
+    #[arg(long, default_value_t = 240)]
+    pair_ols_periods: usize,
+
+    /// Pair: seconds between spread samples
+    #[arg(long, default_value_t = 60)]
+    pair_sample_secs: u64,
+
+    /// Pair: samples the spread z-score is computed over
+    #[arg(long, default_value_t = 120)]
+    pair_lookback: usize,
+
+    /// Pair: enter when the z-score is beyond ±this
+    #[arg(long, default_value_t = 2.0)]
+    pair_entry_z: f64,
+
+    /// Pair: exit when the z-score is back within ±this
+    #[arg(long, default_value_t = 0.5)]
+    pair_exit_z: f64,
+
+    /// Pair: exit after this many seconds regardless of the spread
+    #[arg(long, default_value_t = 86_400)]
+    pair_max_hold_secs: u64,
+
+    /// Absolute hourly funding rate above which the paying channel is scaled down or skipped
+    #[arg(long, default_value_t = 0.0001)]
+    funding_threshold: f64,
+
+    /// What to do with the paying channel when funding is above the threshold
+    #[arg(long, value_enum, default_value_t = FundingAction::Skew)]
+    funding_action: FundingAction,
+
+    /// Size multiplier of the paying channel with `--funding-action skew`
+    #[arg(long, default_value_t = 0.5)]
+    funding_paying_scale: f64,
+
+    /// Dual channel: cooldowns and entry caps applied before re-opening a channel
+    #[command(flatten)]
+    reentry: ReEntryConfig,
+
+    /// Dual channel: size trades from account equity instead of amount and leverage
+    #[command(flatten)]
+    sizing: SizerArgs,
+
+    /// Daily loss and drawdown limits that halt new entries
+    #[command(flatten)]
+    kill_switch: KillSwitchConfig,
+
+    /// Reaction to market data that stops arriving
+    #[command(flatten)]
+    watchdog: WatchdogConfig,
+
+    /// Rejection of outlier mids before they reach the strategy
+    #[command(flatten)]
+    price_filter: PriceFilterConfig,
+
+    /// Limits every order is checked against before it is sent
+    #[command(flatten)]
+    pre_trade: PreTradeConfig,
+
+    /// Margin usage alerts and de-risking near liquidation
+    #[command(flatten)]
+    margin: MarginConfig,
+
+    /// Dual channel: limit on the net position of both accounts together
+    #[command(flatten)]
+    exposure: ExposureConfig,
+
+    /// Periodic comparison of the tracked trades and orders with the exchange
+    #[command(flatten)]
+    reconcile: ReconcileConfig,
+
+    /// What happens to positions and orders on Ctrl+C or SIGTERM
+    #[command(flatten)]
+    shutdown: ShutdownConfig,
+}
+
+/// One channel block of the `--config` file. Unset keys fall back to the shared CLI values;
+/// CLI channel flags take precedence over the file.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(default)]
+struct ChannelConfig {
+    enabled: Option<bool>,
+    amount: Option<f64>,
+    leverage: Option<f64>,
+    tp_percent: Option<f64>,
+    sl_percent: Option<f64>,
+    timeout_sec: Option<u64>,
+}
+
+impl ChannelConfig {
+    /// Keys set in `self`, the rest taken from `fallback`.
+    fn or(self, fallback: &ChannelConfig) -> Self {
+        Self {
+            enabled: self.enabled.or(fallback.enabled),
+            amount: self.amount.or(fallback.amount),
+            leverage: self.leverage.or(fallback.leverage),
+            tp_percent: self.tp_percent.or(fallback.tp_percent),
+            sl_percent: self.sl_percent.or(fallback.sl_percent),
+            timeout_sec: self.timeout_sec.or(fallback.timeout_sec),
+        }
+    }
 }
 
+/// Channel blocks of one coin (`[coins.<COIN>.long]` / `[coins.<COIN>.short]`), overriding the
+/// top-level `[long]` / `[short]` blocks.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct CoinConfig {
+    long: ChannelConfig,
+    short: ChannelConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct FileConfig {
+    /// Coins to trade when `--asset` is not given
+    assets: Vec<String>,
+    long: ChannelConfig,
+    short: ChannelConfig,
+    coins: HashMap<String, CoinConfig>,
+    /// Dual channel: when new trades may be opened
+    schedule: ScheduleConfig,
+    /// Dual channel: position sizing, overridden by `--sizing`
+    sizing: Option<Sizer>,
+    /// Dual channel: scale or pause the channels by market regime
+    regime: Option<RegimeConfig>,
+}
+
+/// Dual-channel state of one coin. The accounts of every coin share the same exchange clients.
 #[derive(Debug)]
-struct DualAccountBot {
+struct AssetChannels {
     asset: String,
-    params: SimParams,
+    long_params: SimParams,
+    short_params: SimParams,
     long_account: TradingAccount,
     short_account: TradingAccount,
-    info_client: InfoClient,
     latest_price: f64,
     total_pnl: f64,
+    funding: FundingBias,
+    /// Estimated funding paid/received by each channel (also included in `total_pnl`)
+    long_funding: FundingLedger,
+    short_funding: FundingLedger,
+    long_reentry: ReEntryGuard,
+    short_reentry: ReEntryGuard,
+    /// Sizes trades when set; otherwise each channel trades `amount` at `leverage`
+    sizer: Option<Sizer>,
+    /// Last account value of each channel's account
+    long_equity: f64,
+    short_equity: f64,
+    volatility: PriceVol,
+    /// Trend/range classifier scaling both channels, when configured
+    regime: Option<RegimeFilter>,
+    /// Drops outlier mids before they move `latest_price`
+    price_filter: PriceFilter,
 }
 
-/// Minimal struct to hold our simulation parameters
+#[derive(Debug)]
+struct DualAccountBot {
+    /// One entry per traded coin; market making and grid run on the first one only
+    channels: Vec<AssetChannels>,
+    info_client: InfoClient,
+    strategy: Strategy,
+    /// Quoting state when running `Strategy::MarketMaking`
+    market_maker: Option<MarketMaker>,
+    mm_reference: QuoteReference,
+    order_book: OrderBook,
+    grid_params: GridParams,
+    /// Level state when running `Strategy::Grid`, laid out once the first price arrives
+    grid: Option<Grid>,
+    /// Dual channel: when new trades may be opened
+    schedule: Schedule,
+    /// Spread state when running `Strategy::Pair` on `channels[0]` and `channels[1]`
+    pair: Option<PairTrader>,
+    /// Daily loss / drawdown kill switch over both accounts
+    risk: RiskSupervisor,
+    /// Last market data update per coin and feed
+    watchdog: Watchdog,
+    /// Liquidation distance of the open trades, polled from the clearinghouse state
+    margin: MarginMonitor,
+    /// Dual channel: net position of both accounts from their fills
+    exposure: ExposureGuard,
+    /// Compares the tracked trades, quotes and grid orders with the exchange
+    reconciler: Reconciler,
+    /// Close policy and state file applied on exit
+    shutdown: ShutdownConfig,
+}
+
+/// Minimal struct to hold our simulation parameters, one per channel
 #[derive(Debug, Clone)]
 struct SimParams {
+    enabled: bool,
     amount: f64,
     leverage: f64,
     tp_percent: f64,
@@ -78,11 +416,265 @@ impl From<&SimParams> for BotParams {
     }
 }
 
+impl SimParams {
+    /// Resolve one channel: CLI channel flag, then config block, then the shared CLI value.
+    fn resolve(args: &Args, file: &ChannelConfig, cli: ChannelConfig, disabled: bool) -> Self {
+        Self {
+            enabled: !disabled && file.enabled.unwrap_or(true),
+            amount: cli.amount.or(file.amount).unwrap_or(args.amount),
+            leverage: cli.leverage.or(file.leverage).unwrap_or(args.leverage),
+            tp_percent: cli.tp_percent.or(file.tp_percent).unwrap_or(args.tp_percent),
+            sl_percent: cli.sl_percent.or(file.sl_percent).unwrap_or(args.sl_percent),
+            timeout_sec: cli.timeout_sec.or(file.timeout_sec).unwrap_or(args.timeout_sec),
+        }
+    }
+}
+
+impl AssetChannels {
+    /// Run one dual-channel step on the latest price; the long and short channels are only
+    /// re-opened when `long_entries` and `short_entries` allow it
+    async fn on_price(
+        &mut self,
+        schedule: &Schedule,
+        long_entries: bool,
+        short_entries: bool,
+    ) -> eyre::Result<()> {
+        self.print_current_pnl();
+        self.accrue_funding();
+
+        let long_params = self.channel_params(true).filter(|_| long_entries);
+        let long_is_long_account = self.long_account.is_long_account;
+        check_account_position(
+            &mut self.long_account,
+            self.latest_price,
+            self.long_params.timeout_sec,
+            long_is_long_account,
+            &mut self.total_pnl,
+            &self.asset,
+            long_params.as_ref(),
+            &mut self.long_reentry,
+            schedule,
+        )
+        .await?;
+
+        let short_params = self.channel_params(false).filter(|_| short_entries);
+        let short_is_long_account = self.short_account.is_long_account;
+        check_account_position(
+            &mut self.short_account,
+            self.latest_price,
+            self.short_params.timeout_sec,
+            short_is_long_account,
+            &mut self.total_pnl,
+            &self.asset,
+            short_params.as_ref(),
+            &mut self.short_reentry,
+            schedule,
+        )
+        .await?;
+
+        // Open flat channels: at start-up, or once funding, re-entry or the schedule no longer
+        // holds them back
+        if !schedule.can_enter(Utc::now()) {
+            return Ok(());
+        }
+        let now = Utc::now().timestamp();
+        for (account, params, reentry) in [
+            (&mut self.long_account, long_params, &mut self.long_reentry),
+            (&mut self.short_account, short_params, &mut self.short_reentry),
+        ] {
+            if let (None, Some(params)) = (&account.active_trade, params) {
+                if !reentry.can_enter(now) {
+                    continue;
+                }
+                let trade = create_trade(
+                    account.is_long_account,
+                    self.latest_price,
+                    &params,
+                    &account.pre_trade,
+                    &self.asset,
+                );
+                if account.open_position(trade, &self.asset).await? {
+                    reentry.on_entry(now);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Realized, funding and unrealized PnL of one channel
+    fn channel_pnl(&self, is_long: bool) -> f64 {
+        let (account, funding) = if is_long {
+            (&self.long_account, &self.long_funding)
+        } else {
+            (&self.short_account, &self.short_funding)
+        };
+        let realized: f64 = account.closed_trades.iter().filter_map(Trade::pnl).sum();
+        let unrealized =
+            account.active_trade.as_ref().map_or(0.0, |trade| trade.pnl_at(self.latest_price));
+        realized + funding.total() + unrealized
+    }
+
+    /// Close the open trades of both channels at the latest price
+    async fn flatten(&mut self) -> eyre::Result<()> {
+        for account in [&mut self.long_account, &mut self.short_account] {
+            if account.active_trade.is_some() {
+                info!(
+                    "[{}] Flattening {} at {:.4}",
+                    self.asset,
+                    if account.is_long_account { "LONG" } else { "SHORT" },
+                    self.latest_price
+                );
+                close_position(account, self.latest_price, &self.asset, &mut self.total_pnl)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Parameters for the next trade of a channel, with its size scaled for funding.
+    /// `None` when the channel is disabled or funding says it should stay flat.
+    fn channel_params(&self, is_long: bool) -> Option<BotParams> {
+        let params = if is_long { &self.long_params } else { &self.short_params };
+        if !params.enabled {
+            return None;
+        }
+        let regime_scale = self.regime.as_ref().map_or(1.0, RegimeFilter::scale);
+        if regime_scale <= 0.0 {
+            return None;
+        }
+        let multiplier = self.funding.size_multiplier(is_long);
+        if multiplier <= 0.0 {
+            return None;
+        }
+        if multiplier < 1.0 {
+            debug!(
+                "[{}] Funding rate {:?}: {} channel sized at {:.0}%",
+                self.asset,
+                self.funding.rate(),
+                if is_long { "LONG" } else { "SHORT" },
+                multiplier * 100.0
+            );
+        }
+        let mut params = BotParams::from(params);
+        if let Some(sizer) = &self.sizer {
+            let stop_price = if is_long {
+                self.latest_price * (1.0 - params.sl_percent)
+            } else {
+                self.latest_price * (1.0 + params.sl_percent)
+            };
+            let input = SizingInput {
+                price: self.latest_price,
+                equity: if is_long { self.long_equity } else { self.short_equity },
+                stop_price: Some(stop_price),
+                volatility: self.volatility.annualized(),
+            };
+            match sizer.notional(&input) {
+                Result::Ok(notional) => params.amount = notional / params.leverage,
+                Err(e) => debug!("[{}] Sizing unavailable ({}), using amount", self.asset, e),
+            }
+        }
+        params.amount *= multiplier * regime_scale;
+        Some(params)
+    }
+
+    /// Book funding settled since the last update on each channel's open trade
+    fn accrue_funding(&mut self) {
+        let now = Utc::now().timestamp();
+        for (account, ledger, label) in [
+            (&self.long_account, &mut self.long_funding, "LONG"),
+            (&self.short_account, &mut self.short_funding, "SHORT"),
+        ] {
+            let (signed_size, opened_at) = match &account.active_trade {
+                Some(trade) if account.is_long_account => (trade.size, trade.entry_time),
+                Some(trade) => (-trade.size, trade.entry_time),
+                None => (0.0, now),
+            };
+            let payment = ledger.accrue(&self.funding, signed_size, opened_at, now);
+            if payment != 0.0 {
+                self.total_pnl += payment;
+                info!(
+                    "[{}] {} funding {}: ${:.4} (total ${:.4})",
+                    self.asset,
+                    label,
+                    if payment > 0.0 { "received" } else { "paid" },
+                    payment.abs(),
+                    ledger.total()
+                );
+            }
+        }
+    }
+
+    /// Print closed-trade statistics and funding of both channels
+    fn print_statistics(&self) {
+        info!("[{}] Total PnL: ${:.4}", self.asset, self.total_pnl);
+        print_statistics(&self.long_account.closed_trades);
+        print_statistics(&self.short_account.closed_trades);
+        info!(
+            "[{}] Funding LONG: ${:.4}, SHORT: ${:.4} (rate {:?})",
+            self.asset,
+            self.long_funding.total(),
+            self.short_funding.total(),
+            self.funding.rate()
+        );
+        if let Some(regime) = &self.regime {
+            regime.print_statistics(&self.asset);
+        }
+    }
+
+    /// Print the current PnL of the long and short accounts.
+    /// We calculate unrealized PnL as:
+    ///     PnL = (current_price - entry_price) / entry_price * 100  (for Long positions)
+    ///     PnL = (entry_price - current_price) / entry_price * 100  (for Short positions)
+    fn print_current_pnl(&self) {
+        let current_price = self.latest_price;
+
+        // Print LONG account PnL
+        if let Some(trade) = &self.long_account.active_trade {
+            let pnl = (current_price - trade.entry_price) / trade.entry_price * 100.0;
+            debug!(
+                "[{}] LONG Account: Entry Price={:.4}, Current Price={:.4}, PnL={:.4}%",
+                self.asset, trade.entry_price, current_price, pnl
+            );
+        } else {
+            debug!("[{}] LONG Account: No active trade.", self.asset);
+        }
+
+        // Print SHORT account PnL
+        if let Some(trade) = &self.short_account.active_trade {
+            let pnl = (trade.entry_price - current_price) / trade.entry_price * 100.0;
+            debug!(
+                "[{}] SHORT Account: Entry Price={:.4}, Current Price={:.4}, PnL={:.4}",
+                self.asset, trade.entry_price, current_price, pnl
+            );
+        } else {
+            debug!("[{}] SHORT Account: No active trade.", self.asset);
+        }
+    }
+}
+
 impl DualAccountBot {
-    /// Construct a new DualAccountBot
+    /// Construct a new DualAccountBot trading `assets` (coin, long params, short params)
+    #[allow(clippy::too_many_arguments)]
     async fn new(
-        asset: String,
-        params: SimParams,
+        assets: Vec<(String, SimParams, SimParams)>,
+        strategy: Strategy,
+        mm_params: MarketMakingParams,
+        mm_reference: QuoteReference,
+        grid_params: GridParams,
+        schedule: Schedule,
+        funding_config: FundingBiasConfig,
+        reentry_config: ReEntryConfig,
+        sizer: Option<Sizer>,
+        regime_config: Option<RegimeConfig>,
+        pair: Option<PairTrader>,
+        kill_switch: KillSwitchConfig,
+        watchdog: WatchdogConfig,
+        price_filter: PriceFilterConfig,
+        pre_trade: PreTradeConfig,
+        margin: MarginConfig,
+        exposure: ExposureConfig,
+        reconcile: ReconcileConfig,
+        shutdown: ShutdownConfig,
         long_wallet: LocalWallet,
         short_wallet: LocalWallet,
         user_address_long: String,
@@ -90,42 +682,149 @@ impl DualAccountBot {
         network: BaseUrl,
     ) -> eyre::Result<Self> {
         let info_client = InfoClient::new(None, Some(network.clone())).await?;
-        let long_exchange =
+        let long_exchange = Arc::new(
             ExchangeClient::new(None, long_wallet.clone(), Some(network.clone()), None, None)
-                .await?;
-        let short_exchange =
+                .await?,
+        );
+        let short_exchange = Arc::new(
             ExchangeClient::new(None, short_wallet.clone(), Some(network.clone()), None, None)
-                .await?;
+                .await?,
+        );
+        let user_address_long = H160::from_str(&user_address_long)?;
+        let user_address_short = H160::from_str(&user_address_short)?;
+        let pre_trade = Arc::new(PreTradeChecker::load(pre_trade, &info_client).await?);
 
-        let long_account = TradingAccount {
-            wallet: long_wallet,
-            exchange_client: long_exchange,
-            user_address: H160::from_str(&user_address_long)?,
-            active_trade: None,
-            is_long_account: true,
-            closed_trades: vec![],
-        };
+        let channels = assets
+            .into_iter()
+            .map(|(asset, long_params, short_params)| AssetChannels {
+                price_filter: PriceFilter::new(&asset, price_filter.clone()),
+                asset,
+                long_params,
+                short_params,
+                long_account: TradingAccount {
+                    wallet: long_wallet.clone(),
+                    exchange_client: long_exchange.clone(),
+                    user_address: user_address_long,
+                    active_trade: None,
+                    is_long_account: true,
+                    closed_trades: vec![],
+                    pre_trade: pre_trade.clone(),
+                },
+                short_account: TradingAccount {
+                    wallet: short_wallet.clone(),
+                    exchange_client: short_exchange.clone(),
+                    user_address: user_address_short,
+                    active_trade: None,
+                    is_long_account: false,
+                    closed_trades: vec![],
+                    pre_trade: pre_trade.clone(),
+                },
+                latest_price: 0.0,
+                total_pnl: 0.0,
+                funding: FundingBias::new(funding_config.clone()),
+                long_funding: FundingLedger::default(),
+                short_funding: FundingLedger::default(),
+                long_reentry: ReEntryGuard::new(reentry_config.clone()),
+                short_reentry: ReEntryGuard::new(reentry_config.clone()),
+                sizer,
+                long_equity: 0.0,
+                short_equity: 0.0,
+                volatility: PriceVol::default(),
+                regime: regime_config.clone().map(RegimeFilter::new),
+            })
+            .collect::<Vec<_>>();
 
-        let short_account = TradingAccount {
-            wallet: short_wallet,
-            exchange_client: short_exchange,
-            user_address: H160::from_str(&user_address_short)?,
-            active_trade: None,
-            is_long_account: false,
-            closed_trades: vec![],
-        };
+        let market_maker =
+            (strategy == Strategy::MarketMaking).then(|| MarketMaker::new(mm_params));
 
         Ok(Self {
-            asset,
-            params,
-            long_account,
-            short_account,
+            order_book: OrderBook::new(&channels[0].asset),
+            channels,
             info_client,
-            latest_price: 0.0,
-            total_pnl: 0.0,
+            strategy,
+            market_maker,
+            mm_reference,
+            grid_params,
+            grid: None,
+            schedule,
+            pair,
+            risk: RiskSupervisor::new(kill_switch),
+            watchdog: Watchdog::new(watchdog),
+            margin: MarginMonitor::new(margin),
+            exposure: ExposureGuard::new(exposure),
+            reconciler: Reconciler::new(reconcile),
+            shutdown,
         })
     }
 
+    /// Sync the open notional of both accounts into the pre-trade checks and the shared budget
+    async fn refresh_exposure(&self) {
+        let pre_trade = &self.channels[0].long_account.pre_trade;
+        for user in [
+            self.channels[0].long_account.user_address,
+            self.channels[0].short_account.user_address,
+        ] {
+            let synced = async {
+                let state = self.info_client.user_state(user).await?;
+                let open_orders = if pre_trade.budget().is_some() {
+                    self.info_client.open_orders(user).await?
+                } else {
+                    Vec::new()
+                };
+                pre_trade.sync_account(user, &state, &open_orders)
+            }
+            .await;
+            if let Err(e) = synced {
+                warn!("Failed to sync account notional of {:?}: {}", user, e);
+            }
+        }
+    }
+
+    /// Take the positions both accounts hold from the exchange; fills update them afterwards
+    async fn seed_exposure(&mut self) -> eyre::Result<()> {
+        if self.strategy != Strategy::DualChannel || !self.exposure.enabled() {
+            return Ok(());
+        }
+        let coins: Vec<String> =
+            self.channels.iter().map(|channel| channel.asset.clone()).collect();
+        for (is_long, user) in [
+            (true, self.channels[0].long_account.user_address),
+            (false, self.channels[0].short_account.user_address),
+        ] {
+            let state = self.info_client.user_state(user).await?;
+            self.exposure.sync(is_long, &state, &coins)?;
+        }
+        for coin in &coins {
+            info!("[{}] Net position of both accounts: {}", coin, self.exposure.net(coin));
+        }
+        Ok(())
+    }
+
+    /// Fetch the account value of both accounts for equity-based sizing
+    async fn refresh_equity(&mut self) {
+        if self.channels[0].sizer.is_none() {
+            return;
+        }
+        let long_address = self.channels[0].long_account.user_address;
+        let short_address = self.channels[0].short_account.user_address;
+        let equity = async {
+            let long_equity = account_equity(&self.info_client, long_address).await?;
+            let short_equity = account_equity(&self.info_client, short_address).await?;
+            Ok((long_equity, short_equity))
+        }
+        .await;
+        let Some((long_equity, short_equity)) =
+            equity.inspect_err(|e| warn!("Failed to fetch account equity: {}", e)).ok()
+        else {
+            return;
+        };
+        debug!("Account equity: LONG ${:.2}, SHORT ${:.2}", long_equity, short_equity);
+        for channel in &mut self.channels {
+            channel.long_equity = long_equity;
+            channel.short_equity = short_equity;
+        }
+    }
+
     /// Helper function to handle reconnection
     async fn handle_reconnection(&mut self, network: &BaseUrl) -> eyre::Result<()> {
         error!("Attempting to reconnect...");
@@ -133,50 +832,206 @@ impl DualAccountBot {
         Ok(())
     }
 
-    async fn start(&mut self, network: BaseUrl) -> eyre::Result<()> {
-        let (sender, mut receiver) = unbounded_channel();
+    /// Subscribe to every feed the strategy needs and watch its market data for staleness
+    async fn subscribe_all(&mut self, sender: &UnboundedSender<Message>) -> eyre::Result<()> {
+        let primary_asset = self.channels[0].asset.clone();
+        let long_address = self.channels[0].long_account.user_address;
+        let short_address = self.channels[0].short_account.user_address;
+        let now = Utc::now().timestamp();
 
         // Subscribe to market data
         self.info_client.subscribe(Subscription::AllMids, sender.clone()).await?;
+        for channel in &self.channels {
+            self.watchdog.watch(ALL_MIDS_FEED, &channel.asset, now);
+        }
 
         // Subscribe to user events for both accounts
         // self.info_client
         //     .subscribe(
-        //         Subscription::UserEvents { user: self.long_account.user_address },
+        //         Subscription::UserEvents { user: long_address },
         //         sender.clone(),
         //     )
         //     .await?;
 
         self.info_client
-            .subscribe(
-                Subscription::UserEvents { user: self.short_account.user_address },
-                sender.clone(),
-            )
+            .subscribe(Subscription::UserEvents { user: short_address }, sender.clone())
             .await?;
 
-        // Wait for initial price
+        if self.strategy == Strategy::DualChannel {
+            for channel in &self.channels {
+                self.info_client
+                    .subscribe(
+                        Subscription::ActiveAssetCtx { coin: channel.asset.clone() },
+                        sender.clone(),
+                    )
+                    .await?;
+                if let Some(regime) = &channel.regime {
+                    self.info_client
+                        .subscribe(
+                            Subscription::Candle {
+                                coin: channel.asset.clone(),
+                                interval: regime.config().interval.clone(),
+                            },
+                            sender.clone(),
+                        )
+                        .await?;
+                }
+            }
+        }
+
+        if matches!(self.strategy, Strategy::MarketMaking | Strategy::Grid) ||
+            (self.strategy == Strategy::DualChannel && self.exposure.enabled())
+        {
+            // Only one UserEvents subscription is allowed per connection, so order fills on the
+            // long account come through UserFills.
+            self.info_client
+                .subscribe(Subscription::UserFills { user: long_address }, sender.clone())
+                .await?;
+        }
+
+        if self.strategy == Strategy::MarketMaking &&
+            self.mm_reference == QuoteReference::Microprice
+        {
+            self.info_client
+                .subscribe(Subscription::L2Book { coin: primary_asset.clone() }, sender.clone())
+                .await?;
+            self.watchdog.watch(L2_BOOK_FEED, &primary_asset, now);
+        }
+
+        Ok(())
+    }
+
+    /// Pause, resubscribe or flatten the coins whose market data went stale
+    async fn check_watchdog(
+        &mut self,
+        network: &BaseUrl,
+        sender: &UnboundedSender<Message>,
+    ) -> eyre::Result<()> {
+        let events = self.watchdog.check(Utc::now().timestamp());
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        // A fresh connection, whether the websocket died or the SDK stalled
+        info!("Market data stale, resubscribing on a new connection");
+        self.handle_reconnection(network).await?;
+        self.subscribe_all(sender).await?;
+
+        let flatten: HashSet<&str> = events
+            .iter()
+            .filter(|event| event.level == Staleness::Flatten)
+            .map(|event| event.asset.as_str())
+            .collect();
+        if flatten.is_empty() {
+            return Ok(());
+        }
+        let mids = self.info_client.all_mids().await?;
+        for (index, channel) in self.channels.iter_mut().enumerate() {
+            if !flatten.contains(channel.asset.as_str()) {
+                continue;
+            }
+            let Some(mid) = mids.get(&channel.asset) else {
+                error!("[{}] No REST price to flatten at", channel.asset);
+                continue;
+            };
+            channel.latest_price = mid.parse()?;
+            warn!(
+                "[{}] Flattening on stale market data at {:.4}",
+                channel.asset, channel.latest_price
+            );
+            if index == 0 {
+                if let Some(mm) = self.market_maker.as_mut() {
+                    mm.cancel_all(&channel.long_account, &channel.asset).await?;
+                }
+                if let Some(grid) = self.grid.as_mut() {
+                    grid.cancel_all(&channel.long_account).await?;
+                }
+            }
+            channel.flatten().await?;
+            if index < 2 {
+                if let Some(pair) = self.pair.as_mut() {
+                    pair.on_exited();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn start(&mut self, network: BaseUrl) -> eyre::Result<()> {
+        let (sender, mut receiver) = unbounded_channel();
+        let long_address = self.channels[0].long_account.user_address;
+
+        self.subscribe_all(&sender).await?;
+
+        // Wait for initial price of every coin
         info!("Waiting for initial price data...");
-        loop {
+        while self.channels.iter().any(|channel| channel.latest_price <= 0.0) {
             let message = receiver.recv().await.unwrap();
             if let Message::AllMids(all_mids) = message {
-                if let Some(mid) = all_mids.data.mids.get(&self.asset) {
-                    self.latest_price = get_price(mid.parse()?, 0.001);
-                    if self.latest_price > 0.0 {
-                        info!("Initial price received: {}", self.latest_price);
-                        break;
+                for channel in &mut self.channels {
+                    if let Some(mid) = all_mids.data.mids.get(&channel.asset) {
+                        let price: f64 = mid.parse()?;
+                        if channel.price_filter.check(price, None).is_accepted() {
+                            channel.latest_price = price;
+                        }
                     }
                 }
             }
         }
+        for channel in &self.channels {
+            info!("Initial {} price received: {}", channel.asset, channel.latest_price);
+        }
 
-        // Open initial positions
-        let long_trade = create_trade(true, self.latest_price, &BotParams::from(&self.params));
-        self.long_account.open_position(long_trade, &self.asset).await?;
+        // Initial positions are opened by the first price update, once funding is known or not
+        self.refresh_equity().await;
+        self.refresh_exposure().await;
+        self.seed_exposure().await?;
 
-        let short_trade = create_trade(false, self.latest_price, &BotParams::from(&self.params));
-        self.short_account.open_position(short_trade, &self.asset).await?;
+        // Lay out (or resume) the grid and drop levels whose orders filled while we were down
+        if self.strategy == Strategy::Grid {
+            let primary = &self.channels[0];
+            let mut grid = Grid::load_or_new(
+                self.grid_params.clone(),
+                &primary.asset,
+                primary.latest_price,
+                &primary.long_account.pre_trade,
+            )?;
+            let open_oids: HashSet<u64> = self
+                .info_client
+                .open_orders(long_address)
+                .await?
+                .into_iter()
+                .filter(|order| order.coin == primary.asset)
+                .map(|order| order.oid)
+                .collect();
+            let state = self.info_client.user_state(long_address).await?;
+            let position = match state
+                .asset_positions
+                .iter()
+                .find(|asset| asset.position.coin == primary.asset)
+            {
+                Some(asset) => asset.position.szi.parse()?,
+                None => 0.0,
+            };
+            grid.reconcile(&open_oids, position)?;
+            grid.place_missing(&primary.long_account).await?;
+            self.grid = Some(grid);
+        }
 
         let mut stats_interval = interval(std::time::Duration::from_secs(STATS_INTERVAL_SECS));
+        let mut watchdog_interval =
+            interval(std::time::Duration::from_secs(WATCHDOG_INTERVAL_SECS));
+        let mut margin_interval =
+            interval(std::time::Duration::from_secs(self.margin.config().margin_poll_secs.max(1)));
+        let mut reconcile_interval = interval(std::time::Duration::from_secs(
+            self.reconciler.config().reconcile_secs.max(1),
+        ));
+        let budget = self.channels[0].long_account.pre_trade.budget().map(|budget| budget.config());
+        let mut budget_interval = interval(std::time::Duration::from_secs(
+            budget.map_or(STATS_INTERVAL_SECS, |config| config.budget_publish_secs.max(1)),
+        ));
+        let shares_budget = budget.is_some();
+        let mut shutdown_signal = ShutdownSignal::new()?;
 
         loop {
             let result = async {
@@ -184,60 +1039,657 @@ impl DualAccountBot {
                     Some(msg) = receiver.recv() => {
                         match msg {
                             Message::AllMids(all_mids) => {
-                                if let Some(mid) = all_mids.data.mids.get(&self.asset) {
-                                    self.latest_price = get_price(mid.parse()?, 0.001);
-                                    self.print_current_pnl();
-
-                                    let long_is_long_account = self.long_account.is_long_account;
-                                    check_account_position(
-                                        &mut self.long_account,
-                                        self.latest_price,
-                                        self.params.timeout_sec,
-                                        long_is_long_account,
-                                        &mut self.total_pnl,
-                                        &self.asset,
-                                        &BotParams::from(&self.params),
-                                    ).await?;
-
-                                    let short_is_long_account = self.short_account.is_long_account;
-                                    check_account_position(
-                                        &mut self.short_account,
-                                        self.latest_price,
-                                        self.params.timeout_sec,
-                                        short_is_long_account,
-                                        &mut self.total_pnl,
-                                        &self.asset,
-                                        &BotParams::from(&self.params),
-                                    ).await?;
+                                if self.strategy == Strategy::DualChannel &&
+                                    self.schedule.window_ended(Utc::now())
+                                {
+                                    info!("Trading window ended, flattening open trades");
+                                    for channel in &mut self.channels {
+                                        channel.flatten().await?;
+                                    }
                                 }
+                                for index in 0..self.channels.len() {
+                                    let channel = &mut self.channels[index];
+                                    if let Some(mid) = all_mids.data.mids.get(&channel.asset) {
+                                        self.watchdog.touch(
+                                            ALL_MIDS_FEED,
+                                            &channel.asset,
+                                            Utc::now().timestamp(),
+                                        );
+                                        let price: f64 = mid.parse()?;
+                                        let book = self
+                                            .order_book
+                                            .best_bid()
+                                            .zip(self.order_book.best_ask())
+                                            .filter(|_| self.order_book.coin() == channel.asset)
+                                            .map(|((bid, _), (ask, _))| (bid, ask));
+                                        if !channel.price_filter.check(price, book).is_accepted() {
+                                            continue;
+                                        }
+                                        channel.latest_price = price;
+                                        channel.volatility.update(Utc::now().timestamp(), price);
+                                        self.on_price_update(index).await?;
+                                    }
+                                }
+                                self.check_risk().await?;
                             }
                             Message::User(user_events) => {
                                 if let UserData::Fills(fills) = user_events.data {
                                     self.handle_fills(fills).await?;
                                 }
                             }
+                            Message::UserFills(user_fills) => {
+                                let is_snapshot = user_fills.data.is_snapshot.unwrap_or(false);
+                                if !is_snapshot && user_fills.data.user == long_address {
+                                    self.handle_long_fills(user_fills.data.fills)?;
+                                }
+                            }
+                            Message::L2Book(book) => {
+                                self.watchdog.touch(
+                                    L2_BOOK_FEED,
+                                    &book.data.coin,
+                                    Utc::now().timestamp(),
+                                );
+                                self.order_book.apply(&book.data);
+                            }
+                            Message::ActiveAssetCtx(ctx) => {
+                                if let Some(channel) = self
+                                    .channels
+                                    .iter_mut()
+                                    .find(|channel| channel.asset == ctx.data.coin)
+                                {
+                                    channel.funding.update(&ctx.data.ctx)?;
+                                }
+                            }
+                            Message::Candle(candle) => {
+                                if let Some(regime) = self
+                                    .channels
+                                    .iter_mut()
+                                    .find(|channel| channel.asset == candle.data.coin)
+                                    .and_then(|channel| channel.regime.as_mut())
+                                {
+                                    regime.on_candle(&Candle::try_from(&candle.data)?);
+                                }
+                            }
                             _ => {}
                         }
                     }
                     _ = stats_interval.tick() => {
-                        print_statistics(&self.long_account.closed_trades);
-                        print_statistics(&self.short_account.closed_trades);
+                        self.print_statistics();
+                        self.refresh_equity().await;
+                        self.refresh_exposure().await;
+                    }
+                    _ = watchdog_interval.tick() => {
+                        self.check_watchdog(&network, &sender).await?;
+                    }
+                    _ = margin_interval.tick() => {
+                        self.check_margin().await?;
+                    }
+                    _ = reconcile_interval.tick() => {
+                        self.reconcile().await?;
+                    }
+                    _ = budget_interval.tick(), if shares_budget => {
+                        // Also publishes both accounts' positions to the shared budget
+                        self.refresh_exposure().await;
+                    }
+                    reason = shutdown_signal.recv() => {
+                        return Ok(Some(reason));
+                    }
+                }
+                Ok(None)
+            }
+            .await;
+
+            let reason = match result {
+                Result::Ok(None) => continue,
+                Result::Ok(Some(reason)) => reason.to_string(),
+                Err(e) => {
+                    error!("Error occurred: {}", e);
+                    // self.handle_reconnection(&network).await?;
+                    format!("error: {}", e)
+                }
+            };
+            self.shutdown(&reason).await;
+            break Ok(());
+        }
+    }
+
+    /// Trades, inventory and grid levels still held, with the stop each should be left with
+    fn open_trades(&self) -> Vec<OpenTrade> {
+        let mut open_trades = Vec::new();
+        for channel in &self.channels {
+            for (label, account) in
+                [("LONG", &channel.long_account), ("SHORT", &channel.short_account)]
+            {
+                if let Some(trade) = &account.active_trade {
+                    open_trades.push(OpenTrade {
+                        label: label.to_string(),
+                        coin: channel.asset.clone(),
+                        size: trade.signed_size(),
+                        entry_price: trade.entry_price,
+                        // Pair legs carry no TP/SL (0.0)
+                        stop: Some(trade.sl_price).filter(|stop| *stop > 0.0),
+                        take_profit: Some(trade.tp_price).filter(|tp| *tp > 0.0),
+                    });
+                }
+            }
+        }
+        let primary = &self.channels[0];
+        if let Some(mm) = self.market_maker.as_ref().filter(|mm| mm.inventory() != 0.0) {
+            open_trades.push(OpenTrade {
+                label: "LONG".to_string(),
+                coin: primary.asset.clone(),
+                size: mm.inventory(),
+                entry_price: mm.avg_entry(),
+                stop: None,
+                take_profit: None,
+            });
+        }
+        if let Some(grid) = &self.grid {
+            for level in grid.state().levels.iter().filter(|level| level.state == LevelState::Open)
+            {
+                open_trades.push(OpenTrade {
+                    label: "LONG".to_string(),
+                    coin: primary.asset.clone(),
+                    size: level.trade.signed_size(),
+                    entry_price: level.trade.entry_price,
+                    stop: None,
+                    take_profit: Some(level.trade.tp_price),
+                });
+            }
+        }
+        open_trades
+    }
+
+    /// Cancel the quotes or grid orders, apply the close policy to the accounts, then print the
+    /// final statistics and persist what was left behind. Failures are logged, not returned.
+    async fn shutdown(&mut self, reason: &str) {
+        info!("Shutting down ({})...", reason);
+        let primary = &self.channels[0];
+        if let Some(mm) = self.market_maker.as_mut() {
+            if let Err(e) = mm.cancel_all(&primary.long_account, &primary.asset).await {
+                error!("Failed to cancel quotes: {}", e);
+            }
+        }
+        // Keeps the grid state on disk for the next run
+        if let Some(grid) = self.grid.as_mut() {
+            if let Err(e) = grid.cancel_all(&primary.long_account).await {
+                error!("Failed to cancel grid orders: {}", e);
+            }
+        }
+
+        let mut open_trades = self.open_trades();
+        let coins: Vec<String> =
+            self.channels.iter().map(|channel| channel.asset.clone()).collect();
+        let accounts = [
+            FlattenAccount {
+                label: "LONG",
+                user: primary.long_account.user_address,
+                exchange_client: &primary.long_account.exchange_client,
+            },
+            FlattenAccount {
+                label: "SHORT",
+                user: primary.short_account.user_address,
+                exchange_client: &primary.short_account.exchange_client,
+            },
+        ];
+        // Market making and grid only trade the long account
+        let accounts = if matches!(self.strategy, Strategy::MarketMaking | Strategy::Grid) {
+            &accounts[..1]
+        } else {
+            &accounts[..]
+        };
+        let completed = close_out(
+            &self.info_client,
+            &primary.long_account.pre_trade,
+            accounts,
+            &coins,
+            &open_trades,
+            &self.shutdown,
+        )
+        .await;
+
+        let flattened = completed && self.shutdown.close_policy == ClosePolicy::Flatten;
+        if flattened {
+            // Book the flattened trades at the last price, the fills aren't awaited
+            for channel in &mut self.channels {
+                for account in [&mut channel.long_account, &mut channel.short_account] {
+                    if let Some(mut trade) = account.active_trade.take() {
+                        trade.close_price = Some(channel.latest_price);
+                        channel.total_pnl += trade.pnl().unwrap_or_default();
+                        account.closed_trades.push(trade);
+                    }
+                }
+            }
+            open_trades.clear();
+        }
+        self.print_statistics();
+
+        let mut state = ShutdownState::new("hype-bot", reason, self.shutdown.close_policy);
+        state.completed = completed;
+        state.open_trades = open_trades;
+        state.closed_trades = self
+            .channels
+            .iter()
+            .map(|channel| {
+                channel.long_account.closed_trades.len() + channel.short_account.closed_trades.len()
+            })
+            .sum();
+        state.total_pnl = self.channels.iter().map(|channel| channel.total_pnl).sum();
+        if let Err(e) = state.save(&self.shutdown.state_path("hype-bot")) {
+            error!("Failed to write the shutdown state: {}", e);
+        }
+    }
+
+    /// Run the active strategy on a new price of `self.channels[index]`
+    async fn on_price_update(&mut self, index: usize) -> eyre::Result<()> {
+        let now = Utc::now();
+        let halted = self.risk.is_halted(now) ||
+            !self.watchdog.can_enter(&self.channels[index].asset, now.timestamp()) ||
+            self.reconciler.is_halted();
+        match self.strategy {
+            Strategy::DualChannel => {
+                self.check_exposure(index).await?;
+                let asset = &self.channels[index].asset;
+                let long_entries = !halted && !self.exposure.is_paused(asset, true);
+                let short_entries = !halted && !self.exposure.is_paused(asset, false);
+                self.channels[index].on_price(&self.schedule, long_entries, short_entries).await?;
+            }
+            Strategy::MarketMaking | Strategy::Grid if halted => {}
+            Strategy::MarketMaking if index == 0 => {
+                let primary = &self.channels[0];
+                let reference = match self.mm_reference {
+                    QuoteReference::Mid => primary.latest_price,
+                    QuoteReference::Microprice => {
+                        self.order_book.microprice().unwrap_or(primary.latest_price)
+                    }
+                };
+                if let Some(mm) = self.market_maker.as_mut() {
+                    mm.on_price(&primary.long_account, &primary.asset, reference).await?;
+                }
+            }
+            Strategy::Grid if index == 0 => {
+                // Re-place take-profits and re-armed entries (also retries rejected orders)
+                if let Some(grid) = self.grid.as_mut() {
+                    grid.place_missing(&self.channels[0].long_account).await?;
+                }
+            }
+            Strategy::Pair => self.on_pair_price(!halted).await?,
+            Strategy::MarketMaking | Strategy::Grid => {}
+        }
+        Ok(())
+    }
+
+    /// Sample the spread of the two coins and exit the pair, or enter it if `entries_allowed`
+    async fn on_pair_price(&mut self, entries_allowed: bool) -> eyre::Result<()> {
+        let Some(pair) = self.pair.as_mut() else {
+            return Ok(());
+        };
+        let now = Utc::now().timestamp();
+        let (price_a, price_b) = (self.channels[0].latest_price, self.channels[1].latest_price);
+        if !pair.update(now, price_a, price_b) {
+            return Ok(());
+        }
+
+        match pair.decide(now) {
+            PairAction::Hold => {}
+            PairAction::Enter { .. } if !entries_allowed => {}
+            PairAction::Enter { side, beta, zscore } => {
+                let params = pair.params();
+                let notional_a = params.amount * params.leverage;
+                info!(
+                    "[PAIR] Spread z-score {:.2}, beta {:.3}: {} {}, {} {}",
+                    zscore,
+                    beta,
+                    if side == PairSide::LongA { "long" } else { "short" },
+                    params.leg_a,
+                    if side == PairSide::LongA { "short" } else { "long" },
+                    params.leg_b
+                );
+                let mut opened = Vec::with_capacity(2);
+                let (long_index, short_index) =
+                    if side == PairSide::LongA { (0, 1) } else { (1, 0) };
+                for (index, is_long) in [(long_index, true), (short_index, false)] {
+                    let channel = &mut self.channels[index];
+                    let notional = if index == 0 { notional_a } else { notional_a * beta };
+                    let pre_trade = &channel.long_account.pre_trade;
+                    let entry_price = pre_trade.round_price(&channel.asset, channel.latest_price);
+                    let trade = Trade {
+                        direction: if is_long { Direction::Long } else { Direction::Short },
+                        entry_price,
+                        entry_time: now,
+                        size: pre_trade.round_size(&channel.asset, notional / entry_price),
+                        // Pair legs exit on the spread, not on TP/SL
+                        tp_price: 0.0,
+                        sl_price: 0.0,
+                        close_price: None,
+                    };
+                    let account = if is_long {
+                        &mut channel.long_account
+                    } else {
+                        &mut channel.short_account
+                    };
+                    match account.open_position(trade, &channel.asset).await {
+                        Result::Ok(true) => opened.push(index),
+                        Result::Ok(false) => {}
+                        Err(e) => error!("[PAIR] Failed to open {}: {}", channel.asset, e),
+                    }
+                }
+                // The pair is only held with both legs; a lone leg is flattened again
+                if opened.len() == 2 {
+                    pair.on_entered(side, beta, now);
+                } else {
+                    for index in opened {
+                        let channel = &mut self.channels[index];
+                        warn!("[PAIR] Other leg not placed, flattening {}", channel.asset);
+                        channel.flatten().await?;
+                    }
+                }
+            }
+            PairAction::Exit { reason, zscore } => {
+                info!("[PAIR] Exiting: {} (z-score {:?})", reason, zscore);
+                for channel in &mut self.channels[..2] {
+                    channel.flatten().await?;
+                }
+                pair.on_exited();
+            }
+        }
+        Ok(())
+    }
+
+    /// Feed the PnL of each account and of both together to the kill switch, and stop trading
+    /// on a breach
+    async fn check_risk(&mut self) -> eyre::Result<()> {
+        let now = Utc::now();
+        let long: f64 = self.channels.iter().map(|channel| channel.channel_pnl(true)).sum();
+        let short: f64 = self.channels.iter().map(|channel| channel.channel_pnl(false)).sum();
+        let mut breached = false;
+        for (label, pnl, aggregate) in
+            [("LONG", long, false), ("SHORT", short, false), ("ALL", long + short, true)]
+        {
+            breached |= self.risk.update(label, pnl, aggregate, now).is_some();
+        }
+        if !breached {
+            return Ok(());
+        }
+
+        let primary = &self.channels[0];
+        if let Some(mm) = self.market_maker.as_mut() {
+            mm.cancel_all(&primary.long_account, &primary.asset).await?;
+        }
+        if let Some(grid) = self.grid.as_mut() {
+            grid.cancel_all(&primary.long_account).await?;
+        }
+        for channel in &self.channels {
+            for account in [&channel.long_account, &channel.short_account] {
+                let cancelled = cancel_open_orders(
+                    &self.info_client,
+                    &account.exchange_client,
+                    account.user_address,
+                    &channel.asset,
+                )
+                .await?;
+                if cancelled > 0 {
+                    info!("[{}] Cancelled {} resting orders", channel.asset, cancelled);
+                }
+            }
+        }
+        if self.risk.config().flatten_on_breach {
+            for channel in &mut self.channels {
+                channel.flatten().await?;
+            }
+            if let Some(pair) = self.pair.as_mut() {
+                pair.on_exited();
+            }
+        }
+        Ok(())
+    }
+
+    /// Re-hedge `self.channels[index]` once its net exposure stayed above the limit past the
+    /// grace period: the leading leg is reduced by the net position. Pausing is left to
+    /// [`ExposureGuard::is_paused`].
+    async fn check_exposure(&mut self, index: usize) -> eyre::Result<()> {
+        let channel = &mut self.channels[index];
+        let now = Utc::now().timestamp();
+        let Some(breach) = self.exposure.check(&channel.asset, channel.latest_price, now) else {
+            return Ok(());
+        };
+        if breach.action != ExposureAction::Rehedge {
+            return Ok(());
+        }
+        let account = if breach.long_leads() {
+            &mut channel.long_account
+        } else {
+            &mut channel.short_account
+        };
+        let Some(trade) = account.active_trade else {
+            warn!(
+                "[{}] Net exposure ${:.2} is not from a trade of the bot, not re-hedging",
+                channel.asset, breach.net_usd
+            );
+            return Ok(());
+        };
+        let fraction = (breach.net.abs() / trade.size.abs()).min(1.0);
+        info!(
+            "[{}] Re-hedging: reducing the {} leg by {:.0}%",
+            channel.asset,
+            if breach.long_leads() { "LONG" } else { "SHORT" },
+            fraction * 100.0
+        );
+        reduce_position(
+            account,
+            channel.latest_price,
+            &channel.asset,
+            fraction,
+            &mut channel.total_pnl,
+        )
+        .await
+    }
+
+    /// Poll the clearinghouse state of both accounts and reduce or close the trades whose
+    /// liquidation price got too close relative to their stop. A pair is closed as a whole.
+    async fn check_margin(&mut self) -> eyre::Result<()> {
+        if !self.margin.enabled() {
+            return Ok(());
+        }
+        for (label, is_long) in [("LONG", true), ("SHORT", false)] {
+            let primary = &self.channels[0];
+            let user = if is_long {
+                primary.long_account.user_address
+            } else {
+                primary.short_account.user_address
+            };
+            let snapshot = match self.info_client.user_state(user).await {
+                Result::Ok(state) => match MarginSnapshot::from_state(&state) {
+                    Result::Ok(snapshot) => snapshot,
+                    Err(e) => {
+                        warn!("[{}] Invalid clearinghouse state: {}", label, e);
+                        continue;
                     }
-                    _ = signal::ctrl_c() => {
-                        info!("Shutting down...");
-                        return Err(eyre::eyre!("Shutting down"));
+                },
+                Err(e) => {
+                    warn!("[{}] Failed to fetch clearinghouse state: {}", label, e);
+                    continue;
+                }
+            };
+
+            let mut close_pair = false;
+            for channel in &mut self.channels {
+                let account =
+                    if is_long { &mut channel.long_account } else { &mut channel.short_account };
+                let Some(trade) = account.active_trade else { continue };
+                let held = HeldPosition {
+                    coin: &channel.asset,
+                    mark: channel.latest_price,
+                    // Pair legs have no stop (`sl_price` 0)
+                    stop: Some(trade.sl_price).filter(|stop| *stop > 0.0),
+                };
+                let Some(action) = self.margin.check(label, &snapshot, &held) else { continue };
+                if self.pair.is_some() {
+                    close_pair = true;
+                    continue;
+                }
+                match action {
+                    DeRisk::Close => {
+                        close_position(
+                            account,
+                            channel.latest_price,
+                            &channel.asset,
+                            &mut channel.total_pnl,
+                        )
+                        .await?
+                    }
+                    DeRisk::Reduce { fraction } => {
+                        reduce_position(
+                            account,
+                            channel.latest_price,
+                            &channel.asset,
+                            fraction,
+                            &mut channel.total_pnl,
+                        )
+                        .await?
                     }
                 }
-                Ok(())
+            }
+            if close_pair {
+                warn!("Closing the pair on margin risk");
+                for channel in &mut self.channels {
+                    channel.flatten().await?;
+                }
+                if let Some(pair) = self.pair.as_mut() {
+                    pair.on_exited();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Compare the trades, quotes and grid orders the bot tracks with each account's state on
+    /// the exchange and apply the drift policy
+    async fn reconcile(&mut self) -> eyre::Result<()> {
+        if !self.reconciler.enabled() {
+            return Ok(());
+        }
+        for (label, is_long) in [("LONG", true), ("SHORT", false)] {
+            // Market making and grid only trade the long account
+            if !is_long && matches!(self.strategy, Strategy::MarketMaking | Strategy::Grid) {
+                continue;
+            }
+            let primary = &self.channels[0];
+            let user = if is_long {
+                primary.long_account.user_address
+            } else {
+                primary.short_account.user_address
+            };
+            let fetched = async {
+                let state = self.info_client.user_state(user).await?;
+                let open_orders = self.info_client.open_orders(user).await?;
+                Ok((state, open_orders))
             }
             .await;
+            let (state, open_orders) = match fetched {
+                Result::Ok(fetched) => fetched,
+                Err(e) => {
+                    warn!("[{}] Failed to fetch account state: {}", label, e);
+                    continue;
+                }
+            };
+
+            if let Some(mm) = self.market_maker.as_mut() {
+                let orders = mm.open_oids();
+                let internal = InternalState {
+                    coin: &primary.asset,
+                    position: mm.inventory(),
+                    orders: Some(&orders),
+                };
+                let event = self.reconciler.check(label, &internal, &state, &open_orders)?;
+                if let Some(event) = event.filter(|event| event.policy == DriftPolicy::Correct) {
+                    let resting = resting_oids(&event, &orders);
+                    mm.correct(event.exchange, event.exchange_entry, &resting);
+                }
+                continue;
+            }
+            if let Some(grid) = self.grid.as_mut() {
+                let orders = grid.open_oids();
+                let internal = InternalState {
+                    coin: &primary.asset,
+                    position: grid.position(),
+                    orders: Some(&orders),
+                };
+                let event = self.reconciler.check(label, &internal, &state, &open_orders)?;
+                if let Some(event) = event.filter(|event| event.policy == DriftPolicy::Correct) {
+                    // Levels whose orders are gone advance as far as the position shows them
+                    // filled; the next price places their follow-up orders
+                    grid.reconcile(&resting_oids(&event, &orders), event.exchange)?;
+                }
+                continue;
+            }
 
-            if let Err(e) = result {
-                error!("Error occurred: {}", e);
-                break Ok(());
-                // self.handle_reconnection(&network).await?;
+            // Trades are sent without tracking their order ids, so only positions are compared
+            for channel in &mut self.channels {
+                let (account, params) = if is_long {
+                    (&mut channel.long_account, &channel.long_params)
+                } else {
+                    (&mut channel.short_account, &channel.short_params)
+                };
+                let position = account.active_trade.as_ref().map_or(0.0, Trade::signed_size);
+                let internal = InternalState { coin: &channel.asset, position, orders: None };
+                let event = self.reconciler.check(label, &internal, &state, &open_orders)?;
+                if let Some(event) = event.filter(|event| event.policy == DriftPolicy::Correct) {
+                    correct_trade(account, &event, &BotParams::from(params), channel.latest_price);
+                }
             }
         }
+        Ok(())
+    }
+
+    /// Per-coin statistics, then the aggregate over all coins
+    fn print_statistics(&self) {
+        for channel in &self.channels {
+            channel.print_statistics();
+        }
+        if self.channels.len() > 1 {
+            let all_trades: Vec<_> = self
+                .channels
+                .iter()
+                .flat_map(|channel| {
+                    channel
+                        .long_account
+                        .closed_trades
+                        .iter()
+                        .chain(&channel.short_account.closed_trades)
+                })
+                .copied()
+                .collect();
+            let total_pnl: f64 = self.channels.iter().map(|channel| channel.total_pnl).sum();
+            info!("[ALL] Total PnL over {} coins: ${:.4}", self.channels.len(), total_pnl);
+            print_statistics(&all_trades);
+        }
+    }
+
+    /// Book fills of our market-making quotes or grid orders on the long account
+    fn handle_long_fills(&mut self, fills: Vec<TradeInfo>) -> eyre::Result<()> {
+        if self.exposure.enabled() {
+            for fill in &fills {
+                self.exposure.on_fill(true, fill)?;
+            }
+        }
+        let primary = &mut self.channels[0];
+        for fill in fills.iter().filter(|fill| fill.coin == primary.asset) {
+            if let Some(mm) = self.market_maker.as_mut() {
+                mm.on_fill(&mut primary.long_account, fill, &mut primary.total_pnl)?;
+                info!(
+                    "Quote fill: {} {} at {} (inventory {:.4})",
+                    if fill.side == "B" { "Buy" } else { "Sell" },
+                    fill.sz,
+                    fill.px,
+                    mm.inventory()
+                );
+            }
+            if let Some(grid) = self.grid.as_mut() {
+                grid.on_fill(&mut primary.long_account, fill, &mut primary.total_pnl)?;
+            }
+        }
+        Ok(())
     }
 
     /// Handle fill events (optional / example usage)
@@ -247,11 +1699,16 @@ impl DualAccountBot {
             let price: f64 = fill.px.parse()?;
 
             debug!(
-                "Fill received: {} {} at {}",
+                "Fill received: {} {} {} at {}",
                 if fill.side == "B" { "Buy" } else { "Sell" },
                 amount,
+                fill.coin,
                 price
             );
+            // UserEvents is only subscribed for the short account
+            if self.exposure.enabled() {
+                self.exposure.on_fill(false, &fill)?;
+            }
         }
         Ok(())
     }
@@ -264,58 +1721,86 @@ impl DualAccountBot {
         self.info_client = InfoClient::new(None, Some(network.clone())).await?;
 
         // Attempt to recreate ExchangeClient for long account
-        self.long_account.exchange_client = ExchangeClient::new(
-            None,
-            self.long_account.wallet.clone(),
-            Some(network.clone()),
-            None,
-            None,
-        )
-        .await?;
+        let long_exchange = Arc::new(
+            ExchangeClient::new(
+                None,
+                self.channels[0].long_account.wallet.clone(),
+                Some(network.clone()),
+                None,
+                None,
+            )
+            .await?,
+        );
 
         // Attempt to recreate ExchangeClient for short account
-        self.short_account.exchange_client = ExchangeClient::new(
-            None,
-            self.short_account.wallet.clone(),
-            Some(network.clone()),
-            None,
-            None,
-        )
-        .await?;
+        let short_exchange = Arc::new(
+            ExchangeClient::new(
+                None,
+                self.channels[0].short_account.wallet.clone(),
+                Some(network.clone()),
+                None,
+                None,
+            )
+            .await?,
+        );
+
+        // Every coin shares the same client per account
+        for channel in &mut self.channels {
+            channel.long_account.exchange_client = long_exchange.clone();
+            channel.short_account.exchange_client = short_exchange.clone();
+        }
 
         info!("Reconnection successful.");
 
         Ok(())
     }
+}
 
-    /// Print the current PnL of the long and short accounts.
-    /// We calculate unrealized PnL as:
-    ///     PnL = (current_price - entry_price) / entry_price * 100  (for Long positions)
-    ///     PnL = (entry_price - current_price) / entry_price * 100  (for Short positions)
-    fn print_current_pnl(&self) {
-        let current_price = self.latest_price;
+/// The tracked orders of a drift event that are still resting on the exchange
+fn resting_oids(event: &DriftEvent, orders: &HashSet<u64>) -> HashSet<u64> {
+    orders.iter().filter(|oid| !event.missing_orders.contains(oid)).copied().collect()
+}
 
-        // Print LONG account PnL
-        if let Some(trade) = &self.long_account.active_trade {
-            let pnl = (current_price - trade.entry_price) / trade.entry_price * 100.0;
-            debug!(
-                "LONG Account: Entry Price={:.4}, Current Price={:.4}, PnL={:.4}%",
-                trade.entry_price, current_price, pnl
-            );
-        } else {
-            debug!("LONG Account: No active trade.");
+/// Take the exchange's position as the truth for `account`: resize, drop or adopt its trade.
+/// An adopted position gets the channel's TP/SL around its exchange entry price.
+fn correct_trade(
+    account: &mut TradingAccount,
+    event: &DriftEvent,
+    params: &BotParams,
+    latest_price: f64,
+) {
+    let direction = if event.exchange > 0.0 { Direction::Long } else { Direction::Short };
+    match account.active_trade.as_mut() {
+        Some(_) if event.exchange == 0.0 => {
+            warn!("[{}] {} trade no longer on the exchange, dropping it", event.label, event.coin);
+            account.active_trade = None;
         }
-
-        // Print SHORT account PnL
-        if let Some(trade) = &self.short_account.active_trade {
-            let pnl = (trade.entry_price - current_price) / trade.entry_price * 100.0;
-            debug!(
-                "SHORT Account: Entry Price={:.4}, Current Price={:.4}, PnL={:.4}",
-                trade.entry_price, current_price, pnl
+        Some(trade) => {
+            trade.size = event.exchange.abs();
+            trade.direction = direction;
+        }
+        None if event.exchange != 0.0 => {
+            let entry_price = event.exchange_entry.unwrap_or(latest_price);
+            let mut trade = create_trade(
+                direction == Direction::Long,
+                entry_price,
+                params,
+                &account.pre_trade,
+                &event.coin,
             );
-        } else {
-            debug!("SHORT Account: No active trade.");
+            trade.size = event.exchange.abs();
+            warn!(
+                "[{}] Adopting untracked {} position {} at {} (TP: {:.4}, SL: {:.4})",
+                event.label,
+                event.coin,
+                event.exchange,
+                entry_price,
+                trade.tp_price,
+                trade.sl_price
+            );
+            account.active_trade = Some(trade);
         }
+        None => {}
     }
 }
 
@@ -342,25 +1827,160 @@ async fn main() -> eyre::Result<()> {
     // Decide the network
     let network = if args.mainnet { BaseUrl::Mainnet } else { BaseUrl::Testnet };
 
-    // Our simulation parameters
-    let params = SimParams {
-        amount: args.amount,
-        leverage: args.leverage,
-        tp_percent: args.tp_percent,
-        sl_percent: args.sl_percent,
-        timeout_sec: args.timeout_sec,
+    if let Some(Command::Flatten { coin, config }) = &args.command {
+        let info_client = InfoClient::new(None, Some(network)).await?;
+        let pre_trade = PreTradeChecker::load(args.pre_trade.clone(), &info_client).await?;
+        let long_exchange =
+            ExchangeClient::new(None, long_wallet, Some(network), None, None).await?;
+        let short_exchange =
+            ExchangeClient::new(None, short_wallet, Some(network), None, None).await?;
+        let accounts = [
+            FlattenAccount {
+                label: "LONG",
+                user: H160::from_str(&user_address_long)?,
+                exchange_client: &long_exchange,
+            },
+            FlattenAccount {
+                label: "SHORT",
+                user: H160::from_str(&user_address_short)?,
+                exchange_client: &short_exchange,
+            },
+        ];
+        let report = flatten_all(&info_client, &pre_trade, &accounts, coin, config).await?;
+        if !report.is_flat() {
+            return Err(eyre::eyre!(
+                "{} position(s) still open after flattening",
+                report.residual.len()
+            ));
+        }
+        return Ok(());
+    }
+
+    let file_config: FileConfig = match &args.config {
+        Some(path) => toml::from_str(&std::fs::read_to_string(path)?)?,
+        None => FileConfig::default(),
+    };
+    let asset_names = if !args.asset.is_empty() {
+        args.asset.clone()
+    } else if !file_config.assets.is_empty() {
+        file_config.assets.clone()
+    } else {
+        vec!["HYPE".to_string()]
+    };
+    match args.strategy {
+        Strategy::DualChannel => {}
+        Strategy::Pair if asset_names.len() != 2 => {
+            return Err(eyre::eyre!("Pair trades exactly two assets, e.g. --asset ETH,BTC"));
+        }
+        Strategy::Pair => {}
+        _ if asset_names.len() > 1 => {
+            return Err(eyre::eyre!("{:?} trades a single asset", args.strategy));
+        }
+        _ => {}
+    }
+
+    // Our simulation parameters, per coin and channel
+    let long_cli = ChannelConfig {
+        enabled: None,
+        amount: args.long_amount,
+        leverage: args.long_leverage,
+        tp_percent: args.long_tp_percent,
+        sl_percent: args.long_sl_percent,
+        timeout_sec: args.long_timeout_sec,
+    };
+    let short_cli = ChannelConfig {
+        enabled: None,
+        amount: args.short_amount,
+        leverage: args.short_leverage,
+        tp_percent: args.short_tp_percent,
+        sl_percent: args.short_sl_percent,
+        timeout_sec: args.short_timeout_sec,
     };
+    let mut assets = Vec::with_capacity(asset_names.len());
+    for asset in asset_names {
+        let coin = file_config.coins.get(&asset);
+        let long_file = coin.map_or(file_config.long, |coin| coin.long.or(&file_config.long));
+        let short_file = coin.map_or(file_config.short, |coin| coin.short.or(&file_config.short));
+        let long_params = SimParams::resolve(&args, &long_file, long_cli, args.disable_long);
+        let short_params = SimParams::resolve(&args, &short_file, short_cli, args.disable_short);
+        if args.strategy == Strategy::DualChannel && !long_params.enabled && !short_params.enabled {
+            return Err(eyre::eyre!("Both channels are disabled for {}", asset));
+        }
+        info!("[{}] LONG channel: {:?}", asset, long_params);
+        info!("[{}] SHORT channel: {:?}", asset, short_params);
+        assets.push((asset, long_params, short_params));
+    }
+    let primary_asset = assets[0].0.clone();
 
     info!(
-        "Starting dual-account bot on {} for {}",
+        "Starting dual-account bot ({:?}) on {} for {}",
+        args.strategy,
         if args.mainnet { "mainnet" } else { "testnet" },
-        args.asset
+        assets.iter().map(|(asset, _, _)| asset.as_str()).collect::<Vec<_>>().join(", ")
     );
 
+    let mm_params = MarketMakingParams {
+        amount: args.amount,
+        leverage: args.leverage,
+        spread_bps: args.mm_spread_bps,
+        skew_bps: args.mm_skew_bps,
+        requote_bps: args.mm_requote_bps,
+        max_inventory: args.mm_max_inventory,
+    };
+
+    let grid_params = GridParams {
+        levels: args.grid_levels,
+        step_percent: args.grid_step_percent,
+        amount: args.amount,
+        leverage: args.leverage,
+        state_path: args
+            .grid_state_path
+            .unwrap_or_else(|| format!(".cache/grid_{}.json", primary_asset)),
+    };
+
+    let pair = (args.strategy == Strategy::Pair).then(|| {
+        PairTrader::new(PairParams {
+            leg_a: assets[0].0.clone(),
+            leg_b: assets[1].0.clone(),
+            hedge: match args.pair_hedge {
+                HedgeMode::Fixed => HedgeRatio::Fixed { beta: args.pair_beta },
+                HedgeMode::RollingOls => HedgeRatio::RollingOls { periods: args.pair_ols_periods },
+            },
+            sample_secs: args.pair_sample_secs,
+            lookback: args.pair_lookback,
+            entry_z: args.pair_entry_z,
+            exit_z: args.pair_exit_z,
+            max_hold_secs: args.pair_max_hold_secs,
+            amount: args.amount,
+            leverage: args.leverage,
+        })
+    });
+
     // Create the bot
     let mut bot = DualAccountBot::new(
-        args.asset,
-        params,
+        assets,
+        args.strategy,
+        mm_params,
+        args.mm_reference,
+        grid_params,
+        Schedule::new(&file_config.schedule)?,
+        FundingBiasConfig {
+            threshold: args.funding_threshold,
+            action: args.funding_action,
+            paying_scale: args.funding_paying_scale,
+        },
+        args.reentry,
+        args.sizing.sizer()?.or(file_config.sizing),
+        file_config.regime,
+        pair,
+        args.kill_switch,
+        args.watchdog,
+        args.price_filter,
+        args.pre_trade,
+        args.margin,
+        args.exposure,
+        args.reconcile,
+        args.shutdown,
         long_wallet,
         short_wallet,
         user_address_long,
@@ -20,7 +20,12 @@ use tokio::{
 use tracing::{debug, error, info, instrument, warn};
 use tracing_subscriber::{self, EnvFilter};
 
-use dual_channel_bot::caching::{store_asset_to_cache, store_candle_to_cache, store_tick_to_cache};
+use dual_channel_bot::{
+    caching::{
+        store_asset_to_cache, store_candle_to_cache, store_tick_to_cache, store_trades_to_cache,
+    },
+    tape::TapeTrade,
+};
 
 #[derive(Error, Debug)]
 pub enum MarketDataError {
@@ -78,6 +83,14 @@ impl SubscriptionManager {
                 self.subscription_ids.push(sub_id);
                 debug!("Subscribed to {asset} {interval} candles with ID: {sub_id}");
             }
+            let trades_id = self
+                .info_client
+                .subscribe(Subscription::Trades { coin: asset.clone() }, self.sender.clone())
+                .await
+                .map_err(|e| MarketDataError::SubscriptionError(e.to_string()))?;
+            self.subscription_ids.push(trades_id);
+            debug!("Subscribed to {asset} trades with ID: {trades_id}");
+
             let sub = self
                 .info_client
                 .subscribe(
@@ -126,6 +139,7 @@ struct MessageHandler {
     // Batching buffers
     tick_buffer: HashMap<String, Vec<f64>>,
     candle_buffer: HashMap<String, Vec<CandleData>>,
+    trade_buffer: HashMap<String, Vec<TapeTrade>>,
     last_flush: Instant,
     batch_size: usize,
     flush_interval: Duration,
@@ -141,6 +155,7 @@ impl MessageHandler {
             last_reconnect: Instant::now(),
             tick_buffer: HashMap::new(),
             candle_buffer: HashMap::new(),
+            trade_buffer: HashMap::new(),
             last_flush: Instant::now(),
             batch_size: 100,                        // Adjust based on your needs
             flush_interval: Duration::from_secs(5), // Flush every 5 seconds
@@ -166,6 +181,13 @@ impl MessageHandler {
             }
         }
 
+        // Flush trade buffers
+        for (asset, trades) in self.trade_buffer.drain() {
+            let trade_cache_path = format!("{}/{}_trades", self.cache_dir, asset);
+            store_trades_to_cache(&trade_cache_path, &trades)
+                .map_err(|e| MarketDataError::CacheError(e.to_string()))?;
+        }
+
         info!("Flushed buffers");
         self.last_flush = Instant::now();
         Ok(())
@@ -196,6 +218,16 @@ impl MessageHandler {
                     }
                 }
             }
+            Message::Trades(trades) => {
+                for trade in &trades.data {
+                    match TapeTrade::try_from(trade) {
+                        Ok(parsed) => {
+                            self.trade_buffer.entry(trade.coin.clone()).or_default().push(parsed)
+                        }
+                        Err(e) => warn!("Skipping unparseable trade {:?}: {}", trade, e),
+                    }
+                }
+            }
             Message::ActiveAssetCtx(active_asset_ctx) => {
                 debug!("Active asset context: {:?}", active_asset_ctx);
                 let asset = active_asset_ctx.data.coin.clone();
@@ -211,12 +243,14 @@ impl MessageHandler {
         // Check if we need to flush based on buffer size or time
         let should_flush = self.tick_buffer.values().any(|v| v.len() >= self.batch_size) ||
             self.candle_buffer.values().any(|v| v.len() >= self.batch_size) ||
+            self.trade_buffer.values().any(|v| v.len() >= self.batch_size) ||
             self.last_flush.elapsed() >= self.flush_interval;
 
         if should_flush {
             // Take ownership of the buffers and replace them with empty ones
             let tick_buffer = std::mem::take(&mut self.tick_buffer);
             let candle_buffer = std::mem::take(&mut self.candle_buffer);
+            let trade_buffer = std::mem::take(&mut self.trade_buffer);
             let cache_dir = self.cache_dir.clone();
 
             tokio::spawn(async move {
@@ -224,6 +258,7 @@ impl MessageHandler {
                 let mut temp_handler = MessageHandler::new(vec![], cache_dir, 0);
                 temp_handler.tick_buffer = tick_buffer;
                 temp_handler.candle_buffer = candle_buffer;
+                temp_handler.trade_buffer = trade_buffer;
 
                 if let Err(e) = temp_handler.flush_buffers().await {
                     error!("Error flushing buffers: {}", e);
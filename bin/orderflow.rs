@@ -1,4 +1,8 @@
-use std::{collections::VecDeque, fs, str::FromStr};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    str::FromStr,
+};
 
 use chrono::Utc;
 use dotenvy::dotenv;
@@ -8,14 +12,35 @@ use hyperliquid_rust_sdk::{
     ExchangeDataStatus, ExchangeResponseStatus, InfoClient, Message, Subscription, UserData,
 };
 use serde::Deserialize;
-use tokio::{sync::mpsc::unbounded_channel, time::interval};
+use tokio::{
+    sync::mpsc::{unbounded_channel, UnboundedSender},
+    time::interval,
+};
 use tracing::{debug, error, info, trace, warn};
 use tracing_subscriber::EnvFilter;
 
 use dual_channel_bot::{
     caching::store_candle_to_cache,
-    get_price, store_tick_to_cache,
-    utils::{print_statistics, Direction, Trade},
+    flatten::FlattenAccount,
+    indicators::{Vwap, VwapPrice, VwapWindow},
+    margin::{DeRisk, HeldPosition, MarginConfig, MarginMonitor, MarginSnapshot},
+    orderbook::{EntryPrice, OrderBook},
+    pretrade::{OrderCheck, PreTradeChecker, PreTradeConfig},
+    price_filter::{PriceFilter, PriceFilterConfig},
+    reconcile::{DriftEvent, DriftPolicy, InternalState, ReconcileConfig, Reconciler},
+    regime::{RegimeConfig, RegimeFilter},
+    risk::{KillSwitchConfig, RiskSupervisor},
+    schedule::{Schedule, ScheduleConfig},
+    shutdown::{close_out, ClosePolicy, OpenTrade, ShutdownConfig, ShutdownSignal, ShutdownState},
+    signal::{ImbalanceConfig, ImbalanceTracker, SignalConfig, SignalScore},
+    sizing::{PriceVol, Sizer, SizingInput},
+    store_tick_to_cache,
+    tape::{TapeConfig, TapeTrade, TradeTape},
+    utils::{cancel_open_orders, print_statistics, Direction, Trade},
+    watchdog::{
+        Staleness, Watchdog, WatchdogConfig, ALL_MIDS_FEED, L2_BOOK_FEED, WATCHDOG_INTERVAL_SECS,
+    },
+    Candle, Indicator,
 };
 
 const MAKER_FEE: f64 = 0.0001; // 0.01%
@@ -27,6 +52,32 @@ struct Config {
     bot: BotConfig,
     risk: RiskConfig,
     vwap: VwapConfig,
+    #[serde(default)]
+    imbalance: ImbalanceConfig,
+    #[serde(default)]
+    signal: SignalConfig,
+    #[serde(default)]
+    tape: TapeConfig,
+    #[serde(default)]
+    schedule: ScheduleConfig,
+    /// Position sizing; defaults to `risk_per_trade` of capital at `leverage`
+    sizing: Option<Sizer>,
+    /// Scale or pause entries by market regime
+    regime: Option<RegimeConfig>,
+    #[serde(default)]
+    kill_switch: KillSwitchConfig,
+    #[serde(default)]
+    watchdog: WatchdogConfig,
+    #[serde(default)]
+    price_filter: PriceFilterConfig,
+    #[serde(default)]
+    pre_trade: PreTradeConfig,
+    #[serde(default)]
+    margin: MarginConfig,
+    #[serde(default)]
+    reconcile: ReconcileConfig,
+    #[serde(default)]
+    shutdown: ShutdownConfig,
 }
 
 #[derive(Debug, Deserialize)]
@@ -37,6 +88,9 @@ struct BotConfig {
     leverage: f64,
     decimals: u32,
     test_mode: bool,
+    /// Price used for entries: "mid", "microprice", "best_passive" or "best_aggressive"
+    #[serde(default)]
+    entry_price: EntryPrice,
 }
 
 #[derive(Debug, Deserialize)]
@@ -49,38 +103,58 @@ struct RiskConfig {
 struct VwapConfig {
     hourly_periods: usize,
     five_min_periods: usize,
+    /// How the slow (hourly) VWAP is windowed; the 5m VWAP is always rolling
+    #[serde(default)]
+    anchor: VwapAnchor,
+    /// Session length for `anchor = "custom"`
+    #[serde(default = "default_anchor_length_secs")]
+    anchor_length_secs: u64,
+    /// Shift of the session boundary from the UTC epoch alignment
+    #[serde(default)]
+    anchor_offset_secs: u64,
+    #[serde(default)]
+    price: VwapPrice,
+    /// Width of the VWAP bands in standard deviations
+    #[serde(default = "default_band_std")]
+    band_std: f64,
 }
 
-#[derive(Debug)]
-/// Simple struct to hold order book updates: bids and asks
-struct OrderBookUpdate {
-    bids: Vec<(f64, f64)>, // (price, size)
-    asks: Vec<(f64, f64)>, // (price, size)
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum VwapAnchor {
+    /// Last `hourly_periods` candles
+    #[default]
+    Rolling,
+    /// Reset at 00:00 UTC (plus `anchor_offset_secs`)
+    UtcDay,
+    /// Reset every `anchor_length_secs` (plus `anchor_offset_secs`)
+    Custom,
 }
 
-#[derive(Debug)]
-/// A basic VWAP struct that aggregates sum of price*volume over total volume
-struct VWAP {
-    sum_pv: f64,
-    sum_v: f64,
+fn default_anchor_length_secs() -> u64 {
+    86_400
 }
 
-impl VWAP {
-    fn new() -> Self {
-        VWAP { sum_pv: 0.0, sum_v: 0.0 }
-    }
+fn default_band_std() -> f64 {
+    2.0
+}
 
-    fn update(&mut self, price: f64, volume: f64) {
-        self.sum_pv += price * volume;
-        self.sum_v += volume;
+impl VwapConfig {
+    fn hourly_window(&self) -> VwapWindow {
+        match self.anchor {
+            VwapAnchor::Rolling => VwapWindow::Rolling { periods: self.hourly_periods },
+            VwapAnchor::UtcDay => {
+                VwapWindow::Session { length_secs: 86_400, offset_secs: self.anchor_offset_secs }
+            }
+            VwapAnchor::Custom => VwapWindow::Session {
+                length_secs: self.anchor_length_secs,
+                offset_secs: self.anchor_offset_secs,
+            },
+        }
     }
 
-    fn value(&self) -> f64 {
-        if self.sum_v > 0.0 {
-            self.sum_pv / self.sum_v
-        } else {
-            0.0
-        }
+    fn five_min_window(&self) -> VwapWindow {
+        VwapWindow::Rolling { periods: self.five_min_periods }
     }
 }
 
@@ -96,14 +170,49 @@ pub struct OrderFlowTradingBot {
     closed_trades: Vec<Trade>,
 
     // Candle buffers
-    hourly_candles: VecDeque<CandleData>,
-    five_min_candles: VecDeque<CandleData>,
-    last_hourly_candle_ts: i64, // Timestamp of last processed hourly candle
-    last_five_min_candle_ts: i64, // Timestamp of last processed 5min candle
+    hourly_candles: VecDeque<Candle>,
+    five_min_candles: VecDeque<Candle>,
+    /// Latest update of the candle still forming, per interval
+    pending_candles: HashMap<String, (CandleData, Candle)>,
 
     // VWAP trackers
-    hourly_vwap: VWAP,
-    five_min_vwap: VWAP,
+    hourly_vwap: Vwap,
+    five_min_vwap: Vwap,
+
+    // Local L2 book and its smoothed imbalance
+    order_book: OrderBook,
+    imbalance: ImbalanceTracker,
+
+    // Aggressor flow from the trades feed
+    tape: TradeTape,
+
+    // Windows in which new trades may be opened
+    schedule: Schedule,
+
+    // Sizing and the volatility estimate it may need
+    sizer: Sizer,
+    volatility: PriceVol,
+
+    // Trend/range classifier scaling entries, when configured
+    regime: Option<RegimeFilter>,
+
+    // Daily loss / drawdown kill switch
+    risk: RiskSupervisor,
+
+    // Last mids / book update, to pause and flatten on stale market data
+    watchdog: Watchdog,
+
+    // Drops outlier mids before they reach the strategy and the tick cache
+    price_filter: PriceFilter,
+
+    // Validates every order before it is sent
+    pre_trade: PreTradeChecker,
+
+    // Liquidation distance of the open trade, polled from the clearinghouse state
+    margin: MarginMonitor,
+
+    // Compares `current_position` and the open trade with the exchange
+    reconciler: Reconciler,
 
     // Clients & user address
     info_client: InfoClient,
@@ -143,6 +252,24 @@ impl OrderFlowTradingBot {
             None
         };
 
+        let hourly_vwap = Vwap::new(config.vwap.hourly_window(), config.vwap.price);
+        let five_min_vwap = Vwap::new(config.vwap.five_min_window(), config.vwap.price);
+        let order_book = OrderBook::new(&config.bot.asset);
+        let imbalance = ImbalanceTracker::new(config.imbalance.clone());
+        let tape = TradeTape::new(config.tape.clone());
+        let schedule = Schedule::new(&config.schedule)?;
+        let sizer = config.sizing.unwrap_or(Sizer::EquityFraction {
+            fraction: config.bot.risk_per_trade,
+            leverage: config.bot.leverage,
+        });
+        let regime = config.regime.clone().map(RegimeFilter::new);
+        let risk = RiskSupervisor::new(config.kill_switch.clone());
+        let watchdog = Watchdog::new(config.watchdog.clone());
+        let price_filter = PriceFilter::new(&config.bot.asset, config.price_filter.clone());
+        let pre_trade = PreTradeChecker::load(config.pre_trade.clone(), &info_client).await?;
+        let margin = MarginMonitor::new(config.margin.clone());
+        let reconciler = Reconciler::new(config.reconcile.clone());
+
         Ok(Self {
             initial_capital: config.bot.capital,
             capital: config.bot.capital,
@@ -155,11 +282,34 @@ impl OrderFlowTradingBot {
 
             hourly_candles: VecDeque::with_capacity(24),
             five_min_candles: VecDeque::with_capacity(12),
-            last_hourly_candle_ts: 0,
-            last_five_min_candle_ts: 0,
+            pending_candles: HashMap::new(),
+
+            hourly_vwap,
+            five_min_vwap,
+
+            order_book,
+            imbalance,
+
+            tape,
 
-            hourly_vwap: VWAP::new(),
-            five_min_vwap: VWAP::new(),
+            schedule,
+
+            sizer,
+            volatility: PriceVol::default(),
+
+            regime,
+
+            risk,
+
+            watchdog,
+
+            price_filter,
+
+            pre_trade,
+
+            margin,
+
+            reconciler,
 
             info_client,
             exchange_client,
@@ -167,20 +317,39 @@ impl OrderFlowTradingBot {
         })
     }
 
-    /// Calculate position size based on risk and capital
-    fn calculate_position_size(&self, entry_price: f64) -> f64 {
-        let risk_amount = self.capital * self.config.bot.risk_per_trade;
-        let position_value = risk_amount * self.config.bot.leverage;
-        position_value / entry_price
+    /// Calculate position size from current capital with the configured sizer
+    fn calculate_position_size(&self, entry_price: f64, stop_price: f64) -> eyre::Result<f64> {
+        self.sizer.size(&SizingInput {
+            price: entry_price,
+            equity: self.capital,
+            stop_price: Some(stop_price),
+            volatility: self.volatility.annualized(),
+        })
     }
 
-    /// Place an order - either real or simulated
-    async fn place_order(&self, size: f64, price: f64) -> eyre::Result<()> {
+    /// Place an order - either real or simulated - once it passes the pre-trade checks.
+    /// `reduce_only` orders may only shrink the open trade.
+    async fn place_order(&self, size: f64, price: f64, reduce_only: bool) -> eyre::Result<()> {
         let is_buy = size > 0.0;
+        let position = self.current_trade.as_ref().map_or(0.0, |trade| trade.size);
+        let user = self.user_address.unwrap_or_default();
+        self.pre_trade.set_account_notional(user, position.abs() * self.latest_mid_price);
+        let check = OrderCheck {
+            asset: &self.config.bot.asset,
+            is_buy,
+            price,
+            size,
+            reduce_only,
+            mid: Some(self.latest_mid_price),
+            position,
+        };
+        let reservation = self.pre_trade.check(user, &check)?;
         let fees = if is_buy { TAKER_FEE } else { MAKER_FEE };
         let fee_amount = price * size.abs() * fees;
 
         if self.config.bot.test_mode {
+            // Nothing reaches the exchange, so nothing may hold the shared budget
+            self.pre_trade.release(reservation);
             info!(
                 "[TEST] {} order: size={}, price={}, fees={}",
                 if is_buy { "Buy" } else { "Sell" },
@@ -193,7 +362,7 @@ impl OrderFlowTradingBot {
             let order_request = ClientOrderRequest {
                 asset: self.config.bot.asset.clone(),
                 is_buy,
-                reduce_only: false,
+                reduce_only,
                 limit_px: price,
                 sz: size.abs(),
                 cloid: None,
@@ -213,7 +382,7 @@ impl OrderFlowTradingBot {
                                     price
                                 );
                             }
-                            ExchangeDataStatus::Resting(_) => {
+                            ExchangeDataStatus::Resting(resting) => {
                                 info!(
                                     "Order resting: {} {} {} at {}",
                                     if is_buy { "Buy" } else { "Sell" },
@@ -221,9 +390,11 @@ impl OrderFlowTradingBot {
                                     self.config.bot.asset,
                                     price
                                 );
+                                self.pre_trade.on_resting(reservation, resting.oid);
                             }
                             ExchangeDataStatus::Error(e) => {
                                 error!("Error placing order: {}", e);
+                                self.pre_trade.release(reservation);
                             }
                             _ => {}
                         }
@@ -231,9 +402,11 @@ impl OrderFlowTradingBot {
                 }
                 Ok(ExchangeResponseStatus::Err(e)) => {
                     error!("Error placing order: {}", e);
+                    self.pre_trade.release(reservation);
                 }
                 Err(e) => {
                     error!("Error placing order: {}", e);
+                    self.pre_trade.release(reservation);
                 }
             }
             Ok(())
@@ -249,9 +422,35 @@ impl OrderFlowTradingBot {
             warn!("No valid mid price available to enter trade.");
             return;
         }
+        if let Err(block) = self.schedule.check(Utc::now()) {
+            debug!("Not entering trade: {}", block);
+            return;
+        }
+        if self.risk.is_halted(Utc::now()) {
+            debug!("Not entering trade: kill switch engaged");
+            return;
+        }
+        if !self.watchdog.can_enter(&self.config.bot.asset, Utc::now().timestamp()) {
+            debug!("Not entering trade: market data is stale");
+            return;
+        }
+        if self.reconciler.is_halted() {
+            debug!("Not entering trade: halted on position drift");
+            return;
+        }
 
-        let entry_price = self.latest_mid_price;
-        let position_size = self.calculate_position_size(entry_price) * signal.signum();
+        let entry_price =
+            match self.order_book.entry_price(self.config.bot.entry_price, signal > 0.0) {
+                Some(px) => px,
+                None => {
+                    debug!(
+                        "No book available for {:?} entry, using mid price",
+                        self.config.bot.entry_price
+                    );
+                    self.latest_mid_price
+                }
+            };
+        let entry_price = self.pre_trade.round_price(&self.config.bot.asset, entry_price);
         let direction = if signal > 0.0 { Direction::Long } else { Direction::Short };
 
         // Calculate stop loss and take profit based on risk config
@@ -261,6 +460,23 @@ impl OrderFlowTradingBot {
             entry_price * (1.0 + self.config.risk.stop_loss)
         };
 
+        let regime_scale = self.regime.as_ref().map_or(1.0, RegimeFilter::scale);
+        if regime_scale <= 0.0 {
+            debug!("Not entering trade: disabled by the market regime");
+            return;
+        }
+
+        let position_size = match self.calculate_position_size(entry_price, stop_loss) {
+            Ok(size) => {
+                self.pre_trade.round_size(&self.config.bot.asset, size * regime_scale) *
+                    signal.signum()
+            }
+            Err(e) => {
+                debug!("Not entering trade: {}", e);
+                return;
+            }
+        };
+
         let take_profit = if signal > 0.0 {
             entry_price * (1.0 + self.config.risk.take_profit)
         } else {
@@ -281,7 +497,7 @@ impl OrderFlowTradingBot {
         );
 
         // Place the order
-        if let Err(e) = self.place_order(position_size, entry_price).await {
+        if let Err(e) = self.place_order(position_size, entry_price, false).await {
             error!("Failed to place opening order: {}", e);
             return;
         }
@@ -310,40 +526,96 @@ impl OrderFlowTradingBot {
     /// Exit trade and update PnL
     async fn exit_trade(&mut self, exit_price: f64) {
         debug!("Exiting trade at price: {}", exit_price);
-        if let Some(mut trade) = self.current_trade.take() {
+        let exit_price = self.pre_trade.round_price(&self.config.bot.asset, exit_price);
+        if let Some(trade) = self.current_trade {
             let offset_size = -trade.size;
 
-            // Calculate PnL
-            let price_diff = exit_price - trade.entry_price;
-            let pnl = if trade.direction == Direction::Long {
-                price_diff * trade.size
-            } else {
-                -price_diff * trade.size
-            };
+            // Place exit order; the trade stays open if it fails
+            if let Err(e) = self.place_order(offset_size, exit_price, true).await {
+                error!("Failed to place exit order: {}", e);
+                return;
+            }
+            self.current_trade = None;
+            self.book_exit(trade, exit_price);
+        }
+    }
 
-            // Calculate fees
-            let entry_fees = trade.entry_price * trade.size.abs() * TAKER_FEE;
-            let exit_fees = exit_price * trade.size.abs() * MAKER_FEE;
-            let total_fees = entry_fees + exit_fees;
+    /// Book the PnL and fees of a trade closed at `exit_price`
+    fn book_exit(&mut self, mut trade: Trade, exit_price: f64) {
+        // Calculate PnL (the size is negative for shorts)
+        let pnl = (exit_price - trade.entry_price) * trade.size;
 
-            // Update capital
-            self.capital += pnl - total_fees;
+        // Calculate fees
+        let entry_fees = trade.entry_price * trade.size.abs() * TAKER_FEE;
+        let exit_fees = exit_price * trade.size.abs() * MAKER_FEE;
+        let total_fees = entry_fees + exit_fees;
 
-            // Update trade with close price
-            trade.close_price = Some(exit_price);
+        // Update capital
+        self.capital += pnl - total_fees;
 
-            // Place exit order
-            if let Err(e) = self.place_order(offset_size, exit_price).await {
-                error!("Failed to place exit order: {}", e);
-                return;
-            }
+        // Update trade with close price
+        trade.close_price = Some(exit_price);
 
-            self.closed_trades.push(trade);
+        self.closed_trades.push(trade);
 
-            info!(
-                "Trade closed - PnL: ${:.2}, Fees: ${:.2}, Current Capital: ${:.2}",
-                pnl, total_fees, self.capital
-            );
+        info!(
+            "Trade closed - PnL: ${:.2}, Fees: ${:.2}, Current Capital: ${:.2}",
+            pnl, total_fees, self.capital
+        );
+    }
+
+    /// Apply the close policy to the account, then print the final statistics and persist the
+    /// trade left behind
+    async fn shutdown(&mut self, reason: &str) {
+        info!("Shutting down ({})...", reason);
+        let asset = self.config.bot.asset.clone();
+        let mut open_trades: Vec<OpenTrade> = self
+            .current_trade
+            .iter()
+            .map(|trade| OpenTrade {
+                label: "ACCOUNT".to_string(),
+                coin: asset.clone(),
+                size: trade.size,
+                entry_price: trade.entry_price,
+                stop: Some(trade.sl_price),
+                take_profit: Some(trade.tp_price),
+            })
+            .collect();
+        let policy = self.config.shutdown.close_policy;
+        let completed = match (&self.exchange_client, self.user_address) {
+            (Some(exchange_client), Some(user)) => {
+                let account = FlattenAccount { label: "ACCOUNT", user, exchange_client };
+                close_out(
+                    &self.info_client,
+                    &self.pre_trade,
+                    &[account],
+                    &[asset],
+                    &open_trades,
+                    &self.config.shutdown,
+                )
+                .await
+            }
+            // Test mode: nothing rests on the exchange
+            _ => true,
+        };
+
+        if completed && policy == ClosePolicy::Flatten {
+            // Booked at the last mid, the fills aren't awaited
+            if let Some(trade) = self.current_trade.take() {
+                self.book_exit(trade, self.latest_mid_price);
+            }
+            open_trades.clear();
+        }
+        print_statistics(&self.closed_trades);
+        info!("Final capital: ${:.2} (started with ${:.2})", self.capital, self.initial_capital);
+
+        let mut state = ShutdownState::new("orderflow", reason, policy);
+        state.completed = completed;
+        state.open_trades = open_trades;
+        state.closed_trades = self.closed_trades.len();
+        state.total_pnl = self.capital - self.initial_capital;
+        if let Err(e) = state.save(&self.config.shutdown.state_path("orderflow")) {
+            error!("Failed to write the shutdown state: {}", e);
         }
     }
 
@@ -357,6 +629,17 @@ impl OrderFlowTradingBot {
         self.subscribe_all(sender.clone()).await?;
 
         let mut stats_interval = interval(std::time::Duration::from_secs(STATS_INTERVAL_SECS));
+        let mut watchdog_interval =
+            interval(std::time::Duration::from_secs(WATCHDOG_INTERVAL_SECS));
+        let mut margin_interval =
+            interval(std::time::Duration::from_secs(self.margin.config().margin_poll_secs.max(1)));
+        let mut reconcile_interval = interval(std::time::Duration::from_secs(
+            self.reconciler.config().reconcile_secs.max(1),
+        ));
+        let mut budget_interval = interval(std::time::Duration::from_secs(
+            self.pre_trade.config().budget.budget_publish_secs.max(1),
+        ));
+        let mut shutdown_signal = ShutdownSignal::new()?;
         let (tick_cache_path, candle_cache_path) = self.get_cache_paths();
 
         // Create cache directory if it doesn't exist
@@ -373,8 +656,30 @@ impl OrderFlowTradingBot {
                             if let Some(mid) = all_mids.data.mids.get(&self.config.bot.asset) {
                                 match mid.parse::<f64>() {
                                     Ok(px) => {
-                                        self.latest_mid_price = get_price(px, 0.1);
+                                        self.watchdog.touch(
+                                            ALL_MIDS_FEED,
+                                            &self.config.bot.asset,
+                                            Utc::now().timestamp(),
+                                        );
+                                        let book = self
+                                            .order_book
+                                            .best_bid()
+                                            .zip(self.order_book.best_ask())
+                                            .map(|((bid, _), (ask, _))| (bid, ask));
+                                        if !self.price_filter.check(px, book).is_accepted() {
+                                            continue;
+                                        }
+                                        self.latest_mid_price = px;
+                                        self.volatility.update(Utc::now().timestamp(), px);
                                         self.check_stop_loss_take_profit().await;
+                                        self.check_risk().await;
+
+                                        if self.schedule.window_ended(Utc::now()) &&
+                                            self.current_trade.is_some()
+                                        {
+                                            info!("Trading window ended, closing open trade");
+                                            self.exit_trade(self.latest_mid_price).await;
+                                        }
 
                                         if let Err(e) = store_tick_to_cache(&tick_cache_path, px) {
                                             error!("Failed to store tick: {}", e);
@@ -390,40 +695,21 @@ impl OrderFlowTradingBot {
                             self.handle_user_event(user_events.data).await;
                         }
                         Message::L2Book(order_book) => {
-                            let update = OrderBookUpdate {
-                                bids: order_book.data.levels[0]
-                                    .iter()
-                                    .filter_map(|level| {
-                                        let px = level.px.parse().ok()?;
-                                        let sz = level.sz.parse().ok()?;
-                                        Some((px, sz))
-                                    })
-                                    .collect(),
-                                asks: order_book.data.levels[1]
-                                    .iter()
-                                    .filter_map(|level| {
-                                        let px = level.px.parse().ok()?;
-                                        let sz = level.sz.parse().ok()?;
-                                        Some((px, sz))
-                                    })
-                                    .collect(),
-                            };
-                            self.process_order_book_update(update).await;
+                            self.watchdog.touch(
+                                L2_BOOK_FEED,
+                                &order_book.data.coin,
+                                Utc::now().timestamp(),
+                            );
+                            if self.order_book.apply(&order_book.data) {
+                                self.process_order_book_update();
+                            }
+                        }
+                        Message::Trades(trades) => {
+                            self.process_trades(&trades.data);
                         }
                         Message::Candle(candle) => {
-                            let candle_data = candle.data.clone();
-
-                            // Only process if it's a new candle
-                            let candle_ts = candle_data.time_close as i64;
-                            let should_process = match candle_data.interval.as_str() {
-                                "1h" => candle_ts > self.last_hourly_candle_ts,
-                                "5m" => candle_ts > self.last_five_min_candle_ts,
-                                _ => false,
-                            };
-
-                            if should_process {
-                                self.process_candle(candle_data.clone()).await;
-                                if let Err(e) = store_candle_to_cache(&candle_cache_path, &candle_data) {
+                            if let Some(closed) = self.process_candle(candle.data).await {
+                                if let Err(e) = store_candle_to_cache(&candle_cache_path, &closed) {
                                     error!("Failed to store candle: {}", e);
                                 }
                             }
@@ -435,22 +721,224 @@ impl OrderFlowTradingBot {
                 }
                 _ = stats_interval.tick() => {
                     print_statistics(&self.closed_trades);
+                    if let Some(regime) = &self.regime {
+                        regime.print_statistics(&self.config.bot.asset);
+                    }
                     info!(
                         "Capital: ${:.2}, Current Position: {:?}",
                         self.capital,
                         self.current_trade.unwrap_or(Trade::default())
                     );
                 }
+                _ = watchdog_interval.tick() => {
+                    self.check_watchdog(&sender).await?;
+                }
+                _ = margin_interval.tick() => {
+                    self.check_margin().await;
+                }
+                _ = reconcile_interval.tick() => {
+                    self.reconcile().await;
+                }
+                _ = budget_interval.tick(), if self.pre_trade.budget().is_some() => {
+                    self.publish_budget().await;
+                }
+                reason = shutdown_signal.recv() => {
+                    self.shutdown(reason).await;
+                    return Ok(());
+                }
             }
         }
     }
 
+    /// Poll the clearinghouse state and reduce or close the open trade when its liquidation
+    /// price got too close relative to its stop
+    async fn check_margin(&mut self) {
+        let (Some(user), Some(trade)) = (self.user_address, self.current_trade) else { return };
+        if !self.margin.enabled() || self.config.bot.test_mode {
+            return;
+        }
+        let snapshot = match self.info_client.user_state(user).await {
+            Ok(state) => match MarginSnapshot::from_state(&state) {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    warn!("Invalid clearinghouse state: {}", e);
+                    return;
+                }
+            },
+            Err(e) => {
+                warn!("Failed to fetch clearinghouse state: {}", e);
+                return;
+            }
+        };
+        let held = HeldPosition {
+            coin: &self.config.bot.asset,
+            mark: self.latest_mid_price,
+            stop: Some(trade.sl_price),
+        };
+        match self.margin.check("ACCOUNT", &snapshot, &held) {
+            Some(DeRisk::Close) => self.exit_trade(self.latest_mid_price).await,
+            Some(DeRisk::Reduce { fraction }) => self.reduce_trade(fraction).await,
+            None => {}
+        }
+    }
+
+    /// Publish the account's positions to the budget shared with other processes
+    async fn publish_budget(&self) {
+        let (Some(budget), Some(user)) = (self.pre_trade.budget(), self.user_address) else {
+            return;
+        };
+        let published = async {
+            let state = self.info_client.user_state(user).await?;
+            let open_orders = self.info_client.open_orders(user).await?;
+            budget.publish(user, &state, &open_orders)
+        }
+        .await;
+        if let Err(e) = published {
+            warn!("Failed to publish positions to the shared budget: {}", e);
+        }
+    }
+
+    /// Compare `current_position` with the exchange's and apply the drift policy
+    async fn reconcile(&mut self) {
+        let Some(user) = self.user_address else { return };
+        if !self.reconciler.enabled() || self.config.bot.test_mode {
+            return;
+        }
+        let state = match self.info_client.user_state(user).await {
+            Ok(state) => state,
+            Err(e) => {
+                warn!("Failed to fetch clearinghouse state: {}", e);
+                return;
+            }
+        };
+        // Orders are sent without tracking their ids, so only the position is compared
+        let internal = InternalState {
+            coin: &self.config.bot.asset,
+            position: self.current_position,
+            orders: None,
+        };
+        match self.reconciler.check("ACCOUNT", &internal, &state, &[]) {
+            Ok(Some(event)) if event.policy == DriftPolicy::Correct => {
+                self.correct_position(&event)
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Invalid clearinghouse state: {}", e),
+        }
+    }
+
+    /// Take the exchange's position as the truth: resize, drop or adopt the open trade
+    fn correct_position(&mut self, event: &DriftEvent) {
+        self.current_position = event.exchange;
+        match self.current_trade.as_mut() {
+            Some(_) if event.exchange == 0.0 => {
+                warn!("Open trade no longer on the exchange, dropping it");
+                self.current_trade = None;
+            }
+            Some(trade) => {
+                trade.size = event.exchange;
+                trade.direction =
+                    if event.exchange > 0.0 { Direction::Long } else { Direction::Short };
+            }
+            None if event.exchange != 0.0 => {
+                let entry_price = event.exchange_entry.unwrap_or(self.latest_mid_price);
+                let sign = event.exchange.signum();
+                let trade = Trade {
+                    direction: if sign > 0.0 { Direction::Long } else { Direction::Short },
+                    entry_price,
+                    entry_time: Utc::now().timestamp(),
+                    size: event.exchange,
+                    tp_price: entry_price * (1.0 + sign * self.config.risk.take_profit),
+                    sl_price: entry_price * (1.0 - sign * self.config.risk.stop_loss),
+                    close_price: None,
+                };
+                warn!(
+                    "Adopting untracked position {} at {} (SL: {}, TP: {})",
+                    trade.size, entry_price, trade.sl_price, trade.tp_price
+                );
+                self.current_trade = Some(trade);
+            }
+            None => {}
+        }
+    }
+
+    /// Close `fraction` of the open trade, keeping the rest open
+    async fn reduce_trade(&mut self, fraction: f64) {
+        let Some(trade) = self.current_trade else { return };
+        let asset = &self.config.bot.asset;
+        let reduce = self.pre_trade.round_size(asset, trade.size * fraction);
+        let remaining = trade.size - reduce;
+        if self.pre_trade.round_size(asset, remaining) == 0.0 {
+            self.exit_trade(self.latest_mid_price).await;
+            return;
+        }
+        if reduce == 0.0 {
+            return;
+        }
+        let exit_price = self.pre_trade.round_price(asset, self.latest_mid_price);
+        if let Err(e) = self.place_order(-reduce, exit_price, true).await {
+            error!("Failed to place reduce order: {}", e);
+            return;
+        }
+
+        // The size is negative for shorts, and so is `reduce`
+        let pnl = (exit_price - trade.entry_price) * reduce;
+        let fees = (trade.entry_price * TAKER_FEE + exit_price * MAKER_FEE) * reduce.abs();
+        self.capital += pnl - fees;
+        self.closed_trades.push(Trade { size: reduce, close_price: Some(exit_price), ..trade });
+        if let Some(trade) = self.current_trade.as_mut() {
+            trade.size = remaining;
+        }
+        info!(
+            "Trade reduced by {} to {} - PnL: ${:.2}, Fees: ${:.2}, Current Capital: ${:.2}",
+            reduce.abs(),
+            remaining.abs(),
+            pnl,
+            fees,
+            self.capital
+        );
+    }
+
+    /// Resubscribe on a new connection when mids or the book stop arriving, and close the
+    /// open trade at a REST mid once they have been missing for too long
+    async fn check_watchdog(&mut self, sender: &UnboundedSender<Message>) -> eyre::Result<()> {
+        let events = self.watchdog.check(Utc::now().timestamp());
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        info!("Market data stale, resubscribing on a new connection");
+        self.info_client = InfoClient::new(None, Some(BaseUrl::Mainnet))
+            .await
+            .map_err(|e| eyre::eyre!("Failed to create InfoClient: {}", e))?;
+        self.subscribe_all(sender.clone()).await?;
+
+        if self.current_trade.is_none() ||
+            !events.iter().any(|event| event.level == Staleness::Flatten)
+        {
+            return Ok(());
+        }
+        let mids = self
+            .info_client
+            .all_mids()
+            .await
+            .map_err(|e| eyre::eyre!("Failed to fetch mids: {}", e))?;
+        match mids.get(&self.config.bot.asset).and_then(|mid| mid.parse::<f64>().ok()) {
+            Some(px) => {
+                warn!("Closing trade on stale market data at {}", px);
+                self.latest_mid_price = px;
+                self.exit_trade(self.latest_mid_price).await;
+            }
+            None => error!("No REST price for {}, cannot close trade", self.config.bot.asset),
+        }
+        Ok(())
+    }
+
     /// Subscribe to all needed streams.
-    async fn subscribe_all(
-        &mut self,
-        sender: tokio::sync::mpsc::UnboundedSender<Message>,
-    ) -> eyre::Result<()> {
+    async fn subscribe_all(&mut self, sender: UnboundedSender<Message>) -> eyre::Result<()> {
         debug!("Subscribing to all streams");
+        let now = Utc::now().timestamp();
+        self.watchdog.watch(ALL_MIDS_FEED, &self.config.bot.asset, now);
+        self.watchdog.watch(L2_BOOK_FEED, &self.config.bot.asset, now);
 
         if self.user_address.is_some() {
             // 1) Fills / user events
@@ -475,7 +963,13 @@ impl OrderFlowTradingBot {
             .await
             .map_err(|e| eyre::eyre!("Failed to subscribe to AllMids: {}", e))?;
 
-        // 4) 1H candles
+        // 4) Trades
+        self.info_client
+            .subscribe(Subscription::Trades { coin: self.config.bot.asset.clone() }, sender.clone())
+            .await
+            .map_err(|e| eyre::eyre!("Failed to subscribe to trades: {}", e))?;
+
+        // 5) 1H candles
         self.info_client
             .subscribe(
                 Subscription::Candle {
@@ -487,18 +981,32 @@ impl OrderFlowTradingBot {
             .await
             .map_err(|e| eyre::eyre!("Failed to subscribe to 1h candles: {}", e))?;
 
-        // 5) 5m candles
+        // 6) 5m candles
         self.info_client
             .subscribe(
                 Subscription::Candle {
                     coin: self.config.bot.asset.clone(),
                     interval: "5m".to_string(),
                 },
-                sender,
+                sender.clone(),
             )
             .await
             .map_err(|e| eyre::eyre!("Failed to subscribe to 5m candles: {}", e))?;
 
+        // 7) Regime candles, unless already subscribed above
+        if let Some(interval) = self.regime.as_ref().map(|regime| regime.config().interval.clone())
+        {
+            if interval != "1h" && interval != "5m" {
+                self.info_client
+                    .subscribe(
+                        Subscription::Candle { coin: self.config.bot.asset.clone(), interval },
+                        sender,
+                    )
+                    .await
+                    .map_err(|e| eyre::eyre!("Failed to subscribe to regime candles: {}", e))?;
+            }
+        }
+
         Ok(())
     }
 
@@ -529,90 +1037,155 @@ impl OrderFlowTradingBot {
     }
 
     /// Process an L2 order book update
-    async fn process_order_book_update(&mut self, update: OrderBookUpdate) {
-        // debug!("Processing order book update: {:?}", update);
-        // For demonstration, we compute an order flow imbalance
-        let imbalance = self.calculate_order_flow_imbalance(&update);
-        trace!("Order book imbalance calculated: {}", imbalance);
-
-        // You could incorporate imbalance logic in `generate_trading_signal` or separate
-        // step to refine your strategy.
+    fn process_order_book_update(&mut self) {
+        let raw = self.imbalance.update(self.order_book.bids(), self.order_book.asks());
+        trace!(
+            "Order book: spread {:?} bps, microprice {:?}, imbalance raw {:.4}, smoothed {:?}",
+            self.order_book.spread_bps(),
+            self.order_book.microprice(),
+            raw,
+            self.imbalance.smoothed()
+        );
     }
 
-    /// Calculate a simple order-flow imbalance metric
-    fn calculate_order_flow_imbalance(&self, update: &OrderBookUpdate) -> f64 {
-        let bid_volume: f64 = update.bids.iter().map(|(_, size)| size).sum();
-        let ask_volume: f64 = update.asks.iter().map(|(_, size)| size).sum();
-        if (bid_volume + ask_volume).abs() < f64::EPSILON {
-            return 0.0;
+    /// Process a batch of public trades from the tape
+    fn process_trades(&mut self, trades: &[hyperliquid_rust_sdk::Trade]) {
+        for trade in trades {
+            let trade = match TapeTrade::try_from(trade) {
+                Ok(t) => t,
+                Err(e) => {
+                    warn!("Failed to parse trade: {}", e);
+                    continue;
+                }
+            };
+
+            if self.tape.is_large_print(&trade) {
+                info!(
+                    "Large print: {:?} {} {} at {} (${:.0})",
+                    trade.aggressor,
+                    trade.size,
+                    self.config.bot.asset,
+                    trade.price,
+                    trade.notional()
+                );
+            }
+
+            if let Some(bar) = self.tape.update(&trade) {
+                debug!(
+                    "Tape bar closed: buy {:.4}, sell {:.4}, delta {:.4}, large buys/sells {}/{}, \
+                     CVD {:.4}",
+                    bar.buy_volume,
+                    bar.sell_volume,
+                    bar.delta(),
+                    bar.large_buys,
+                    bar.large_sells,
+                    self.tape.cvd()
+                );
+            }
         }
-        (bid_volume - ask_volume) / (bid_volume + ask_volume)
     }
 
-    /// Process a candle message
-    async fn process_candle(&mut self, candle: CandleData) {
-        let candle_ts = candle.time_close as i64;
-
-        debug!("Processing new candle data: {:?}", candle);
-        let price: f64 = match candle.close.parse() {
-            Ok(p) => p,
+    /// Process a candle message. Hyperliquid resends the candle still forming on every trade, so
+    /// updates of the same candle replace each other and a candle is committed to the buffers and
+    /// VWAPs once the next one starts. Returns the candle committed, if any.
+    async fn process_candle(&mut self, candle_data: CandleData) -> Option<CandleData> {
+        trace!("Processing candle data: {:?}", candle_data);
+        let candle = match Candle::try_from(&candle_data) {
+            Ok(c) => c,
             Err(e) => {
-                error!("Failed to parse candle close price: {}", e);
-                return;
+                error!("Failed to parse candle: {}", e);
+                return None;
             }
         };
 
-        let volume: f64 = candle.volume.parse().unwrap();
+        if let Some(regime) = self.regime.as_mut() {
+            if candle_data.interval == regime.config().interval {
+                // The filter keeps its own forming candle
+                regime.on_candle(&candle);
+            }
+        }
+        if candle_data.interval != "1h" && candle_data.interval != "5m" {
+            // Regime-only interval
+            return None;
+        }
+
+        let closed = match self.pending_candles.get(&candle_data.interval) {
+            // A late update of a candle already committed
+            Some((pending, _)) if pending.time_open > candle_data.time_open => return None,
+            Some((pending, _)) if pending.time_open == candle_data.time_open => None,
+            Some(pending) => Some(pending.clone()),
+            None => None,
+        };
+        self.pending_candles.insert(candle_data.interval.clone(), (candle_data, candle));
+        let (closed_data, closed) = closed?;
+        debug!("Committing {} candle: {:?}", closed_data.interval, closed);
 
         // Update appropriate candle buffer and VWAP
-        if candle.interval == "1h" {
-            self.hourly_candles.push_back(candle);
+        if closed_data.interval == "1h" {
+            self.hourly_candles.push_back(closed);
             if self.hourly_candles.len() > self.config.vwap.hourly_periods * 2 {
                 self.hourly_candles.pop_front();
             }
-            self.hourly_vwap.update(price, volume);
-            self.last_hourly_candle_ts = candle_ts;
-        } else if candle.interval == "5m" {
-            self.five_min_candles.push_back(candle);
+            self.hourly_vwap.update(&closed);
+        } else {
+            self.five_min_candles.push_back(closed);
             if self.five_min_candles.len() > self.config.vwap.five_min_periods * 2 {
                 self.five_min_candles.pop_front();
             }
-            self.five_min_vwap.update(price, volume);
-            self.last_five_min_candle_ts = candle_ts;
+            self.five_min_vwap.update(&closed);
         }
 
         self.generate_trading_signal().await;
+        Some(closed_data)
     }
 
     /// Generate trading signals based on VWAPs and current position
     async fn generate_trading_signal(&mut self) {
-        // Wait until we have enough data
-        if self.hourly_candles.len() < self.config.vwap.hourly_periods ||
-            self.five_min_candles.len() < self.config.vwap.five_min_periods
-        {
+        // Wait until both VWAPs are warmed up
+        let (Some(hourly_vwap), Some(five_min_vwap)) =
+            (self.hourly_vwap.value(), self.five_min_vwap.value())
+        else {
             debug!(
-                "Insufficient data for trading signal generation 24h: {}, 5m: {}",
-                self.hourly_candles.len(),
-                self.five_min_candles.len()
+                "Insufficient data for trading signal generation 1h: {}, 5m: {}",
+                self.hourly_vwap.len(),
+                self.five_min_vwap.len()
             );
             return;
-        }
-
-        let hourly_vwap = self.hourly_vwap.value();
-        let five_min_vwap = self.five_min_vwap.value();
-
-        // Basic cross-over style: short-term VWAP vs. long-term VWAP
-        let signal = if five_min_vwap > hourly_vwap {
-            1.0 // Bullish signal
-        } else if five_min_vwap < hourly_vwap {
-            -1.0 // Bearish signal
-        } else {
-            0.0 // Neutral
         };
 
-        debug!(
-            "Trading signal generated: {} Hourly VWAP: {}, 5m VWAP: {}",
-            signal, hourly_vwap, five_min_vwap
+        // Short-term vs. long-term VWAP crossover, weighted with the book imbalance
+        let imbalance = self.imbalance.smoothed().unwrap_or_else(|| {
+            debug!("Imbalance not warmed up yet, scoring it as neutral");
+            0.0
+        });
+        let last_bar = self.tape.last_bar().copied().unwrap_or_default();
+        let score = SignalScore::compute(
+            five_min_vwap,
+            hourly_vwap,
+            imbalance,
+            last_bar.delta_ratio(),
+            &self.config.signal,
+        );
+        let signal = score.direction(&self.config.signal);
+
+        info!(
+            "Signal {}: score {:.3} (vwap {:.3} x {}, imbalance {:.3} x {}, flow {:.3} x {}), \
+             CVD: {:.4}, large buys/sells: {}/{}, Hourly VWAP: {:.4}, 5m VWAP: {:.4}, \
+             Hourly bands: {:?}",
+            signal,
+            score.score,
+            score.vwap,
+            self.config.signal.vwap_weight,
+            score.imbalance,
+            self.config.signal.imbalance_weight,
+            score.flow,
+            self.config.signal.flow_weight,
+            self.tape.cvd(),
+            last_bar.large_buys,
+            last_bar.large_sells,
+            hourly_vwap,
+            five_min_vwap,
+            self.hourly_vwap.bands(self.config.vwap.band_std)
         );
 
         // If a signal appears and there's no open trade, attempt to open one
@@ -621,6 +1194,36 @@ impl OrderFlowTradingBot {
         }
     }
 
+    /// Feed capital change plus unrealized PnL to the kill switch; on a breach cancel resting
+    /// orders and, if configured, close the open trade
+    async fn check_risk(&mut self) {
+        let unrealized = self
+            .current_trade
+            .as_ref()
+            .map_or(0.0, |trade| (self.latest_mid_price - trade.entry_price) * trade.size);
+        let pnl = self.capital - self.initial_capital + unrealized;
+        if self.risk.update(&self.config.bot.asset, pnl, false, Utc::now()).is_none() {
+            return;
+        }
+
+        if let (Some(exchange_client), Some(user)) = (&self.exchange_client, self.user_address) {
+            match cancel_open_orders(
+                &self.info_client,
+                exchange_client,
+                user,
+                &self.config.bot.asset,
+            )
+            .await
+            {
+                Ok(cancelled) => info!("Cancelled {} resting orders", cancelled),
+                Err(e) => error!("Failed to cancel resting orders: {}", e),
+            }
+        }
+        if self.risk.config().flatten_on_breach && self.current_trade.is_some() {
+            self.exit_trade(self.latest_mid_price).await;
+        }
+    }
+
     /// Check if SL/TP conditions are met
     async fn check_stop_loss_take_profit(&mut self) {
         if let Some(trade) = &self.current_trade {
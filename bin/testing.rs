@@ -1,11 +1,18 @@
 use chrono::Utc;
 use clap::{Parser, ValueEnum};
 use hyperliquid_rust_sdk::{BaseUrl, InfoClient, Message, Subscription};
+use serde::Deserialize;
 use tokio::{select, signal, sync::mpsc::unbounded_channel, time::interval};
 use tracing::{debug, error, info, warn};
 use tracing_subscriber::EnvFilter;
 
-use dual_channel_bot::caching::{load_ticks_from_cache, store_tick_to_cache};
+use dual_channel_bot::{
+    caching::{load_ticks_from_cache, store_tick_to_cache},
+    indicators::TickBars,
+    price_filter::{PriceFilter, PriceFilterConfig},
+    regime::{RegimeConfig, RegimeFilter},
+    sizing::{PriceVol, Sizer, SizerArgs, SizingInput},
+};
 
 // =============================================================
 //  CLI + Enums
@@ -47,6 +54,28 @@ struct Args {
     /// Name of the asset to trade (eg: HYPE)
     #[arg(long, default_value = "HYPE")]
     asset: String,
+
+    /// Simulated starting equity (USD) that `--sizing` sizes against
+    #[arg(long, default_value_t = 1000.0)]
+    equity: f64,
+
+    #[command(flatten)]
+    sizing: SizerArgs,
+
+    #[command(flatten)]
+    price_filter: PriceFilterConfig,
+
+    /// hype-bot config file whose `[regime]` section scales and pauses the simulated trades as
+    /// it does the dual channels
+    #[arg(long)]
+    config: Option<String>,
+}
+
+/// The part of hype-bot's config file the simulation uses
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct FileConfig {
+    regime: Option<RegimeConfig>,
 }
 
 // =============================================================
@@ -64,6 +93,8 @@ pub struct SimulationParams {
     pub sl_percent: f64,
     pub timeout_sec: u64,
     pub fees: f64,
+    pub equity: f64,
+    pub sizer: Sizer,
 }
 
 /// Direction of a trade
@@ -92,24 +123,65 @@ pub struct TestTradingFramework {
     short_trade: Option<Trade>,
     closed_trades: Vec<Trade>,
     total_pnl_usd: f64,
+    volatility: PriceVol,
+    /// Bars of the regime interval and the filter they feed
+    regime: Option<(TickBars, RegimeFilter)>,
 }
 
 impl TestTradingFramework {
     /// Create a new trading framework with the given simulation parameters.
-    pub fn new(params: SimulationParams) -> Self {
+    pub fn new(params: SimulationParams, regime: Option<RegimeConfig>) -> Self {
         Self {
             params,
             long_trade: None,
             short_trade: None,
             closed_trades: Vec::new(),
             total_pnl_usd: 0.0,
+            volatility: PriceVol::default(),
+            regime: regime.map(|config| {
+                (TickBars::new(config.interval_secs() as u64), RegimeFilter::new(config))
+            }),
+        }
+    }
+
+    /// Feed a price into the volatility estimate used for sizing and the regime filter.
+    pub fn record_price(&mut self, ts: i64, price: f64) {
+        self.volatility.update(ts, price);
+        if let Some((bars, regime)) = &mut self.regime {
+            bars.update(ts, price);
+            if let Some(bar) = bars.current() {
+                regime.on_candle(bar);
+            }
         }
     }
 
+    /// Size multiplier of the current regime, as applied to hype-bot's channels.
+    fn regime_scale(&self) -> f64 {
+        self.regime.as_ref().map_or(1.0, |(_, regime)| regime.scale())
+    }
+
+    /// Notional of a new trade entered at `price` with its stop at `sl_price`.
+    fn notional(&self, price: f64, sl_price: f64) -> f64 {
+        let input = SizingInput {
+            price,
+            equity: self.params.equity + self.total_pnl_usd,
+            stop_price: Some(sl_price),
+            volatility: self.volatility.annualized(),
+        };
+        let notional = self.params.sizer.notional(&input).unwrap_or_else(|e| {
+            debug!("Sizing unavailable ({e}), using amount x leverage");
+            self.params.amount * self.params.leverage
+        });
+        notional * self.regime_scale()
+    }
+
     /// Open both trades (long & short) initially at the same price.
     pub fn open_initial_trades(&mut self, price: f64) {
+        if self.regime_scale() <= 0.0 {
+            info!("Regime scale is 0, waiting for another regime before opening trades");
+            return;
+        }
         let current_ts = Utc::now().timestamp();
-        let notional = self.params.amount * self.params.leverage;
 
         let long_tp = price * (1.0 + self.params.tp_percent);
         let long_sl = price * (1.0 - self.params.sl_percent);
@@ -121,7 +193,7 @@ impl TestTradingFramework {
             entry_price: price,
             entry_time: current_ts,
             close_price: None,
-            notional,
+            notional: self.notional(price, long_sl),
             tp_price: long_tp,
             sl_price: long_sl,
         });
@@ -131,7 +203,7 @@ impl TestTradingFramework {
             entry_price: price,
             entry_time: current_ts,
             close_price: None,
-            notional,
+            notional: self.notional(price, short_sl),
             tp_price: short_tp,
             sl_price: short_sl,
         });
@@ -145,7 +217,8 @@ impl TestTradingFramework {
     /// Process each incoming price tick:  
     ///  - Print current PnL for open positions
     ///  - Close trades if conditions are met
-    ///  - Re-open them immediately (so there is always one long and one short open)
+    ///  - Re-open them immediately (so there is always one long and one short open, unless the
+    ///    regime pauses them)
     pub fn on_price_update(&mut self, price: f64) {
         let current_ts = Utc::now().timestamp();
 
@@ -157,7 +230,7 @@ impl TestTradingFramework {
             if let Some(reason) = self.check_should_close(long_trade, price, current_ts) {
                 info!("Closing LONG => {reason}");
                 self.close_trade(long_trade.clone(), price);
-                self.open_new_trade(Direction::Long, price);
+                self.long_trade = None;
             }
         }
 
@@ -166,9 +239,16 @@ impl TestTradingFramework {
             if let Some(reason) = self.check_should_close(short_trade, price, current_ts) {
                 info!("Closing SHORT => {reason}");
                 self.close_trade(short_trade.clone(), price);
-                self.open_new_trade(Direction::Short, price);
+                self.short_trade = None;
             }
         }
+
+        if self.long_trade.is_none() {
+            self.open_new_trade(Direction::Long, price);
+        }
+        if self.short_trade.is_none() {
+            self.open_new_trade(Direction::Short, price);
+        }
     }
 
     /// Check if an existing trade should be closed due to either:
@@ -246,10 +326,12 @@ impl TestTradingFramework {
         );
     }
 
-    /// Re-open a new trade in the specified `direction` immediately.
+    /// Re-open a new trade in the specified `direction` immediately, unless the regime pauses it.
     fn open_new_trade(&mut self, direction: Direction, price: f64) {
+        if self.regime_scale() <= 0.0 {
+            return;
+        }
         let current_ts = Utc::now().timestamp();
-        let notional = self.params.amount * self.params.leverage;
 
         let (tp_price, sl_price) = match direction {
             Direction::Long => {
@@ -259,6 +341,7 @@ impl TestTradingFramework {
                 (price * (1.0 - self.params.tp_percent), price * (1.0 + self.params.sl_percent))
             }
         };
+        let notional = self.notional(price, sl_price);
 
         let new_trade = Trade {
             direction,
@@ -354,6 +437,9 @@ impl TestTradingFramework {
             "[STATS] Total PnL = ${:.4}, Total Profitable Trades = {}, Total Loss Trades = {}",
             self.total_pnl_usd, profitable_trades, loss_trades
         );
+        if let Some((_, regime)) = &self.regime {
+            regime.print_statistics("SIM");
+        }
     }
 }
 
@@ -379,11 +465,21 @@ async fn main() -> eyre::Result<()> {
         sl_percent: args.sl_percent,
         timeout_sec: args.timeout_sec,
         fees: args.fees,
+        equity: args.equity,
+        sizer: args
+            .sizing
+            .sizer()?
+            .unwrap_or(Sizer::FixedNotional { notional: args.amount * args.leverage }),
     };
     let cache_path = format!("{}/{}.json", CACHE_DIR, args.asset);
 
     // Create our test framework
-    let mut framework = TestTradingFramework::new(sim_params);
+    let file_config: FileConfig = match &args.config {
+        Some(path) => toml::from_str(&std::fs::read_to_string(path)?)?,
+        None => FileConfig::default(),
+    };
+    let mut framework = TestTradingFramework::new(sim_params, file_config.regime);
+    let mut price_filter = PriceFilter::new(&args.asset, args.price_filter);
 
     match args.mode {
         RunMode::Live => {
@@ -403,8 +499,11 @@ async fn main() -> eyre::Result<()> {
                 let msg = receiver.recv().await.expect("Channel closed");
                 if let Message::AllMids(all_mids) = msg {
                     if let Some(mid) = all_mids.data.mids.get("HYPE") {
-                        initial_price = mid.parse().unwrap();
-                        break;
+                        let price = mid.parse().unwrap();
+                        if price_filter.check(price, None).is_accepted() {
+                            initial_price = price;
+                            break;
+                        }
                     }
                 }
             }
@@ -420,6 +519,10 @@ async fn main() -> eyre::Result<()> {
                         if let Message::AllMids(all_mids) = msg {
                             if let Some(mid) = all_mids.data.mids.get("HYPE") {
                                 let price = mid.parse::<f64>().unwrap();
+                                if !price_filter.check(price, None).is_accepted() {
+                                    continue;
+                                }
+                                framework.record_price(Utc::now().timestamp(), price);
                                 // 2) forward to test framework
                                 framework.on_price_update(price);
                                 // 3) store this tick in the cache
@@ -455,7 +558,13 @@ async fn main() -> eyre::Result<()> {
             framework.open_initial_trades(price_ticks[0].price);
 
             // 3) Replay each tick in sequence
+            price_filter.check(price_ticks[0].price, None);
             for tick in price_ticks.iter().skip(1) {
+                // Caches written before the filter existed may hold bad ticks
+                if !price_filter.check(tick.price, None).is_accepted() {
+                    continue;
+                }
+                framework.record_price(tick.timestamp, tick.price);
                 framework.on_price_update(tick.price);
                 // optionally, add a small delay to mimic real-time replay
                 // tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -0,0 +1,473 @@
+//! Risk budget shared by the bot processes trading the same accounts.
+//!
+//! Each bot only knows its own trades, so `orderflow` and `hype-bot` on one account can together
+//! hold more than either would allow itself. With `budget_path` set, every order that passes the
+//! other pre-trade checks reserves its notional in a JSON ledger shared by all processes, under an
+//! exclusive lock of `<budget_path>.lock`. The ledger also holds each account's positions and
+//! resting orders, which the bots publish from the exchange every `budget_publish_secs`. A
+//! reservation counts until a snapshot taken after it settles it, as a position or a resting
+//! order (or as neither, once the order is gone); the bot releases it earlier when the exchange
+//! rejects the order or the bot cancels it. `budget_reservation_secs` only bounds reservations
+//! left behind while no bot publishes the account.
+//!
+//! An order is refused when, should every resting order and reservation on its coin fill, the
+//! account would hold more than `max_asset_notional` on the coin or more than
+//! `max_gross_notional` over all coins. Orders that shrink that worst case are never refused.
+#![allow(missing_docs)]
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, OpenOptions},
+    io::ErrorKind,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use chrono::Utc;
+use ethers::types::H160;
+use hyperliquid_rust_sdk::{OpenOrdersResponse, UserStateResponse};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// A reservation made this long (ms) before a snapshot is part of it
+const SETTLE_MS: i64 = 5_000;
+
+#[derive(Debug, Clone, Deserialize, clap::Args)]
+#[serde(default)]
+pub struct BudgetConfig {
+    /// Ledger shared by the processes trading the same accounts (unset = no shared limits)
+    #[arg(long)]
+    pub budget_path: Option<String>,
+
+    /// Largest gross position notional (USD) of one account over all processes (0 = no limit)
+    #[arg(long, default_value_t = 0.0)]
+    pub max_gross_notional: f64,
+
+    /// Largest position notional (USD) of one account on one coin over all processes
+    /// (0 = no limit)
+    #[arg(long, default_value_t = 0.0)]
+    pub max_asset_notional: f64,
+
+    /// Seconds a reservation counts at most when no snapshot settles it (a safety net for
+    /// accounts no bot publishes)
+    #[arg(long, default_value_t = 300)]
+    pub budget_reservation_secs: u64,
+
+    /// Seconds between publications of the account positions to the ledger
+    #[arg(long, default_value_t = 15)]
+    pub budget_publish_secs: u64,
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        Self {
+            budget_path: None,
+            max_gross_notional: 0.0,
+            max_asset_notional: 0.0,
+            budget_reservation_secs: 300,
+            budget_publish_secs: 15,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BudgetRejection {
+    GrossNotional {
+        notional: f64,
+        max: f64,
+    },
+    AssetNotional {
+        notional: f64,
+        max: f64,
+    },
+    /// The ledger couldn't be read or written
+    Unavailable(String),
+}
+
+impl std::fmt::Display for BudgetRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BudgetRejection::GrossNotional { notional, max } => {
+                write!(f, "shared gross notional ${:.2} would exceed ${:.2}", notional, max)
+            }
+            BudgetRejection::AssetNotional { notional, max } => {
+                write!(f, "shared coin notional ${:.2} would exceed ${:.2}", notional, max)
+            }
+            BudgetRejection::Unavailable(e) => write!(f, "shared budget unavailable: {}", e),
+        }
+    }
+}
+
+/// A reservation made by [`SharedBudget::reserve`], to release if its order never rests or
+/// fills.
+#[must_use]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BudgetReservation {
+    user: H160,
+    id: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Reservation {
+    /// Process that made it, for whoever reads the ledger
+    owner: String,
+    /// Unique within `owner`
+    #[serde(default)]
+    id: u64,
+    coin: String,
+    /// Signed notional (USD)
+    notional: f64,
+    /// UTC ms
+    time: i64,
+    /// Resting order it was made for, once known
+    #[serde(default)]
+    oid: Option<u64>,
+}
+
+/// Resting order of a snapshot.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RestingOrder {
+    coin: String,
+    /// Signed notional (USD)
+    notional: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct AccountLedger {
+    /// When the positions were published (UTC ms); 0 until they are
+    snapshot_time: i64,
+    /// Signed position notional (USD) per coin
+    positions: HashMap<String, f64>,
+    /// Orders resting at the snapshot
+    #[serde(default)]
+    orders: Vec<RestingOrder>,
+    reservations: Vec<Reservation>,
+}
+
+impl AccountLedger {
+    /// Drop the reservations settled by the snapshot or older than `max_age_ms`.
+    fn prune(&mut self, now: i64, max_age_ms: i64) {
+        let settled = self.snapshot_time - SETTLE_MS;
+        self.reservations.retain(|reservation| {
+            reservation.time > settled && now - reservation.time < max_age_ms
+        });
+    }
+
+    /// Largest notional the account may hold on `coin` once its resting orders, reservations
+    /// and `extra` (signed USD) fill or not.
+    fn exposure(&self, coin: &str, extra: f64) -> f64 {
+        let position = self.positions.get(coin).copied().unwrap_or_default();
+        let (mut buys, mut sells) = (extra.max(0.0), extra.min(0.0));
+        let orders =
+            self.orders.iter().filter(|order| order.coin == coin).map(|order| order.notional);
+        let reservations = self
+            .reservations
+            .iter()
+            .filter(|reservation| reservation.coin == coin)
+            .map(|reservation| reservation.notional);
+        for notional in orders.chain(reservations) {
+            if notional > 0.0 {
+                buys += notional;
+            } else {
+                sells += notional;
+            }
+        }
+        (position + buys).abs().max((position + sells).abs())
+    }
+
+    fn coins(&self) -> HashSet<&str> {
+        self.positions
+            .keys()
+            .map(String::as_str)
+            .chain(self.orders.iter().map(|order| order.coin.as_str()))
+            .chain(self.reservations.iter().map(|reservation| reservation.coin.as_str()))
+            .collect()
+    }
+
+    /// Check whether `notional` (signed USD) more on `coin` keeps the account within `config`.
+    fn admits(
+        &self,
+        config: &BudgetConfig,
+        coin: &str,
+        notional: f64,
+    ) -> Result<(), BudgetRejection> {
+        let before = self.exposure(coin, 0.0);
+        let after = self.exposure(coin, notional);
+        if after <= before {
+            return Ok(());
+        }
+        if config.max_asset_notional > 0.0 && after > config.max_asset_notional {
+            return Err(BudgetRejection::AssetNotional {
+                notional: after,
+                max: config.max_asset_notional,
+            });
+        }
+        let gross = after +
+            self.coins()
+                .into_iter()
+                .filter(|other| *other != coin)
+                .map(|other| self.exposure(other, 0.0))
+                .sum::<f64>();
+        if config.max_gross_notional > 0.0 && gross > config.max_gross_notional {
+            return Err(BudgetRejection::GrossNotional {
+                notional: gross,
+                max: config.max_gross_notional,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Ledger {
+    /// Keyed by account address
+    accounts: HashMap<String, AccountLedger>,
+}
+
+#[derive(Debug)]
+pub struct SharedBudget {
+    config: BudgetConfig,
+    path: String,
+    owner: String,
+    next_id: AtomicU64,
+}
+
+impl SharedBudget {
+    /// `None` without a `budget_path`.
+    pub fn new(config: BudgetConfig) -> Option<Self> {
+        let path = config.budget_path.clone()?;
+        let bot = std::env::args()
+            .next()
+            .and_then(|arg| Path::new(&arg).file_name()?.to_str().map(str::to_string))
+            .unwrap_or_else(|| "bot".to_string());
+        let owner = format!("{}-{}", bot, std::process::id());
+        Some(Self { config, path, owner, next_id: AtomicU64::new(1) })
+    }
+
+    pub fn config(&self) -> &BudgetConfig {
+        &self.config
+    }
+
+    fn max_age_ms(&self) -> i64 {
+        self.config.budget_reservation_secs as i64 * 1000
+    }
+
+    /// Run `update` on the ledger under the lock, then write it back atomically.
+    fn with_ledger<T>(&self, update: impl FnOnce(&mut Ledger) -> T) -> eyre::Result<T> {
+        if let Some(dir) = Path::new(&self.path).parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(format!("{}.lock", self.path))?;
+        // Released when `lock` is dropped
+        lock.lock()?;
+        let mut ledger: Ledger = match fs::read_to_string(&self.path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == ErrorKind::NotFound => Ledger::default(),
+            Err(e) => return Err(e.into()),
+        };
+        let result = update(&mut ledger);
+        let tmp_path = format!("{}.tmp", self.path);
+        fs::write(&tmp_path, serde_json::to_string_pretty(&ledger)?)?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(result)
+    }
+
+    /// Reserve `notional` (signed USD) of `coin` for an order of `user`, unless the account
+    /// would break a limit.
+    pub fn reserve(
+        &self,
+        user: H160,
+        coin: &str,
+        notional: f64,
+    ) -> Result<BudgetReservation, BudgetRejection> {
+        let now = Utc::now().timestamp_millis();
+        let max_age_ms = self.max_age_ms();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let reserved = self.with_ledger(|ledger| {
+            let account = ledger.accounts.entry(format!("{:?}", user)).or_default();
+            account.prune(now, max_age_ms);
+            if account.snapshot_time == 0 {
+                debug!("No positions of {:?} published yet, only reservations count", user);
+            }
+            account.admits(&self.config, coin, notional)?;
+            account.reservations.push(Reservation {
+                owner: self.owner.clone(),
+                id,
+                coin: coin.to_string(),
+                notional,
+                time: now,
+                oid: None,
+            });
+            Ok(BudgetReservation { user, id })
+        });
+        reserved.unwrap_or_else(|e| Err(BudgetRejection::Unavailable(e.to_string())))
+    }
+
+    /// Drop the reservations of this process on `user` that `matches` selects.
+    fn remove(&self, user: H160, matches: impl Fn(&Reservation) -> bool) -> eyre::Result<()> {
+        self.with_ledger(|ledger| {
+            if let Some(account) = ledger.accounts.get_mut(&format!("{:?}", user)) {
+                account
+                    .reservations
+                    .retain(|reservation| reservation.owner != self.owner || !matches(reservation));
+            }
+        })
+    }
+
+    /// Release a reservation whose order was rejected.
+    pub fn release(&self, reservation: BudgetReservation) -> eyre::Result<()> {
+        self.remove(reservation.user, |entry| entry.id == reservation.id)
+    }
+
+    /// Tie a reservation to the order resting for it, so cancelling the order releases it.
+    pub fn on_resting(&self, reservation: BudgetReservation, oid: u64) -> eyre::Result<()> {
+        self.with_ledger(|ledger| {
+            let Some(account) = ledger.accounts.get_mut(&format!("{:?}", reservation.user)) else {
+                return;
+            };
+            if let Some(entry) = account
+                .reservations
+                .iter_mut()
+                .find(|entry| entry.owner == self.owner && entry.id == reservation.id)
+            {
+                entry.oid = Some(oid);
+            }
+        })
+    }
+
+    /// Release the reservation of a cancelled order of `user`.
+    pub fn on_cancelled(&self, user: H160, oid: u64) -> eyre::Result<()> {
+        self.remove(user, |entry| entry.oid == Some(oid))
+    }
+
+    /// Publish the positions and resting orders of `user`, read from the exchange just now,
+    /// settling the reservations made before them.
+    pub fn publish(
+        &self,
+        user: H160,
+        state: &UserStateResponse,
+        open_orders: &[OpenOrdersResponse],
+    ) -> eyre::Result<()> {
+        let mut positions = HashMap::new();
+        for asset in &state.asset_positions {
+            let size: f64 = asset.position.szi.parse()?;
+            let value: f64 = asset.position.position_value.parse()?;
+            if size != 0.0 {
+                positions.insert(asset.position.coin.clone(), value.abs() * size.signum());
+            }
+        }
+        let orders = open_orders
+            .iter()
+            .map(|order| {
+                let notional = order.sz.parse::<f64>()? * order.limit_px.parse::<f64>()?;
+                let notional = if order.side == "B" { notional } else { -notional };
+                Ok(RestingOrder { coin: order.coin.clone(), notional })
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+        let now = Utc::now().timestamp_millis();
+        let max_age_ms = self.max_age_ms();
+        self.with_ledger(|ledger| {
+            let account = ledger.accounts.entry(format!("{:?}", user)).or_default();
+            account.snapshot_time = now;
+            account.positions = positions;
+            account.orders = orders;
+            account.prune(now, max_age_ms);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(name: &str, max_asset_notional: f64) -> SharedBudget {
+        let path =
+            std::env::temp_dir().join(format!("budget-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        SharedBudget::new(BudgetConfig {
+            budget_path: Some(path.to_string_lossy().into_owned()),
+            max_asset_notional,
+            ..BudgetConfig::default()
+        })
+        .unwrap()
+    }
+
+    fn reservation(notional: f64, time: i64) -> Reservation {
+        Reservation {
+            owner: "test".to_string(),
+            id: 0,
+            coin: "ETH".to_string(),
+            notional,
+            time,
+            oid: None,
+        }
+    }
+
+    #[test]
+    fn prune_settles_reservations_made_before_the_snapshot() {
+        let mut account = AccountLedger {
+            snapshot_time: 100_000,
+            reservations: vec![
+                reservation(10.0, 90_000),
+                reservation(20.0, 97_000),
+                reservation(30.0, 110_000),
+            ],
+            ..AccountLedger::default()
+        };
+        account.prune(120_000, 300_000);
+        let left: Vec<f64> = account.reservations.iter().map(|r| r.notional).collect();
+        assert_eq!(left, vec![20.0, 30.0]);
+
+        // Without snapshots only the age bounds them
+        account.prune(420_000, 300_000);
+        assert!(account.reservations.is_empty());
+    }
+
+    #[test]
+    fn exposure_counts_resting_orders_and_reservations_per_side() {
+        let account = AccountLedger {
+            snapshot_time: 1,
+            positions: HashMap::from([("ETH".to_string(), 100.0)]),
+            orders: vec![
+                RestingOrder { coin: "ETH".to_string(), notional: 50.0 },
+                RestingOrder { coin: "ETH".to_string(), notional: -300.0 },
+                RestingOrder { coin: "BTC".to_string(), notional: 1000.0 },
+            ],
+            reservations: vec![reservation(25.0, 2)],
+        };
+        // Sells take the account from +100 to -200, buys to +175
+        assert_eq!(account.exposure("ETH", 0.0), 200.0);
+        assert_eq!(account.exposure("ETH", 100.0), 275.0);
+        assert_eq!(account.exposure("BTC", 0.0), 1000.0);
+    }
+
+    #[test]
+    fn reserve_refuses_past_the_limit_until_released() {
+        let budget = budget("release", 100.0);
+        let user = H160::zero();
+        let first = budget.reserve(user, "ETH", 80.0).unwrap();
+        assert!(matches!(
+            budget.reserve(user, "ETH", 40.0),
+            Err(BudgetRejection::AssetNotional { .. })
+        ));
+        // Shrinking the worst case is always allowed
+        budget.release(budget.reserve(user, "ETH", -40.0).unwrap()).unwrap();
+
+        budget.release(first).unwrap();
+        assert!(budget.reserve(user, "ETH", 40.0).is_ok());
+    }
+
+    #[test]
+    fn cancelling_a_resting_order_releases_its_reservation() {
+        let budget = budget("cancel", 100.0);
+        let user = H160::zero();
+        budget.on_resting(budget.reserve(user, "ETH", 80.0).unwrap(), 7).unwrap();
+        assert!(budget.reserve(user, "ETH", 40.0).is_err());
+        budget.on_cancelled(user, 7).unwrap();
+        assert!(budget.reserve(user, "ETH", 40.0).is_ok());
+    }
+}
@@ -7,6 +7,8 @@ use std::{
     io::{BufRead, BufReader, BufWriter, Write},
 };
 
+use crate::tape::TapeTrade;
+
 /// A single price tick (price + timestamp).
 /// If you want to store more info (like best bid/ask, volume, etc.),
 /// extend this struct with additional fields.
@@ -135,3 +137,33 @@ pub fn store_asset_to_cache(path: &str, data: AssetCtx) -> eyre::Result<()> {
 
     Ok(())
 }
+
+/// Store a batch of parsed public trades to the cache
+pub fn store_trades_to_cache(path: &str, trades: &[TapeTrade]) -> eyre::Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut writer = BufWriter::new(file);
+
+    for trade in trades {
+        serde_json::to_writer(&mut writer, trade)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Load all public trades from the cache, in recorded order
+pub fn load_trades_from_cache(path: &str) -> eyre::Result<Vec<TapeTrade>> {
+    let file = File::open(path)?;
+    let reader = BufReader::new(file);
+
+    let mut trades = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        trades.push(serde_json::from_str(&line)?);
+    }
+    Ok(trades)
+}
@@ -0,0 +1,191 @@
+//! Net exposure of the long and short accounts.
+//!
+//! The dual-channel bot relies on its long and short accounts roughly offsetting each other.
+//! [`ExposureGuard`] follows each account's position per coin from the clearinghouse state at
+//! start-up and the confirmed fills after it. When the net position is worth more than
+//! `max_net_exposure` for longer than `exposure_grace_secs`, the leading leg (the account the net
+//! position leans towards) is either paused, so it isn't re-opened until the accounts offset
+//! again, or trimmed back towards the lagging leg.
+#![allow(missing_docs)]
+
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+use hyperliquid_rust_sdk::{TradeInfo, UserStateResponse};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ExposureAction {
+    /// Don't re-open the leading leg until the accounts offset again
+    #[default]
+    Pause,
+    /// Reduce the leading leg by the net position
+    Rehedge,
+}
+
+#[derive(Debug, Clone, Deserialize, clap::Args)]
+#[serde(default)]
+pub struct ExposureConfig {
+    /// Net position value (USD) of both accounts together tolerated per coin (0 = no guard)
+    #[arg(long, default_value_t = 0.0)]
+    pub max_net_exposure: f64,
+
+    /// Seconds the net exposure may stay above the limit before the guard acts
+    #[arg(long, default_value_t = 300)]
+    pub exposure_grace_secs: u64,
+
+    /// What to do with the leading leg once the grace period is over
+    #[arg(long, value_enum, default_value_t = ExposureAction::Pause)]
+    pub exposure_action: ExposureAction,
+}
+
+impl Default for ExposureConfig {
+    fn default() -> Self {
+        Self {
+            max_net_exposure: 0.0,
+            exposure_grace_secs: 300,
+            exposure_action: ExposureAction::Pause,
+        }
+    }
+}
+
+/// Net exposure of one coin that stayed above the limit past the grace period.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExposureBreach {
+    /// Net position in coins, positive when the long account leads
+    pub net: f64,
+    pub net_usd: f64,
+    /// Seconds above the limit
+    pub since: i64,
+    pub action: ExposureAction,
+}
+
+impl ExposureBreach {
+    pub fn long_leads(&self) -> bool {
+        self.net > 0.0
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct CoinExposure {
+    long: f64,
+    short: f64,
+    /// When the net exposure went above the limit (UTC seconds)
+    above_since: Option<i64>,
+    breached: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExposureGuard {
+    config: ExposureConfig,
+    coins: HashMap<String, CoinExposure>,
+}
+
+impl ExposureGuard {
+    pub fn new(config: ExposureConfig) -> Self {
+        Self { config, coins: HashMap::new() }
+    }
+
+    pub fn config(&self) -> &ExposureConfig {
+        &self.config
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.max_net_exposure > 0.0
+    }
+
+    /// Take the positions of one account on `coins` from its clearinghouse state.
+    pub fn sync(
+        &mut self,
+        is_long_account: bool,
+        state: &UserStateResponse,
+        coins: &[String],
+    ) -> eyre::Result<()> {
+        for coin in coins {
+            let size = match state.asset_positions.iter().find(|p| &p.position.coin == coin) {
+                Some(asset) => asset.position.szi.parse()?,
+                None => 0.0,
+            };
+            let exposure = self.coins.entry(coin.clone()).or_default();
+            if is_long_account {
+                exposure.long = size;
+            } else {
+                exposure.short = size;
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply a confirmed fill of one account.
+    pub fn on_fill(&mut self, is_long_account: bool, fill: &TradeInfo) -> eyre::Result<()> {
+        let size: f64 = fill.sz.parse()?;
+        let signed = if fill.side == "B" { size } else { -size };
+        let exposure = self.coins.entry(fill.coin.clone()).or_default();
+        if is_long_account {
+            exposure.long += signed;
+        } else {
+            exposure.short += signed;
+        }
+        Ok(())
+    }
+
+    /// Net position (coins) of both accounts on `coin`.
+    pub fn net(&self, coin: &str) -> f64 {
+        self.coins.get(coin).map_or(0.0, |exposure| exposure.long + exposure.short)
+    }
+
+    /// Update the imbalance timer of `coin` at `price` and `now` (UTC seconds). Returns the
+    /// breach while the net exposure stays above the limit past the grace period; a re-hedge
+    /// breach is returned once per grace period.
+    pub fn check(&mut self, coin: &str, price: f64, now: i64) -> Option<ExposureBreach> {
+        if !self.enabled() {
+            return None;
+        }
+        let exposure = self.coins.entry(coin.to_string()).or_default();
+        let net = exposure.long + exposure.short;
+        let net_usd = net * price;
+        if net_usd.abs() <= self.config.max_net_exposure {
+            if exposure.breached {
+                info!("[{}] Net exposure back to ${:.2}", coin, net_usd);
+            }
+            exposure.above_since = None;
+            exposure.breached = false;
+            return None;
+        }
+
+        let since = now - *exposure.above_since.get_or_insert(now);
+        if since < self.config.exposure_grace_secs as i64 {
+            return None;
+        }
+        if !exposure.breached {
+            exposure.breached = true;
+            warn!(
+                "[{}] Net exposure ${:.2} ({} long, {} short) above ${:.2} for {}s: {:?} the {} leg",
+                coin,
+                net_usd,
+                exposure.long,
+                exposure.short,
+                self.config.max_net_exposure,
+                since,
+                self.config.exposure_action,
+                if net > 0.0 { "LONG" } else { "SHORT" }
+            );
+        }
+        if self.config.exposure_action == ExposureAction::Rehedge {
+            // The re-hedge fills take a while to arrive: give them a fresh grace period
+            exposure.above_since = None;
+            exposure.breached = false;
+        }
+        Some(ExposureBreach { net, net_usd, since, action: self.config.exposure_action })
+    }
+
+    /// Whether re-opening the long (`is_long`) or short leg of `coin` is paused.
+    pub fn is_paused(&self, coin: &str, is_long: bool) -> bool {
+        self.config.exposure_action == ExposureAction::Pause &&
+            self.coins.get(coin).is_some_and(|exposure| {
+                exposure.breached && (exposure.long + exposure.short > 0.0) == is_long
+            })
+    }
+}
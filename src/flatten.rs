@@ -0,0 +1,276 @@
+//! Emergency flatten of whole accounts.
+//!
+//! [`flatten_all`] works from the exchange's view of each account only, so it can run from a
+//! separate process (`hype-bot flatten`) while a bot is trading on the same accounts: it cancels
+//! every open order, closes every position with reduce-only IOC orders priced
+//! `flatten_slippage_bps` through the mid, then re-reads the clearinghouse state and repeats
+//! every `flatten_retry_secs` until the accounts are flat or `flatten_timeout_secs` passed. A
+//! failed exchange request is retried with the next round rather than ending the flatten.
+//! A bot left running may open new trades afterwards; stop it or halt its entries first.
+#![allow(missing_docs)]
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use ethers::types::H160;
+use hyperliquid_rust_sdk::{
+    ClientCancelRequest, ClientLimit, ClientOrder, ClientOrderRequest, ExchangeClient,
+    ExchangeDataStatus, ExchangeResponseStatus, InfoClient,
+};
+use serde::Deserialize;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+
+use crate::pretrade::PreTradeChecker;
+
+#[derive(Debug, Clone, Deserialize, clap::Args)]
+#[serde(default)]
+pub struct FlattenConfig {
+    /// How far (bps) through the mid the IOC close orders are priced
+    #[arg(long, default_value_t = 100.0)]
+    pub flatten_slippage_bps: f64,
+
+    /// Seconds to keep retrying before giving up on an account that isn't flat
+    #[arg(long, default_value_t = 60)]
+    pub flatten_timeout_secs: u64,
+
+    /// Seconds between a round of close orders and the next state check
+    #[arg(long, default_value_t = 2)]
+    pub flatten_retry_secs: u64,
+}
+
+impl Default for FlattenConfig {
+    fn default() -> Self {
+        Self { flatten_slippage_bps: 100.0, flatten_timeout_secs: 60, flatten_retry_secs: 2 }
+    }
+}
+
+/// An account to flatten.
+#[derive(Debug, Clone, Copy)]
+pub struct FlattenAccount<'a> {
+    pub label: &'a str,
+    pub user: H160,
+    pub exchange_client: &'a ExchangeClient,
+}
+
+/// A position still open when [`flatten_all`] gave up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResidualPosition {
+    pub label: String,
+    pub coin: String,
+    /// Signed size in coins
+    pub size: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlattenReport {
+    pub cancelled_orders: usize,
+    pub close_orders: usize,
+    pub rounds: usize,
+    pub residual: Vec<ResidualPosition>,
+}
+
+impl FlattenReport {
+    pub fn is_flat(&self) -> bool {
+        self.residual.is_empty()
+    }
+}
+
+/// Cancel every open order of `account` on `coins` (every coin if empty).
+async fn cancel_all(
+    info_client: &InfoClient,
+    account: &FlattenAccount<'_>,
+    coins: &[String],
+) -> eyre::Result<usize> {
+    let mut cancelled = 0;
+    for order in info_client
+        .open_orders(account.user)
+        .await?
+        .into_iter()
+        .filter(|order| coins.is_empty() || coins.contains(&order.coin))
+    {
+        let cancel = ClientCancelRequest { asset: order.coin.clone(), oid: order.oid };
+        match account.exchange_client.cancel(cancel, None).await? {
+            ExchangeResponseStatus::Ok(_) => cancelled += 1,
+            ExchangeResponseStatus::Err(e) => {
+                error!("[{}] Cancel error for {} {}: {}", account.label, order.coin, order.oid, e)
+            }
+        }
+    }
+    Ok(cancelled)
+}
+
+/// Open positions (coin, signed size) of `account` on `coins` (every coin if empty).
+async fn open_positions(
+    info_client: &InfoClient,
+    account: &FlattenAccount<'_>,
+    coins: &[String],
+) -> eyre::Result<Vec<(String, f64)>> {
+    let state = info_client.user_state(account.user).await?;
+    let mut positions = Vec::new();
+    for asset in state.asset_positions {
+        let size: f64 = asset.position.szi.parse()?;
+        if size != 0.0 && (coins.is_empty() || coins.contains(&asset.position.coin)) {
+            positions.push((asset.position.coin, size));
+        }
+    }
+    Ok(positions)
+}
+
+/// Send a reduce-only IOC order against `size` of `coin`. Returns whether it was accepted.
+async fn close_ioc(
+    account: &FlattenAccount<'_>,
+    pre_trade: &PreTradeChecker,
+    coin: &str,
+    size: f64,
+    mid: f64,
+    slippage_bps: f64,
+) -> eyre::Result<bool> {
+    let is_buy = size < 0.0;
+    let slippage = slippage_bps / 10_000.0;
+    let limit_px = pre_trade
+        .round_price(coin, if is_buy { mid * (1.0 + slippage) } else { mid * (1.0 - slippage) });
+    let sz = pre_trade.round_size(coin, size.abs());
+    info!(
+        "[{}] Flattening {} {}: {} {} at {}",
+        account.label,
+        size,
+        coin,
+        if is_buy { "buy" } else { "sell" },
+        sz,
+        limit_px
+    );
+    let order = ClientOrderRequest {
+        asset: coin.to_string(),
+        is_buy,
+        reduce_only: true,
+        limit_px,
+        sz,
+        cloid: None,
+        order_type: ClientOrder::Limit(ClientLimit { tif: "Ioc".to_string() }),
+    };
+    match account.exchange_client.order(order, None).await? {
+        ExchangeResponseStatus::Ok(response) => {
+            if let Some(ExchangeDataStatus::Error(e)) =
+                response.data.as_ref().and_then(|data| data.statuses.first())
+            {
+                error!("[{}] Flatten order error for {}: {}", account.label, coin, e);
+                return Ok(false);
+            }
+            Ok(true)
+        }
+        ExchangeResponseStatus::Err(e) => {
+            error!("[{}] Flatten order error for {}: {}", account.label, coin, e);
+            Ok(false)
+        }
+    }
+}
+
+/// Cancel all orders and close all positions of `accounts` on `coins` (every coin if empty),
+/// retrying until the clearinghouse state shows them flat or the timeout passed. Prices and
+/// sizes are rounded with `pre_trade`; the pre-trade limits themselves are not applied.
+///
+/// A failed request only fails its account's round: it is logged and retried with the next
+/// round. If the last round before the timeout still failed, its error is returned.
+pub async fn flatten_all(
+    info_client: &InfoClient,
+    pre_trade: &PreTradeChecker,
+    accounts: &[FlattenAccount<'_>],
+    coins: &[String],
+    config: &FlattenConfig,
+) -> eyre::Result<FlattenReport> {
+    let deadline = Instant::now() + Duration::from_secs(config.flatten_timeout_secs);
+    let mut report = FlattenReport::default();
+    loop {
+        report.rounds += 1;
+        report.residual.clear();
+        let mut round_error = None;
+        match info_client.all_mids().await {
+            Ok(mids) => {
+                for account in accounts {
+                    let flattened = flatten_round(
+                        info_client,
+                        pre_trade,
+                        account,
+                        coins,
+                        &mids,
+                        config,
+                        &mut report,
+                    )
+                    .await;
+                    if let Err(e) = flattened {
+                        error!("[{}] Flatten round {} failed: {}", account.label, report.rounds, e);
+                        round_error = Some(e);
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Flatten round {} failed to fetch mids: {}", report.rounds, e);
+                round_error = Some(e.into());
+            }
+        }
+
+        if round_error.is_none() && report.residual.is_empty() {
+            info!(
+                "Flat after {} round(s): {} order(s) cancelled, {} close order(s)",
+                report.rounds, report.cancelled_orders, report.close_orders
+            );
+            return Ok(report);
+        }
+        if Instant::now() >= deadline {
+            if let Some(e) = round_error {
+                return Err(e);
+            }
+            break;
+        }
+        sleep(Duration::from_secs(config.flatten_retry_secs)).await;
+    }
+
+    // The last round sent orders without checking them; one final look at the state
+    let mut residual = Vec::new();
+    for account in accounts {
+        for (coin, size) in open_positions(info_client, account, coins).await? {
+            residual.push(ResidualPosition { label: account.label.to_string(), coin, size });
+        }
+    }
+    for position in &residual {
+        error!(
+            "[{}] Still holding {} {} after {}s",
+            position.label, position.size, position.coin, config.flatten_timeout_secs
+        );
+    }
+    report.residual = residual;
+    Ok(report)
+}
+
+/// One round of [`flatten_all`] on `account`: cancel its orders, then send a close order for
+/// every position left, recording them in `report.residual`.
+async fn flatten_round(
+    info_client: &InfoClient,
+    pre_trade: &PreTradeChecker,
+    account: &FlattenAccount<'_>,
+    coins: &[String],
+    mids: &HashMap<String, String>,
+    config: &FlattenConfig,
+    report: &mut FlattenReport,
+) -> eyre::Result<()> {
+    // Resting orders could re-open what we close, and reduce-only orders block the IOCs
+    report.cancelled_orders += cancel_all(info_client, account, coins).await?;
+    for (coin, size) in open_positions(info_client, account, coins).await? {
+        report.residual.push(ResidualPosition {
+            label: account.label.to_string(),
+            coin: coin.clone(),
+            size,
+        });
+        let Some(mid) = mids.get(&coin).and_then(|mid| mid.parse::<f64>().ok()) else {
+            warn!("[{}] No mid for {}, can't flatten it", account.label, coin);
+            continue;
+        };
+        if close_ioc(account, pre_trade, &coin, size, mid, config.flatten_slippage_bps).await? {
+            report.close_orders += 1;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,139 @@
+//! Funding-rate awareness for the dual-channel strategies.
+//!
+//! Hyperliquid perps settle funding every hour: when the rate is positive longs pay shorts, when
+//! it is negative shorts pay longs. [`FundingBias`] tracks the rate published in `AssetCtx`
+//! updates and scales down (or skips) the channel on the paying side once the rate exceeds a
+//! threshold. [`FundingLedger`] estimates the funding paid or received by one channel.
+#![allow(missing_docs)]
+
+use hyperliquid_rust_sdk::AssetCtx;
+use serde::Deserialize;
+
+/// Funding is settled on every full hour.
+const FUNDING_INTERVAL_SECS: i64 = 3600;
+
+/// What to do with the channel on the paying side when funding is above the threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum FundingAction {
+    /// Open the paying channel with `paying_scale` times its usual size
+    #[default]
+    Skew,
+    /// Don't open the paying channel until funding drops back below the threshold
+    Skip,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FundingBiasConfig {
+    /// Absolute hourly funding rate above which the paying side is penalised
+    /// (e.g. 0.0001 = 0.01% per hour)
+    pub threshold: f64,
+    pub action: FundingAction,
+    /// Size multiplier for the paying side with `FundingAction::Skew`
+    pub paying_scale: f64,
+}
+
+impl Default for FundingBiasConfig {
+    fn default() -> Self {
+        Self { threshold: 0.0001, action: FundingAction::Skew, paying_scale: 0.5 }
+    }
+}
+
+/// Latest funding rate and oracle price of one perp.
+#[derive(Debug, Clone)]
+pub struct FundingBias {
+    config: FundingBiasConfig,
+    rate: Option<f64>,
+    oracle_px: f64,
+}
+
+impl FundingBias {
+    pub fn new(config: FundingBiasConfig) -> Self {
+        Self { config, rate: None, oracle_px: 0.0 }
+    }
+
+    /// Record the funding rate from an `ActiveAssetCtx` update. Spot contexts are ignored.
+    pub fn update(&mut self, ctx: &AssetCtx) -> eyre::Result<()> {
+        if let AssetCtx::Perps(perps) = ctx {
+            self.rate = Some(perps.funding.parse()?);
+            self.oracle_px = perps.oracle_px.parse()?;
+        }
+        Ok(())
+    }
+
+    /// Current hourly funding rate, `None` until the first update.
+    pub fn rate(&self) -> Option<f64> {
+        self.rate
+    }
+
+    pub fn oracle_px(&self) -> f64 {
+        self.oracle_px
+    }
+
+    /// Whether the given side currently pays funding.
+    pub fn pays(&self, is_long: bool) -> bool {
+        match self.rate {
+            Some(rate) if is_long => rate > 0.0,
+            Some(rate) => rate < 0.0,
+            None => false,
+        }
+    }
+
+    /// Multiplier to apply to the channel's size; `0.0` means the channel should stay flat.
+    pub fn size_multiplier(&self, is_long: bool) -> f64 {
+        let above_threshold = self.rate.is_some_and(|rate| rate.abs() > self.config.threshold);
+        if !above_threshold || !self.pays(is_long) {
+            return 1.0;
+        }
+        match self.config.action {
+            FundingAction::Skew => self.config.paying_scale,
+            FundingAction::Skip => 0.0,
+        }
+    }
+}
+
+/// Funding paid or received by one channel, estimated from the published rate at each hourly
+/// settlement the channel's position was held through.
+#[derive(Debug, Clone, Default)]
+pub struct FundingLedger {
+    /// Net funding in USD; positive means received
+    total: f64,
+    /// Last settlement (hour index) that was accounted for
+    last_settlement: Option<i64>,
+}
+
+impl FundingLedger {
+    pub fn total(&self) -> f64 {
+        self.total
+    }
+
+    /// Book the settlements passed since the last call. `signed_size` is the position in base
+    /// units (positive long, zero when flat) and `opened_at` its entry time, both in seconds.
+    /// Returns the funding booked by this call.
+    pub fn accrue(
+        &mut self,
+        bias: &FundingBias,
+        signed_size: f64,
+        opened_at: i64,
+        now: i64,
+    ) -> f64 {
+        let settlement = now.div_euclid(FUNDING_INTERVAL_SECS);
+        let Some(last) = self.last_settlement.replace(settlement) else {
+            return 0.0;
+        };
+        let Some(rate) = bias.rate() else {
+            return 0.0;
+        };
+        if settlement <= last || signed_size == 0.0 {
+            return 0.0;
+        }
+
+        // Only settlements after the position was opened are paid.
+        let first_held = last.max(opened_at.div_euclid(FUNDING_INTERVAL_SECS)) + 1;
+        let settlements = (settlement - first_held + 1).max(0) as f64;
+        let payment = -signed_size * bias.oracle_px() * rate * settlements;
+        self.total += payment;
+        payment
+    }
+}
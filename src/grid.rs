@@ -0,0 +1,451 @@
+//! Grid trading around a reference price.
+//!
+//! `levels` buy orders are spaced one `step_percent` apart below the reference and `levels`
+//! sell orders above it. When an entry fills, a take-profit one step away is placed; when that
+//! fills, the level is re-armed with a fresh entry order. Level state is written to disk after
+//! every change so a restarted bot picks up its resting orders instead of placing them again.
+#![allow(missing_docs)]
+
+use std::{collections::HashSet, fs, path::Path};
+
+use hyperliquid_rust_sdk::TradeInfo;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    pretrade::PreTradeChecker,
+    utils::{create_trade, BotParams, Direction, Trade, TradingAccount},
+};
+
+#[derive(Debug, Clone)]
+pub struct GridParams {
+    /// Number of levels on each side of the reference price
+    pub levels: usize,
+    /// Distance between levels, and from each entry to its take-profit (e.g. 0.005 = 0.5%)
+    pub step_percent: f64,
+    /// Margin per level in USD; the level notional is `amount * leverage`
+    pub amount: f64,
+    pub leverage: f64,
+    /// File the grid state is persisted to
+    pub state_path: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LevelState {
+    /// No order on the book for this level
+    Idle,
+    /// Entry order resting
+    Armed,
+    /// Entry filled, take-profit resting
+    Open,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GridLevel {
+    /// Entry, take-profit and size of the level; `entry_time` is refreshed on each fill
+    pub trade: Trade,
+    pub state: LevelState,
+    /// Resting order for the current state (entry when armed, take-profit when open)
+    pub oid: Option<u64>,
+    /// Size filled so far on the resting order
+    #[serde(default)]
+    pub filled: f64,
+    /// Size held by an open level whose entry was cancelled after a partial fill, when less
+    /// than the level size
+    #[serde(default)]
+    pub open_size: Option<f64>,
+}
+
+impl GridLevel {
+    fn is_buy_entry(&self) -> bool {
+        self.trade.direction == Direction::Long
+    }
+
+    /// Size of the order resting (or to be placed) for the current state.
+    fn order_size(&self) -> f64 {
+        match self.state {
+            LevelState::Open => self.open_size.unwrap_or(self.trade.size),
+            _ => self.trade.size,
+        }
+    }
+
+    /// Size the level holds, partial fills included.
+    fn held(&self) -> f64 {
+        match self.state {
+            LevelState::Idle => 0.0,
+            LevelState::Armed => self.filled,
+            LevelState::Open => self.order_size() - self.filled,
+        }
+    }
+
+    /// Signed change of the position should the rest of the resting order fill.
+    fn pending_fill(&self) -> f64 {
+        let rest = self.order_size() - self.filled;
+        match (self.state, self.is_buy_entry()) {
+            (LevelState::Idle, _) => 0.0,
+            (LevelState::Armed, true) | (LevelState::Open, false) => rest,
+            (LevelState::Armed, false) | (LevelState::Open, true) => -rest,
+        }
+    }
+
+    /// Settle the level after its resting order went away without filling completely: what
+    /// the entry filled stays open for its take-profit, an unfilled entry goes back to idle.
+    fn settle_unfilled(&mut self) {
+        let held = self.held();
+        self.state = if held > f64::EPSILON { LevelState::Open } else { LevelState::Idle };
+        self.open_size = (self.state == LevelState::Open && held + f64::EPSILON < self.trade.size)
+            .then_some(held);
+        self.filled = 0.0;
+        self.oid = None;
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GridState {
+    pub asset: String,
+    pub reference_price: f64,
+    pub levels: Vec<GridLevel>,
+}
+
+#[derive(Debug)]
+pub struct Grid {
+    params: GridParams,
+    state: GridState,
+}
+
+impl Grid {
+    /// Load the persisted grid for `asset`, or lay out a new one around `reference_price`.
+    pub fn load_or_new(
+        params: GridParams,
+        asset: &str,
+        reference_price: f64,
+        pre_trade: &PreTradeChecker,
+    ) -> eyre::Result<Self> {
+        if Path::new(&params.state_path).exists() {
+            let state: GridState = serde_json::from_str(&fs::read_to_string(&params.state_path)?)?;
+            if state.asset == asset {
+                info!(
+                    "Loaded grid for {} around {:.4} from {}",
+                    asset, state.reference_price, params.state_path
+                );
+                return Ok(Self { params, state });
+            }
+            warn!(
+                "Grid state in {} is for {}, starting a new grid",
+                params.state_path, state.asset
+            );
+        }
+
+        let bot_params = BotParams {
+            amount: params.amount,
+            leverage: params.leverage,
+            tp_percent: params.step_percent,
+            sl_percent: 0.0,
+        };
+        let mut levels = Vec::with_capacity(params.levels * 2);
+        for i in 1..=params.levels {
+            let offset = params.step_percent * i as f64;
+            for is_long in [true, false] {
+                let price = if is_long {
+                    reference_price * (1.0 - offset)
+                } else {
+                    reference_price * (1.0 + offset)
+                };
+                let trade = create_trade(is_long, price, &bot_params, pre_trade, asset);
+                levels.push(GridLevel {
+                    trade,
+                    state: LevelState::Idle,
+                    oid: None,
+                    filled: 0.0,
+                    open_size: None,
+                });
+            }
+        }
+
+        let grid =
+            Self { params, state: GridState { asset: asset.to_string(), reference_price, levels } };
+        grid.save()?;
+        info!("Laid out {} grid levels around {:.4}", grid.state.levels.len(), reference_price);
+        Ok(grid)
+    }
+
+    pub fn state(&self) -> &GridState {
+        &self.state
+    }
+
+    /// Resting orders of every level.
+    pub fn open_oids(&self) -> HashSet<u64> {
+        self.state.levels.iter().filter_map(|level| level.oid).collect()
+    }
+
+    /// Signed position (coins) held by the levels, partial fills included.
+    pub fn position(&self) -> f64 {
+        self.state
+            .levels
+            .iter()
+            .map(|level| if level.is_buy_entry() { level.held() } else { -level.held() })
+            .sum()
+    }
+
+    /// Write the grid state atomically (temp file + rename).
+    pub fn save(&self) -> eyre::Result<()> {
+        if let Some(dir) = Path::new(&self.params.state_path).parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp_path = format!("{}.tmp", self.params.state_path);
+        fs::write(&tmp_path, serde_json::to_string_pretty(&self.state)?)?;
+        fs::rename(&tmp_path, &self.params.state_path)?;
+        Ok(())
+    }
+
+    /// Reconcile the levels with the account's open orders and its position on the asset
+    /// (signed coins).
+    ///
+    /// A level whose order is no longer open counts as filled only as far as the position
+    /// shows it: while the gap between `exchange_position` and [`Grid::position`] has room for
+    /// the rest of its order, the level advances (an armed entry becomes open, an open level is
+    /// re-armed). Any other missing order was cancelled: a partially filled entry stays open for
+    /// what it filled and an unfilled one goes back to idle. [`Grid::place_missing`] then places
+    /// the follow-up orders.
+    pub fn reconcile(
+        &mut self,
+        open_oids: &HashSet<u64>,
+        exchange_position: f64,
+    ) -> eyre::Result<()> {
+        let mut unexplained = exchange_position - self.position();
+        for level in &mut self.state.levels {
+            let Some(oid) = level.oid else { continue };
+            if open_oids.contains(&oid) {
+                continue;
+            }
+            let fill = level.pending_fill();
+            let filled = fill != 0.0 &&
+                fill.signum() == unexplained.signum() &&
+                fill.abs() <= unexplained.abs() + 1e-9;
+            if !filled {
+                warn!(
+                    "Grid {:?} order {} at {:.4} no longer open and not in the position, \
+                     taking it as cancelled",
+                    level.state,
+                    oid,
+                    if level.state == LevelState::Open {
+                        level.trade.tp_price
+                    } else {
+                        level.trade.entry_price
+                    }
+                );
+                level.settle_unfilled();
+                continue;
+            }
+            unexplained -= fill;
+            level.oid = None;
+            level.filled = 0.0;
+            level.state = match level.state {
+                LevelState::Armed => {
+                    warn!(
+                        "Grid entry {} at {:.4} no longer open, filled per the position",
+                        oid, level.trade.entry_price
+                    );
+                    LevelState::Open
+                }
+                LevelState::Open => {
+                    warn!(
+                        "Grid take-profit {} at {:.4} no longer open, filled per the position",
+                        oid, level.trade.tp_price
+                    );
+                    level.open_size = None;
+                    LevelState::Idle
+                }
+                LevelState::Idle => LevelState::Idle,
+            };
+        }
+        self.save()
+    }
+
+    /// Place orders for every level that should have one but doesn't.
+    pub async fn place_missing(&mut self, account: &TradingAccount) -> eyre::Result<()> {
+        let asset = self.state.asset.clone();
+        let mut changed = false;
+        for level in &mut self.state.levels {
+            if level.oid.is_some() {
+                continue;
+            }
+            let (is_buy, price, next_state) = match level.state {
+                LevelState::Idle | LevelState::Armed => {
+                    (level.is_buy_entry(), level.trade.entry_price, LevelState::Armed)
+                }
+                LevelState::Open => (!level.is_buy_entry(), level.trade.tp_price, LevelState::Open),
+            };
+            match account
+                .place_limit_order(&asset, is_buy, price, level.order_size(), "Alo", false, None)
+                .await?
+            {
+                Some(oid) => {
+                    level.oid = Some(oid);
+                    level.state = next_state;
+                    changed = true;
+                }
+                None => warn!(
+                    "Grid {} order at {:.4} was not placed; will retry",
+                    if is_buy { "buy" } else { "sell" },
+                    price
+                ),
+            }
+        }
+        if changed {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Advance the level owning the filled order. Take-profit fills are booked into
+    /// `account.closed_trades` and `total_pnl`. Returns whether a level changed state.
+    pub fn on_fill(
+        &mut self,
+        account: &mut TradingAccount,
+        fill: &TradeInfo,
+        total_pnl: &mut f64,
+    ) -> eyre::Result<bool> {
+        let Some(level) = self.state.levels.iter_mut().find(|level| level.oid == Some(fill.oid))
+        else {
+            return Ok(false);
+        };
+        let px: f64 = fill.px.parse()?;
+        let sz: f64 = fill.sz.parse()?;
+
+        // Partial fills keep the order resting; act once the full level size has traded.
+        level.filled += sz;
+        let size = level.order_size();
+        if level.filled + f64::EPSILON < size {
+            return Ok(false);
+        }
+        level.filled = 0.0;
+        level.oid = None;
+
+        match level.state {
+            LevelState::Armed => {
+                level.state = LevelState::Open;
+                level.trade.entry_time = chrono::Utc::now().timestamp();
+                info!(
+                    "Grid {:?} entry filled at {:.4}, take-profit at {:.4}",
+                    level.trade.direction, px, level.trade.tp_price
+                );
+            }
+            LevelState::Open => {
+                level.state = LevelState::Idle;
+                level.open_size = None;
+                let pnl = match level.trade.direction {
+                    Direction::Long => (px - level.trade.entry_price) * size,
+                    Direction::Short => (level.trade.entry_price - px) * size,
+                };
+                *total_pnl += pnl;
+                account.closed_trades.push(Trade { close_price: Some(px), size, ..level.trade });
+                info!(
+                    "Grid {:?} level {:.4} took profit at {:.4}, PnL: {:.4}",
+                    level.trade.direction, level.trade.entry_price, px, pnl
+                );
+            }
+            LevelState::Idle => {}
+        }
+
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Cancel every resting grid order. Open levels, and entries that filled partially, keep
+    /// what they hold so their take-profits are placed again when the grid is resumed.
+    pub async fn cancel_all(&mut self, account: &TradingAccount) -> eyre::Result<()> {
+        let asset = self.state.asset.clone();
+        for level in &mut self.state.levels {
+            if let Some(oid) = level.oid {
+                account.cancel_order(&asset, oid).await?;
+                level.settle_unfilled();
+            }
+        }
+        self.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(direction: Direction, state: LevelState, oid: u64, filled: f64) -> GridLevel {
+        GridLevel {
+            trade: Trade {
+                direction,
+                entry_price: 100.0,
+                entry_time: 0,
+                size: 2.0,
+                tp_price: if direction == Direction::Long { 101.0 } else { 99.0 },
+                sl_price: 0.0,
+                close_price: None,
+            },
+            state,
+            oid: Some(oid),
+            filled,
+            open_size: None,
+        }
+    }
+
+    fn grid(name: &str, levels: Vec<GridLevel>) -> Grid {
+        let state_path =
+            std::env::temp_dir().join(format!("grid-test-{}-{}.json", name, std::process::id()));
+        Grid {
+            params: GridParams {
+                levels: 1,
+                step_percent: 0.01,
+                amount: 100.0,
+                leverage: 2.0,
+                state_path: state_path.to_string_lossy().into_owned(),
+            },
+            state: GridState { asset: "ETH".to_string(), reference_price: 100.0, levels },
+        }
+    }
+
+    #[test]
+    fn reconcile_advances_only_levels_the_position_shows_filled() {
+        let mut grid = grid(
+            "advance",
+            vec![
+                level(Direction::Long, LevelState::Armed, 1, 0.0),
+                level(Direction::Short, LevelState::Armed, 2, 0.0),
+                level(Direction::Long, LevelState::Armed, 3, 0.0),
+            ],
+        );
+        // All three orders are gone but only one buy entry is in the position
+        grid.reconcile(&HashSet::new(), 2.0).unwrap();
+        let states: Vec<_> = grid.state().levels.iter().map(|level| level.state).collect();
+        assert_eq!(states, vec![LevelState::Open, LevelState::Idle, LevelState::Idle]);
+        assert!(grid.open_oids().is_empty());
+        assert_eq!(grid.position(), 2.0);
+    }
+
+    #[test]
+    fn reconcile_keeps_resting_orders_and_cancelled_take_profits() {
+        let mut grid = grid(
+            "resting",
+            vec![
+                level(Direction::Long, LevelState::Armed, 1, 0.0),
+                level(Direction::Short, LevelState::Open, 2, 0.0),
+            ],
+        );
+        // The take-profit was cancelled: the short is still held
+        grid.reconcile(&HashSet::from([1]), -2.0).unwrap();
+        let levels = &grid.state().levels;
+        assert_eq!((levels[0].state, levels[0].oid), (LevelState::Armed, Some(1)));
+        assert_eq!((levels[1].state, levels[1].oid), (LevelState::Open, None));
+        assert_eq!(grid.position(), -2.0);
+    }
+
+    #[test]
+    fn cancelled_partial_entry_stays_open_for_its_filled_size() {
+        let mut grid = grid("partial", vec![level(Direction::Long, LevelState::Armed, 1, 0.5)]);
+        grid.reconcile(&HashSet::new(), 0.5).unwrap();
+        let level = &grid.state().levels[0];
+        assert_eq!(level.state, LevelState::Open);
+        assert_eq!(level.open_size, Some(0.5));
+        assert_eq!(level.order_size(), 0.5);
+        assert_eq!(grid.position(), 0.5);
+    }
+}
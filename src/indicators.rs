@@ -0,0 +1,821 @@
+//! Incremental technical indicators over parsed candles.
+//!
+//! Every indicator is fed one [`Candle`] at a time through [`Indicator::update`] and reports
+//! `None` from [`Indicator::value`] until it has seen enough candles to be meaningful.
+//! Live strategies and the backtester both use these, so a signal computed on cached candles
+//! matches the one computed on the websocket stream.
+#![allow(missing_docs)]
+
+use std::collections::VecDeque;
+
+use hyperliquid_rust_sdk::CandleData;
+use serde::{Deserialize, Serialize};
+
+use crate::caching::SerializableCandleData;
+
+/// A candle with numeric OHLCV fields.
+///
+/// Hyperliquid sends prices and volume as strings; parse them once with `Candle::try_from`
+/// instead of at every use site.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Candle {
+    pub time_open: u64,
+    pub time_close: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+}
+
+impl Candle {
+    /// Typical price of the candle, (high + low + close) / 3.
+    pub fn typical_price(&self) -> f64 {
+        (self.high + self.low + self.close) / 3.0
+    }
+}
+
+impl TryFrom<&CandleData> for Candle {
+    type Error = std::num::ParseFloatError;
+
+    fn try_from(candle: &CandleData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            time_open: candle.time_open,
+            time_close: candle.time_close,
+            open: candle.open.parse()?,
+            high: candle.high.parse()?,
+            low: candle.low.parse()?,
+            close: candle.close.parse()?,
+            volume: candle.volume.parse()?,
+        })
+    }
+}
+
+impl TryFrom<&SerializableCandleData> for Candle {
+    type Error = std::num::ParseFloatError;
+
+    fn try_from(candle: &SerializableCandleData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            time_open: candle.time_open,
+            time_close: candle.time_close,
+            open: candle.open.parse()?,
+            high: candle.high.parse()?,
+            low: candle.low.parse()?,
+            close: candle.close.parse()?,
+            volume: candle.volume.parse()?,
+        })
+    }
+}
+
+/// Candles built from a stream of prices, one per `bar_secs`.
+///
+/// For price feeds without candles (mids, cached ticks); the bars have no volume.
+#[derive(Debug, Clone)]
+pub struct TickBars {
+    bar_secs: u64,
+    bar: Option<Candle>,
+}
+
+impl TickBars {
+    pub fn new(bar_secs: u64) -> Self {
+        Self { bar_secs: bar_secs.max(1), bar: None }
+    }
+
+    pub fn bar_secs(&self) -> u64 {
+        self.bar_secs
+    }
+
+    /// Record a price seen at `ts` (UTC seconds). Returns the bar it closed, if any.
+    pub fn update(&mut self, ts: i64, price: f64) -> Option<Candle> {
+        // Candle timestamps are in milliseconds.
+        let time_open = (ts.max(0) as u64 / self.bar_secs) * self.bar_secs * 1000;
+        match &mut self.bar {
+            Some(bar) if bar.time_open == time_open => {
+                bar.high = bar.high.max(price);
+                bar.low = bar.low.min(price);
+                bar.close = price;
+                None
+            }
+            bar => bar.replace(Candle {
+                time_open,
+                time_close: time_open + self.bar_secs * 1000,
+                open: price,
+                high: price,
+                low: price,
+                close: price,
+                volume: 0.0,
+            }),
+        }
+    }
+
+    /// The bar still forming.
+    pub fn current(&self) -> Option<&Candle> {
+        self.bar.as_ref()
+    }
+}
+
+/// An indicator that is updated incrementally, one candle at a time.
+pub trait Indicator {
+    /// What the indicator reports once warmed up.
+    type Output;
+
+    /// Feed the next closed candle.
+    fn update(&mut self, candle: &Candle);
+
+    /// Current value, or `None` while the indicator is still warming up.
+    fn value(&self) -> Option<Self::Output>;
+
+    /// Whether enough candles have been seen for `value` to be meaningful.
+    fn is_ready(&self) -> bool {
+        self.value().is_some()
+    }
+}
+
+/// Fixed-size window of values with a running sum.
+#[derive(Debug, Clone)]
+struct Window {
+    period: usize,
+    values: VecDeque<f64>,
+    sum: f64,
+}
+
+impl Window {
+    fn new(period: usize) -> Self {
+        let period = period.max(1);
+        Self { period, values: VecDeque::with_capacity(period + 1), sum: 0.0 }
+    }
+
+    fn push(&mut self, value: f64) {
+        self.values.push_back(value);
+        self.sum += value;
+        if self.values.len() > self.period {
+            if let Some(old) = self.values.pop_front() {
+                self.sum -= old;
+            }
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.values.len() >= self.period
+    }
+
+    fn mean(&self) -> f64 {
+        self.sum / self.values.len() as f64
+    }
+
+    /// Population standard deviation of the window.
+    fn std_dev(&self) -> f64 {
+        let mean = self.mean();
+        let var =
+            self.values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / self.values.len() as f64;
+        var.sqrt()
+    }
+}
+
+/// Simple moving average of closes.
+#[derive(Debug, Clone)]
+pub struct Sma {
+    window: Window,
+}
+
+impl Sma {
+    pub fn new(period: usize) -> Self {
+        Self { window: Window::new(period) }
+    }
+}
+
+impl Indicator for Sma {
+    type Output = f64;
+
+    fn update(&mut self, candle: &Candle) {
+        self.window.push(candle.close);
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.window.is_full().then(|| self.window.mean())
+    }
+}
+
+/// Exponential moving average of closes, seeded with the SMA of the first `period` closes.
+#[derive(Debug, Clone)]
+pub struct Ema {
+    period: usize,
+    alpha: f64,
+    seen: usize,
+    seed_sum: f64,
+    value: f64,
+}
+
+impl Ema {
+    pub fn new(period: usize) -> Self {
+        let period = period.max(1);
+        Self { period, alpha: 2.0 / (period as f64 + 1.0), seen: 0, seed_sum: 0.0, value: 0.0 }
+    }
+
+    /// Feed a raw value instead of a candle close.
+    pub fn update_value(&mut self, x: f64) {
+        self.seen += 1;
+        if self.seen < self.period {
+            self.seed_sum += x;
+        } else if self.seen == self.period {
+            self.value = (self.seed_sum + x) / self.period as f64;
+        } else {
+            self.value += self.alpha * (x - self.value);
+        }
+    }
+}
+
+impl Indicator for Ema {
+    type Output = f64;
+
+    fn update(&mut self, candle: &Candle) {
+        self.update_value(candle.close);
+    }
+
+    fn value(&self) -> Option<f64> {
+        (self.seen >= self.period).then_some(self.value)
+    }
+}
+
+/// Wilder-smoothed running average, used by RSI and ATR.
+#[derive(Debug, Clone)]
+struct WilderAverage {
+    period: usize,
+    seen: usize,
+    value: f64,
+}
+
+impl WilderAverage {
+    fn new(period: usize) -> Self {
+        Self { period: period.max(1), seen: 0, value: 0.0 }
+    }
+
+    fn update(&mut self, x: f64) {
+        self.seen += 1;
+        if self.seen <= self.period {
+            // Plain average while seeding.
+            self.value += (x - self.value) / self.seen as f64;
+        } else {
+            self.value = (self.value * (self.period as f64 - 1.0) + x) / self.period as f64;
+        }
+    }
+
+    fn value(&self) -> Option<f64> {
+        (self.seen >= self.period).then_some(self.value)
+    }
+}
+
+/// Relative strength index of closes (Wilder's smoothing), in the range 0..=100.
+#[derive(Debug, Clone)]
+pub struct Rsi {
+    prev_close: Option<f64>,
+    gains: WilderAverage,
+    losses: WilderAverage,
+}
+
+impl Rsi {
+    pub fn new(period: usize) -> Self {
+        Self {
+            prev_close: None,
+            gains: WilderAverage::new(period),
+            losses: WilderAverage::new(period),
+        }
+    }
+}
+
+impl Indicator for Rsi {
+    type Output = f64;
+
+    fn update(&mut self, candle: &Candle) {
+        if let Some(prev) = self.prev_close {
+            let change = candle.close - prev;
+            self.gains.update(change.max(0.0));
+            self.losses.update((-change).max(0.0));
+        }
+        self.prev_close = Some(candle.close);
+    }
+
+    fn value(&self) -> Option<f64> {
+        let gain = self.gains.value()?;
+        let loss = self.losses.value()?;
+        if loss == 0.0 {
+            return Some(if gain == 0.0 { 50.0 } else { 100.0 });
+        }
+        Some(100.0 - 100.0 / (1.0 + gain / loss))
+    }
+}
+
+/// Average true range (Wilder's smoothing), in price units.
+#[derive(Debug, Clone)]
+pub struct Atr {
+    prev_close: Option<f64>,
+    average: WilderAverage,
+}
+
+impl Atr {
+    pub fn new(period: usize) -> Self {
+        Self { prev_close: None, average: WilderAverage::new(period) }
+    }
+}
+
+impl Indicator for Atr {
+    type Output = f64;
+
+    fn update(&mut self, candle: &Candle) {
+        let range = candle.high - candle.low;
+        let true_range = match self.prev_close {
+            Some(prev) => range.max((candle.high - prev).abs()).max((candle.low - prev).abs()),
+            None => range,
+        };
+        self.average.update(true_range);
+        self.prev_close = Some(candle.close);
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.average.value()
+    }
+}
+
+/// Upper, middle and lower band values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bands {
+    pub lower: f64,
+    pub middle: f64,
+    pub upper: f64,
+}
+
+/// Bollinger bands: SMA of closes plus/minus `k` standard deviations.
+#[derive(Debug, Clone)]
+pub struct BollingerBands {
+    window: Window,
+    k: f64,
+}
+
+impl BollingerBands {
+    pub fn new(period: usize, k: f64) -> Self {
+        Self { window: Window::new(period), k }
+    }
+}
+
+impl Indicator for BollingerBands {
+    type Output = Bands;
+
+    fn update(&mut self, candle: &Candle) {
+        self.window.push(candle.close);
+    }
+
+    fn value(&self) -> Option<Bands> {
+        if !self.window.is_full() {
+            return None;
+        }
+        let middle = self.window.mean();
+        let width = self.k * self.window.std_dev();
+        Some(Bands { lower: middle - width, middle, upper: middle + width })
+    }
+}
+
+/// Realized volatility: standard deviation of log returns of closes over a window.
+///
+/// The value is per candle; use [`RealizedVol::annualized`] to scale it.
+#[derive(Debug, Clone)]
+pub struct RealizedVol {
+    prev_close: Option<f64>,
+    returns: Window,
+}
+
+impl RealizedVol {
+    pub fn new(period: usize) -> Self {
+        Self { prev_close: None, returns: Window::new(period) }
+    }
+
+    /// Volatility scaled by the number of candles per year (e.g. 8760 for hourly candles).
+    pub fn annualized(&self, periods_per_year: f64) -> Option<f64> {
+        self.value().map(|vol| vol * periods_per_year.sqrt())
+    }
+}
+
+impl Indicator for RealizedVol {
+    type Output = f64;
+
+    fn update(&mut self, candle: &Candle) {
+        if let Some(prev) = self.prev_close {
+            if prev > 0.0 && candle.close > 0.0 {
+                self.returns.push((candle.close / prev).ln());
+            }
+        }
+        self.prev_close = Some(candle.close);
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.returns.is_full().then(|| self.returns.std_dev())
+    }
+}
+
+/// Average directional index (Wilder's smoothing), in the range 0..=100.
+///
+/// Measures trend strength regardless of direction: high values mean a trending market.
+#[derive(Debug, Clone)]
+pub struct Adx {
+    prev: Option<Candle>,
+    true_range: WilderAverage,
+    plus_dm: WilderAverage,
+    minus_dm: WilderAverage,
+    dx: WilderAverage,
+}
+
+impl Adx {
+    pub fn new(period: usize) -> Self {
+        Self {
+            prev: None,
+            true_range: WilderAverage::new(period),
+            plus_dm: WilderAverage::new(period),
+            minus_dm: WilderAverage::new(period),
+            dx: WilderAverage::new(period),
+        }
+    }
+}
+
+impl Indicator for Adx {
+    type Output = f64;
+
+    fn update(&mut self, candle: &Candle) {
+        if let Some(prev) = self.prev {
+            let up = candle.high - prev.high;
+            let down = prev.low - candle.low;
+            let true_range = (candle.high - candle.low)
+                .max((candle.high - prev.close).abs())
+                .max((candle.low - prev.close).abs());
+            self.true_range.update(true_range);
+            self.plus_dm.update(if up > down && up > 0.0 { up } else { 0.0 });
+            self.minus_dm.update(if down > up && down > 0.0 { down } else { 0.0 });
+
+            if let (Some(tr), Some(plus), Some(minus)) =
+                (self.true_range.value(), self.plus_dm.value(), self.minus_dm.value())
+            {
+                if tr > 0.0 {
+                    let (plus_di, minus_di) = (plus / tr, minus / tr);
+                    let sum = plus_di + minus_di;
+                    self.dx.update(if sum > 0.0 {
+                        100.0 * (plus_di - minus_di).abs() / sum
+                    } else {
+                        0.0
+                    });
+                }
+            }
+        }
+        self.prev = Some(*candle);
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.dx.value()
+    }
+}
+
+/// Hurst exponent of log closes, from how the spread of `lag`-candle changes grows with `lag`.
+///
+/// Around 0.5 for a random walk, above for trending and below for mean-reverting series.
+#[derive(Debug, Clone)]
+pub struct Hurst {
+    closes: VecDeque<f64>,
+    period: usize,
+    max_lag: usize,
+}
+
+impl Hurst {
+    /// `period` closes, lags `2..=max_lag`; `period` should be several times `max_lag`.
+    pub fn new(period: usize, max_lag: usize) -> Self {
+        let max_lag = max_lag.max(3);
+        Self { closes: VecDeque::new(), period: period.max(2 * max_lag), max_lag }
+    }
+}
+
+impl Indicator for Hurst {
+    type Output = f64;
+
+    fn update(&mut self, candle: &Candle) {
+        if candle.close > 0.0 {
+            self.closes.push_back(candle.close.ln());
+            if self.closes.len() > self.period {
+                self.closes.pop_front();
+            }
+        }
+    }
+
+    fn value(&self) -> Option<f64> {
+        if self.closes.len() < self.period {
+            return None;
+        }
+        let points: Vec<(f64, f64)> = (2..=self.max_lag)
+            .filter_map(|lag| {
+                let mut diffs = Window::new(self.closes.len() - lag);
+                for (a, b) in self.closes.iter().zip(self.closes.iter().skip(lag)) {
+                    diffs.push(b - a);
+                }
+                let std = diffs.std_dev();
+                (std > 0.0).then(|| ((lag as f64).ln(), std.ln()))
+            })
+            .collect();
+        if points.len() < 2 {
+            return None;
+        }
+        // Least-squares slope of ln(std) on ln(lag)
+        let n = points.len() as f64;
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let cov: f64 = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+        let var: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+        (var > 0.0).then(|| cov / var)
+    }
+}
+
+/// Which candles a [`Vwap`] averages over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum VwapWindow {
+    /// The last `periods` candles; older candles are evicted as new ones arrive.
+    Rolling { periods: usize },
+    /// Every candle since the current session started. Sessions are `length_secs` long and
+    /// aligned to the unix epoch shifted by `offset_secs`, so a UTC day is
+    /// `{ length_secs: 86400, offset_secs: 0 }`.
+    Session { length_secs: u64, offset_secs: u64 },
+}
+
+impl VwapWindow {
+    /// Session anchored at 00:00 UTC.
+    pub fn utc_day() -> Self {
+        VwapWindow::Session { length_secs: 86_400, offset_secs: 0 }
+    }
+}
+
+/// Price each candle contributes to a [`Vwap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VwapPrice {
+    Close,
+    /// (high + low + close) / 3
+    #[default]
+    Typical,
+}
+
+/// Volume-weighted average price over a rolling or session-anchored window, with
+/// volume-weighted standard-deviation bands.
+#[derive(Debug, Clone)]
+pub struct Vwap {
+    window: VwapWindow,
+    price: VwapPrice,
+    /// (price * volume, price^2 * volume, volume) per candle in the window
+    entries: VecDeque<(f64, f64, f64)>,
+    sum_pv: f64,
+    sum_p2v: f64,
+    sum_v: f64,
+    session: Option<u64>,
+}
+
+impl Vwap {
+    pub fn new(window: VwapWindow, price: VwapPrice) -> Self {
+        let window = match window {
+            VwapWindow::Rolling { periods } => VwapWindow::Rolling { periods: periods.max(1) },
+            VwapWindow::Session { length_secs, offset_secs } => {
+                VwapWindow::Session { length_secs: length_secs.max(1), offset_secs }
+            }
+        };
+        Self {
+            window,
+            price,
+            entries: VecDeque::new(),
+            sum_pv: 0.0,
+            sum_p2v: 0.0,
+            sum_v: 0.0,
+            session: None,
+        }
+    }
+
+    /// Rolling VWAP of typical price over the last `periods` candles.
+    pub fn rolling(periods: usize) -> Self {
+        Self::new(VwapWindow::Rolling { periods }, VwapPrice::Typical)
+    }
+
+    /// Number of candles currently in the window.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn reset(&mut self) {
+        self.entries.clear();
+        self.sum_pv = 0.0;
+        self.sum_p2v = 0.0;
+        self.sum_v = 0.0;
+    }
+
+    /// Volume-weighted standard deviation of price around the VWAP.
+    pub fn std_dev(&self) -> Option<f64> {
+        let vwap = self.value()?;
+        Some((self.sum_p2v / self.sum_v - vwap * vwap).max(0.0).sqrt())
+    }
+
+    /// VWAP plus/minus `k` volume-weighted standard deviations.
+    pub fn bands(&self, k: f64) -> Option<Bands> {
+        let middle = self.value()?;
+        let width = k * self.std_dev()?;
+        Some(Bands { lower: middle - width, middle, upper: middle + width })
+    }
+}
+
+impl Indicator for Vwap {
+    type Output = f64;
+
+    fn update(&mut self, candle: &Candle) {
+        if let VwapWindow::Session { length_secs, offset_secs } = self.window {
+            // Candle timestamps are in milliseconds.
+            let secs = (candle.time_open / 1000).saturating_sub(offset_secs);
+            let session = secs / length_secs;
+            if self.session != Some(session) {
+                self.reset();
+                self.session = Some(session);
+            }
+        }
+
+        let price = match self.price {
+            VwapPrice::Close => candle.close,
+            VwapPrice::Typical => candle.typical_price(),
+        };
+        let entry = (price * candle.volume, price * price * candle.volume, candle.volume);
+        self.entries.push_back(entry);
+        self.sum_pv += entry.0;
+        self.sum_p2v += entry.1;
+        self.sum_v += entry.2;
+
+        if let VwapWindow::Rolling { periods } = self.window {
+            if self.entries.len() > periods {
+                if let Some((pv, p2v, v)) = self.entries.pop_front() {
+                    self.sum_pv -= pv;
+                    self.sum_p2v -= p2v;
+                    self.sum_v -= v;
+                }
+            }
+        }
+    }
+
+    fn value(&self) -> Option<f64> {
+        let warm = match self.window {
+            VwapWindow::Rolling { periods } => self.entries.len() >= periods,
+            VwapWindow::Session { .. } => !self.entries.is_empty(),
+        };
+        (warm && self.sum_v > 0.0).then(|| self.sum_pv / self.sum_v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candle(time_open: u64, high: f64, low: f64, close: f64, volume: f64) -> Candle {
+        Candle { time_open, time_close: time_open + 60_000, open: close, high, low, close, volume }
+    }
+
+    fn closes(values: &[f64]) -> Vec<Candle> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, &close)| candle(i as u64 * 60_000, close, close, close, 1.0))
+            .collect()
+    }
+
+    fn feed<I: Indicator>(indicator: &mut I, candles: &[Candle]) {
+        for candle in candles {
+            indicator.update(candle);
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn sma_warms_up_and_slides() {
+        let mut sma = Sma::new(3);
+        feed(&mut sma, &closes(&[1.0, 2.0]));
+        assert_eq!(sma.value(), None);
+        feed(&mut sma, &closes(&[3.0]));
+        assert_close(sma.value().unwrap(), 2.0);
+        feed(&mut sma, &closes(&[10.0]));
+        assert_close(sma.value().unwrap(), 5.0);
+    }
+
+    #[test]
+    fn ema_is_seeded_with_the_sma() {
+        let mut ema = Ema::new(3);
+        feed(&mut ema, &closes(&[1.0, 2.0]));
+        assert!(!ema.is_ready());
+        feed(&mut ema, &closes(&[3.0]));
+        assert_close(ema.value().unwrap(), 2.0);
+        // alpha = 2 / (3 + 1)
+        feed(&mut ema, &closes(&[6.0]));
+        assert_close(ema.value().unwrap(), 4.0);
+    }
+
+    #[test]
+    fn rsi_bounds_and_wilder_smoothing() {
+        let mut rising = Rsi::new(3);
+        feed(&mut rising, &closes(&[1.0, 2.0, 3.0, 4.0]));
+        assert_close(rising.value().unwrap(), 100.0);
+
+        let mut flat = Rsi::new(3);
+        feed(&mut flat, &closes(&[5.0, 5.0, 5.0, 5.0]));
+        assert_close(flat.value().unwrap(), 50.0);
+
+        // Changes +2, -1, +1: average gain 1, average loss 1/3
+        let mut rsi = Rsi::new(3);
+        feed(&mut rsi, &closes(&[10.0, 12.0, 11.0]));
+        assert_eq!(rsi.value(), None);
+        feed(&mut rsi, &closes(&[12.0]));
+        assert_close(rsi.value().unwrap(), 75.0);
+        // Change -2: gain 2/3, loss (1/3 * 2 + 2) / 3 = 8/9
+        feed(&mut rsi, &closes(&[10.0]));
+        assert_close(rsi.value().unwrap(), 100.0 - 100.0 / (1.0 + 0.75));
+    }
+
+    #[test]
+    fn atr_uses_the_true_range() {
+        let mut atr = Atr::new(2);
+        atr.update(&candle(0, 11.0, 9.0, 10.0, 1.0));
+        assert_eq!(atr.value(), None);
+        // Gap up: true range from the previous close, 15 - 10
+        atr.update(&candle(60_000, 15.0, 14.0, 14.5, 1.0));
+        assert_close(atr.value().unwrap(), 3.5);
+        // Range 2, Wilder: (3.5 + 2) / 2
+        atr.update(&candle(120_000, 15.0, 13.0, 14.0, 1.0));
+        assert_close(atr.value().unwrap(), 2.75);
+    }
+
+    #[test]
+    fn bollinger_bands_use_the_population_std_dev() {
+        let mut bands = BollingerBands::new(8, 2.0);
+        feed(&mut bands, &closes(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0]));
+        assert_eq!(bands.value(), None);
+        // Mean 5, std dev 2
+        feed(&mut bands, &closes(&[9.0]));
+        assert_eq!(bands.value(), Some(Bands { lower: 1.0, middle: 5.0, upper: 9.0 }));
+    }
+
+    #[test]
+    fn realized_vol_of_constant_returns_is_zero() {
+        let mut vol = RealizedVol::new(3);
+        feed(&mut vol, &closes(&[1.0, 2.0, 4.0]));
+        assert_eq!(vol.value(), None);
+        feed(&mut vol, &closes(&[8.0]));
+        assert_close(vol.value().unwrap(), 0.0);
+
+        let mut vol = RealizedVol::new(2);
+        feed(&mut vol, &closes(&[100.0, 110.0, 99.0]));
+        let (up, down) = ((110.0f64 / 100.0).ln(), (99.0f64 / 110.0).ln());
+        let expected = (up - down).abs() / 2.0;
+        assert_close(vol.value().unwrap(), expected);
+        assert_close(vol.annualized(4.0).unwrap(), expected * 2.0);
+    }
+
+    #[test]
+    fn rolling_vwap_evicts_old_candles() {
+        let mut vwap = Vwap::new(VwapWindow::Rolling { periods: 2 }, VwapPrice::Close);
+        vwap.update(&candle(0, 10.0, 10.0, 10.0, 1.0));
+        assert_eq!(vwap.value(), None);
+        vwap.update(&candle(60_000, 20.0, 20.0, 20.0, 3.0));
+        assert_close(vwap.value().unwrap(), 17.5);
+        vwap.update(&candle(120_000, 30.0, 30.0, 30.0, 1.0));
+        assert_eq!(vwap.len(), 2);
+        assert_close(vwap.value().unwrap(), 22.5);
+        // Prices 20 (x3) and 30 (x1) around 22.5
+        assert_close(vwap.std_dev().unwrap(), 75.0f64.sqrt() / 2.0);
+        let bands = vwap.bands(2.0).unwrap();
+        assert_close(bands.upper - bands.middle, 75.0f64.sqrt());
+    }
+
+    #[test]
+    fn session_vwap_resets_at_the_anchor() {
+        let mut vwap = Vwap::new(VwapWindow::utc_day(), VwapPrice::Typical);
+        let day = 86_400_000;
+        vwap.update(&candle(day - 60_000, 12.0, 8.0, 10.0, 1.0));
+        assert_close(vwap.value().unwrap(), 10.0);
+        vwap.update(&candle(day, 21.0, 18.0, 21.0, 2.0));
+        assert_eq!(vwap.len(), 1);
+        assert_close(vwap.value().unwrap(), 20.0);
+        vwap.update(&candle(day + 60_000, 32.0, 28.0, 30.0, 2.0));
+        assert_close(vwap.value().unwrap(), 25.0);
+    }
+
+    #[test]
+    fn tick_bars_close_on_the_next_interval() {
+        let mut bars = TickBars::new(60);
+        assert_eq!(bars.update(0, 10.0), None);
+        assert_eq!(bars.update(30, 12.0), None);
+        assert_eq!(bars.update(59, 9.0), None);
+        let closed = bars.update(60, 11.0).unwrap();
+        assert_eq!((closed.open, closed.high, closed.low, closed.close), (10.0, 12.0, 9.0, 9.0));
+        assert_eq!((closed.time_open, closed.time_close), (0, 60_000));
+        assert_eq!(bars.current().map(|bar| bar.time_open), Some(60_000));
+    }
+}
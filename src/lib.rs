@@ -3,8 +3,54 @@
 #![deny(unused_must_use, rust_2018_idioms)]
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
+pub mod budget;
+
 pub mod caching;
 pub use caching::{load_ticks_from_cache, store_tick_to_cache};
 
+pub mod exposure;
+
+pub mod flatten;
+
+pub mod funding;
+
+pub mod grid;
+
+pub mod indicators;
+pub use indicators::{Candle, Indicator};
+
+pub mod margin;
+
+pub mod market_making;
+
+pub mod orderbook;
+pub use orderbook::OrderBook;
+
+pub mod pairs;
+
+pub mod pretrade;
+
+pub mod price_filter;
+
+pub mod regime;
+
+pub mod reconcile;
+
+pub mod reentry;
+
+pub mod risk;
+
+pub mod schedule;
+
+pub mod shutdown;
+
+pub mod signal;
+
+pub mod sizing;
+
+pub mod tape;
+
 pub mod utils;
 pub use utils::{get_price, get_size};
+
+pub mod watchdog;
@@ -0,0 +1,266 @@
+//! Margin and liquidation distance monitoring.
+//!
+//! Bots poll each account's clearinghouse state every `margin_poll_secs` and hand it to a
+//! [`MarginMonitor`] together with the mark and stop of every position they hold. A position
+//! whose liquidation price is closer than `liq_reduce_ratio` times its stop distance is reduced
+//! by `reduce_fraction`; closer than `liq_close_ratio` times the stop (by default: the exchange
+//! would liquidate before the stop triggers) it is closed. Margin usage above
+//! `max_margin_usage` is only alerted on.
+#![allow(missing_docs)]
+
+use std::collections::HashSet;
+
+use hyperliquid_rust_sdk::UserStateResponse;
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+#[derive(Debug, Clone, Deserialize, clap::Args)]
+#[serde(default)]
+pub struct MarginConfig {
+    /// Seconds between clearinghouse state polls (0 = no monitoring)
+    #[arg(long, default_value_t = 30)]
+    pub margin_poll_secs: u64,
+
+    /// Margin used / account value above which an alert is logged
+    #[arg(long, default_value_t = 0.8)]
+    pub max_margin_usage: f64,
+
+    /// Reduce a position once its liquidation distance is below this multiple of its stop
+    /// distance (0 = never)
+    #[arg(long, default_value_t = 1.5)]
+    pub liq_reduce_ratio: f64,
+
+    /// Close a position once its liquidation distance is below this multiple of its stop
+    /// distance (0 = never)
+    #[arg(long, default_value_t = 1.0)]
+    pub liq_close_ratio: f64,
+
+    /// Share of the position closed by a reduction
+    #[arg(long, default_value_t = 0.5)]
+    pub reduce_fraction: f64,
+}
+
+impl Default for MarginConfig {
+    fn default() -> Self {
+        Self {
+            margin_poll_secs: 30,
+            max_margin_usage: 0.8,
+            liq_reduce_ratio: 1.5,
+            liq_close_ratio: 1.0,
+            reduce_fraction: 0.5,
+        }
+    }
+}
+
+/// Margin state of one position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionMargin {
+    pub coin: String,
+    /// Signed size in coins
+    pub size: f64,
+    pub liquidation_px: Option<f64>,
+    pub margin_used: f64,
+    pub leverage: u32,
+}
+
+/// Margin state of one account.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarginSnapshot {
+    pub account_value: f64,
+    pub margin_used: f64,
+    pub positions: Vec<PositionMargin>,
+}
+
+impl MarginSnapshot {
+    pub fn from_state(state: &UserStateResponse) -> eyre::Result<Self> {
+        let positions = state
+            .asset_positions
+            .iter()
+            .map(|asset| {
+                let position = &asset.position;
+                Ok(PositionMargin {
+                    coin: position.coin.clone(),
+                    size: position.szi.parse()?,
+                    liquidation_px: position
+                        .liquidation_px
+                        .as_deref()
+                        .map(str::parse)
+                        .transpose()?,
+                    margin_used: position.margin_used.parse()?,
+                    leverage: position.leverage.value,
+                })
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+        Ok(Self {
+            account_value: state.margin_summary.account_value.parse()?,
+            margin_used: state.margin_summary.total_margin_used.parse()?,
+            positions,
+        })
+    }
+
+    /// Margin used / account value.
+    pub fn margin_usage(&self) -> Option<f64> {
+        (self.account_value > 0.0).then(|| self.margin_used / self.account_value)
+    }
+
+    pub fn position(&self, coin: &str) -> Option<&PositionMargin> {
+        self.positions.iter().find(|position| position.coin == coin && position.size != 0.0)
+    }
+}
+
+/// What the bot should do with one position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeRisk {
+    /// Close `fraction` of the position
+    Reduce {
+        fraction: f64,
+    },
+    Close,
+}
+
+/// A position the bot holds, as the bot sees it.
+#[derive(Debug, Clone, Copy)]
+pub struct HeldPosition<'a> {
+    pub coin: &'a str,
+    pub mark: f64,
+    /// Client-side stop; without one the position is never reduced or closed
+    pub stop: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MarginMonitor {
+    config: MarginConfig,
+    /// `(account, coin)` currently alerted on, so an alert is logged once per episode
+    alerted: HashSet<(String, String)>,
+}
+
+impl MarginMonitor {
+    pub fn new(config: MarginConfig) -> Self {
+        Self { config, alerted: HashSet::new() }
+    }
+
+    pub fn config(&self) -> &MarginConfig {
+        &self.config
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.margin_poll_secs > 0
+    }
+
+    fn alert(&mut self, label: &str, key: &str, active: bool) -> bool {
+        let key = (label.to_string(), key.to_string());
+        if active {
+            self.alerted.insert(key)
+        } else {
+            self.alerted.remove(&key);
+            false
+        }
+    }
+
+    /// Check account `label` and the position it holds on `held.coin`. Returns the de-risking
+    /// action for that position, if any.
+    pub fn check(
+        &mut self,
+        label: &str,
+        snapshot: &MarginSnapshot,
+        held: &HeldPosition<'_>,
+    ) -> Option<DeRisk> {
+        let usage = snapshot.margin_usage().unwrap_or_default();
+        let high_usage =
+            self.config.max_margin_usage > 0.0 && usage >= self.config.max_margin_usage;
+        if self.alert(label, "", high_usage) {
+            warn!(
+                "[{}] Margin usage {:.0}% (${:.2} of ${:.2}) above {:.0}%",
+                label,
+                usage * 100.0,
+                snapshot.margin_used,
+                snapshot.account_value,
+                self.config.max_margin_usage * 100.0
+            );
+        }
+
+        let position = snapshot.position(held.coin)?;
+        let liquidation_px = position.liquidation_px?;
+        if held.mark <= 0.0 {
+            return None;
+        }
+        let liq_distance = (held.mark - liquidation_px).abs() / held.mark;
+        let stop_distance = held.stop.map_or(0.0, |stop| (held.mark - stop).abs() / held.mark);
+
+        let below = |ratio: f64| ratio > 0.0 && liq_distance < ratio * stop_distance;
+        let action = if below(self.config.liq_close_ratio) {
+            Some(DeRisk::Close)
+        } else if below(self.config.liq_reduce_ratio) && self.config.reduce_fraction > 0.0 {
+            Some(DeRisk::Reduce { fraction: self.config.reduce_fraction.min(1.0) })
+        } else {
+            None
+        };
+
+        if self.alert(label, held.coin, action.is_some()) {
+            warn!(
+                "[{}] {} liquidation at {} is {:.2}% away, stop {:.2}% away ({}x): {:?}",
+                label,
+                held.coin,
+                liquidation_px,
+                liq_distance * 100.0,
+                stop_distance * 100.0,
+                position.leverage,
+                action
+            );
+        } else {
+            debug!(
+                "[{}] {} liquidation {:.2}% away, margin usage {:.0}%",
+                label,
+                held.coin,
+                liq_distance * 100.0,
+                usage * 100.0
+            );
+        }
+        action
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(liquidation_px: f64) -> MarginSnapshot {
+        MarginSnapshot {
+            account_value: 1000.0,
+            margin_used: 100.0,
+            positions: vec![PositionMargin {
+                coin: "ETH".to_string(),
+                size: 1.0,
+                liquidation_px: Some(liquidation_px),
+                margin_used: 100.0,
+                leverage: 10,
+            }],
+        }
+    }
+
+    fn check(liquidation_px: f64, stop: Option<f64>) -> Option<DeRisk> {
+        let mut monitor = MarginMonitor::new(MarginConfig::default());
+        let held = HeldPosition { coin: "ETH", mark: 100.0, stop };
+        monitor.check("LONG", &snapshot(liquidation_px), &held)
+    }
+
+    #[test]
+    fn derisks_on_liquidation_distance_against_the_stop() {
+        // Stop 2% away: reduce below 3%, close below 2%
+        assert_eq!(check(90.0, Some(98.0)), None);
+        assert_eq!(check(97.5, Some(98.0)), Some(DeRisk::Reduce { fraction: 0.5 }));
+        assert_eq!(check(98.5, Some(98.0)), Some(DeRisk::Close));
+    }
+
+    #[test]
+    fn position_without_stop_is_left_alone() {
+        assert_eq!(check(99.0, None), None);
+    }
+
+    #[test]
+    fn ignores_coins_not_held_on_the_exchange() {
+        let mut monitor = MarginMonitor::new(MarginConfig::default());
+        let held = HeldPosition { coin: "BTC", mark: 100.0, stop: Some(98.0) };
+        assert_eq!(monitor.check("LONG", &snapshot(99.0), &held), None);
+    }
+}
@@ -0,0 +1,311 @@
+//! Passive two-sided quoting on a single [`TradingAccount`].
+//!
+//! The maker keeps one post-only bid and one post-only ask around a reference price, skews both
+//! quotes against its inventory and shrinks (or stops quoting) the side that would grow inventory
+//! past the cap. Realized PnL is booked into the account's `closed_trades` like any other trade.
+#![allow(missing_docs)]
+
+use std::collections::HashSet;
+
+use chrono::Utc;
+use hyperliquid_rust_sdk::TradeInfo;
+use serde::Deserialize;
+use tracing::{debug, info, warn};
+
+use crate::{
+    pretrade::PreTradeChecker,
+    utils::{Direction, Trade, TradingAccount},
+};
+
+/// Price the quotes are centred on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum QuoteReference {
+    #[default]
+    Mid,
+    Microprice,
+}
+
+#[derive(Debug, Clone)]
+pub struct MarketMakingParams {
+    /// Margin per quote in USD; the quote notional is `amount * leverage`
+    pub amount: f64,
+    pub leverage: f64,
+    /// Distance between bid and ask, in basis points of the reference price
+    pub spread_bps: f64,
+    /// Shift of both quotes at full inventory, in basis points (against the inventory)
+    pub skew_bps: f64,
+    /// Reference move that triggers cancelling and re-placing the quotes, in basis points
+    pub requote_bps: f64,
+    /// Absolute inventory notional (USD) the quotes may take the inventory to
+    pub max_inventory: f64,
+}
+
+/// (price, size) to quote on one side, or `None` when that side is pulled.
+pub type TargetQuote = Option<(f64, f64)>;
+
+#[derive(Debug, Clone, Copy)]
+struct Quote {
+    oid: u64,
+    price: f64,
+    remaining: f64,
+}
+
+/// Quoting state for one asset on one account.
+#[derive(Debug)]
+pub struct MarketMaker {
+    params: MarketMakingParams,
+    bid: Option<Quote>,
+    ask: Option<Quote>,
+    /// Reference price the current quotes were computed from
+    quoted_reference: Option<f64>,
+    /// Signed position in base units (positive = long)
+    inventory: f64,
+    avg_entry: f64,
+}
+
+impl MarketMaker {
+    pub fn new(params: MarketMakingParams) -> Self {
+        Self {
+            params,
+            bid: None,
+            ask: None,
+            quoted_reference: None,
+            inventory: 0.0,
+            avg_entry: 0.0,
+        }
+    }
+
+    pub fn inventory(&self) -> f64 {
+        self.inventory
+    }
+
+    /// Average entry price of the inventory.
+    pub fn avg_entry(&self) -> f64 {
+        self.avg_entry
+    }
+
+    /// Resting quotes.
+    pub fn open_oids(&self) -> HashSet<u64> {
+        [self.bid, self.ask].into_iter().flatten().map(|quote| quote.oid).collect()
+    }
+
+    /// Take the exchange's inventory (and its entry price) and resting orders as the truth:
+    /// quotes no longer resting are forgotten and requoted on the next price.
+    pub fn correct(&mut self, inventory: f64, avg_entry: Option<f64>, resting: &HashSet<u64>) {
+        for quote in [&mut self.bid, &mut self.ask] {
+            if quote.is_some_and(|q| !resting.contains(&q.oid)) {
+                *quote = None;
+                self.quoted_reference = None;
+            }
+        }
+        self.inventory = inventory;
+        self.avg_entry = if inventory == 0.0 { 0.0 } else { avg_entry.unwrap_or(self.avg_entry) };
+    }
+
+    /// Target bid and ask (price, size) of `asset` for the given reference price, sized with the
+    /// decimals known to `pre_trade`. A side growing the inventory is shrunk so that a full fill
+    /// leaves it within `max_inventory`, and is `None` when there is no room left.
+    pub fn target_quotes(
+        &self,
+        pre_trade: &PreTradeChecker,
+        asset: &str,
+        reference: f64,
+    ) -> (TargetQuote, TargetQuote) {
+        let inventory_notional = self.inventory * reference;
+        let inventory_ratio = if self.params.max_inventory > 0.0 {
+            (inventory_notional / self.params.max_inventory).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        };
+
+        // Long inventory lowers both quotes so we sell more readily and buy less readily.
+        let reservation = reference * (1.0 - self.params.skew_bps / 10_000.0 * inventory_ratio);
+        let half_spread = self.params.spread_bps / 2.0 / 10_000.0;
+        let bid_px = pre_trade.round_price(asset, reservation * (1.0 - half_spread));
+        let ask_px = pre_trade.round_price(asset, reservation * (1.0 + half_spread));
+        let size =
+            pre_trade.round_size(asset, self.params.amount * self.params.leverage / reference);
+        // Room (USD) left before the cap on each side
+        let capped = |room: f64| size.min(pre_trade.floor_size(asset, room.max(0.0) / reference));
+        let bid_size = capped(self.params.max_inventory - inventory_notional);
+        let ask_size = capped(self.params.max_inventory + inventory_notional);
+
+        let bid = (bid_size > 0.0).then_some((bid_px, bid_size));
+        let ask = (ask_size > 0.0).then_some((ask_px, ask_size));
+        (bid, ask)
+    }
+
+    /// Whether the reference has moved far enough from the last quoted one to requote.
+    pub fn needs_requote(&self, reference: f64) -> bool {
+        match self.quoted_reference {
+            Some(quoted) if quoted > 0.0 => {
+                ((reference - quoted) / quoted * 10_000.0).abs() >= self.params.requote_bps
+            }
+            _ => true,
+        }
+    }
+
+    /// Requote if the reference moved beyond the threshold or a quote was filled.
+    pub async fn on_price(
+        &mut self,
+        account: &TradingAccount,
+        asset: &str,
+        reference: f64,
+    ) -> eyre::Result<()> {
+        if reference <= 0.0 || !self.needs_requote(reference) {
+            return Ok(());
+        }
+
+        self.cancel_all(account, asset).await?;
+
+        let (bid, ask) = self.target_quotes(&account.pre_trade, asset, reference);
+        if let Some((px, sz)) = bid {
+            if let Some(oid) = account
+                .place_limit_order(asset, true, px, sz, "Alo", false, Some(reference))
+                .await?
+            {
+                self.bid = Some(Quote { oid, price: px, remaining: sz });
+            }
+        }
+        if let Some((px, sz)) = ask {
+            if let Some(oid) = account
+                .place_limit_order(asset, false, px, sz, "Alo", false, Some(reference))
+                .await?
+            {
+                self.ask = Some(Quote { oid, price: px, remaining: sz });
+            }
+        }
+        self.quoted_reference = Some(reference);
+
+        debug!(
+            "Quoted {} around {:.4}: bid {:?}, ask {:?}, inventory {:.4}",
+            asset, reference, self.bid, self.ask, self.inventory
+        );
+        Ok(())
+    }
+
+    /// Cancel both resting quotes.
+    pub async fn cancel_all(&mut self, account: &TradingAccount, asset: &str) -> eyre::Result<()> {
+        for quote in [self.bid.take(), self.ask.take()].into_iter().flatten() {
+            if !account.cancel_order(asset, quote.oid).await? {
+                warn!("Could not cancel quote {} at {:.4}", quote.oid, quote.price);
+            }
+        }
+        self.quoted_reference = None;
+        Ok(())
+    }
+
+    /// Apply a fill of one of our quotes: update inventory, book realized PnL into
+    /// `account.closed_trades` / `total_pnl`, and force a requote on the next price.
+    pub fn on_fill(
+        &mut self,
+        account: &mut TradingAccount,
+        fill: &TradeInfo,
+        total_pnl: &mut f64,
+    ) -> eyre::Result<()> {
+        let px: f64 = fill.px.parse()?;
+        let sz: f64 = fill.sz.parse()?;
+        let signed = if fill.side == "B" { sz } else { -sz };
+
+        for quote in [&mut self.bid, &mut self.ask] {
+            if let Some(q) = quote {
+                if q.oid == fill.oid {
+                    q.remaining -= sz;
+                    if q.remaining <= f64::EPSILON {
+                        *quote = None;
+                    }
+                }
+            }
+        }
+
+        // Portion of the fill that reduces existing inventory realizes PnL against the
+        // average entry; the rest opens new inventory.
+        let reducing =
+            if self.inventory * signed < 0.0 { sz.min(self.inventory.abs()) } else { 0.0 };
+        if reducing > 0.0 {
+            let direction = if self.inventory > 0.0 { Direction::Long } else { Direction::Short };
+            let pnl = match direction {
+                Direction::Long => (px - self.avg_entry) * reducing,
+                Direction::Short => (self.avg_entry - px) * reducing,
+            };
+            *total_pnl += pnl;
+            account.closed_trades.push(Trade {
+                direction,
+                entry_price: self.avg_entry,
+                entry_time: Utc::now().timestamp(),
+                size: reducing,
+                tp_price: 0.0,
+                sl_price: 0.0,
+                close_price: Some(px),
+            });
+            info!(
+                "Market maker closed {:.4} {:?} - Entry: {:.4}, Exit: {:.4}, PnL: {:.4}",
+                reducing, direction, self.avg_entry, px, pnl
+            );
+        }
+
+        let opening = sz - reducing;
+        let new_inventory = self.inventory + signed;
+        if opening > 0.0 {
+            // Either adding to the position or flipping through zero.
+            let kept = if reducing > 0.0 { 0.0 } else { self.inventory.abs() };
+            self.avg_entry = (self.avg_entry * kept + px * opening) / (kept + opening);
+        } else if new_inventory.abs() <= f64::EPSILON {
+            self.avg_entry = 0.0;
+        }
+        self.inventory = new_inventory;
+
+        // Quotes are stale once one side has traded.
+        self.quoted_reference = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pretrade::PreTradeConfig;
+
+    fn maker(inventory: f64) -> MarketMaker {
+        let mut maker = MarketMaker::new(MarketMakingParams {
+            amount: 20.0,
+            leverage: 5.0,
+            spread_bps: 10.0,
+            skew_bps: 0.0,
+            requote_bps: 5.0,
+            max_inventory: 250.0,
+        });
+        maker.correct(inventory, Some(10.0), &HashSet::new());
+        maker
+    }
+
+    #[test]
+    fn quotes_full_size_within_the_cap() {
+        let pre_trade = PreTradeChecker::new(PreTradeConfig::default());
+        let (bid, ask) = maker(0.0).target_quotes(&pre_trade, "HYPE", 10.0);
+        assert_eq!(bid.map(|(_, size)| size), Some(10.0));
+        assert_eq!(ask.map(|(_, size)| size), Some(10.0));
+    }
+
+    #[test]
+    fn shrinks_the_side_growing_the_inventory_to_the_cap() {
+        let pre_trade = PreTradeChecker::new(PreTradeConfig::default());
+        // $200 long: $50 of room to buy, $450 to sell
+        let (bid, ask) = maker(20.0).target_quotes(&pre_trade, "HYPE", 10.0);
+        assert_eq!(bid.map(|(_, size)| size), Some(5.0));
+        assert_eq!(ask.map(|(_, size)| size), Some(10.0));
+
+        let (bid, ask) = maker(-24.999).target_quotes(&pre_trade, "HYPE", 10.0);
+        assert_eq!(bid.map(|(_, size)| size), Some(10.0));
+        assert_eq!(ask, None);
+    }
+
+    #[test]
+    fn pulls_the_side_at_the_cap() {
+        let pre_trade = PreTradeChecker::new(PreTradeConfig::default());
+        let (bid, ask) = maker(30.0).target_quotes(&pre_trade, "HYPE", 10.0);
+        assert_eq!(bid, None);
+        assert!(ask.is_some());
+    }
+}
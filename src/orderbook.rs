@@ -0,0 +1,169 @@
+//! Local L2 order book maintained from `L2Book` messages.
+//!
+//! Hyperliquid publishes the top levels of each side as a full snapshot per message, so the
+//! book is replaced on each update; stale (older) snapshots are ignored.
+#![allow(missing_docs)]
+
+use hyperliquid_rust_sdk::{BookLevel, L2BookData};
+use serde::Deserialize;
+
+use crate::signal::{book_imbalance, ImbalanceConfig};
+
+/// Price used when entering a trade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryPrice {
+    /// (best bid + best ask) / 2
+    #[default]
+    Mid,
+    /// Size-weighted mid, leaning towards the thinner side
+    Microprice,
+    /// Join our own side: best bid for buys, best ask for sells
+    BestPassive,
+    /// Cross the spread: best ask for buys, best bid for sells
+    BestAggressive,
+}
+
+/// Resting size on each side within a distance from mid.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Depth {
+    pub bid_size: f64,
+    pub ask_size: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OrderBook {
+    coin: String,
+    /// Exchange timestamp of the current snapshot, in milliseconds
+    time: u64,
+    /// (price, size), best first
+    bids: Vec<(f64, f64)>,
+    /// (price, size), best first
+    asks: Vec<(f64, f64)>,
+}
+
+fn parse_levels(levels: Option<&Vec<BookLevel>>) -> Vec<(f64, f64)> {
+    levels
+        .map(|levels| {
+            levels
+                .iter()
+                .filter_map(|level| {
+                    let px = level.px.parse().ok()?;
+                    let sz = level.sz.parse().ok()?;
+                    Some((px, sz))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl OrderBook {
+    pub fn new(coin: &str) -> Self {
+        Self { coin: coin.to_string(), ..Default::default() }
+    }
+
+    /// Replace the book with a new snapshot. Returns `false` if the snapshot is for another
+    /// coin or older than the one we have.
+    pub fn apply(&mut self, book: &L2BookData) -> bool {
+        if book.coin != self.coin || book.time < self.time {
+            return false;
+        }
+        self.time = book.time;
+        self.bids = parse_levels(book.levels.first());
+        self.asks = parse_levels(book.levels.get(1));
+        true
+    }
+
+    pub fn coin(&self) -> &str {
+        &self.coin
+    }
+
+    /// Exchange timestamp of the current snapshot, in milliseconds (0 before the first one).
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    pub fn bids(&self) -> &[(f64, f64)] {
+        &self.bids
+    }
+
+    pub fn asks(&self) -> &[(f64, f64)] {
+        &self.asks
+    }
+
+    pub fn best_bid(&self) -> Option<(f64, f64)> {
+        self.bids.first().copied()
+    }
+
+    pub fn best_ask(&self) -> Option<(f64, f64)> {
+        self.asks.first().copied()
+    }
+
+    pub fn mid(&self) -> Option<f64> {
+        let (bid, _) = self.best_bid()?;
+        let (ask, _) = self.best_ask()?;
+        Some((bid + ask) / 2.0)
+    }
+
+    pub fn spread(&self) -> Option<f64> {
+        let (bid, _) = self.best_bid()?;
+        let (ask, _) = self.best_ask()?;
+        Some(ask - bid)
+    }
+
+    pub fn spread_bps(&self) -> Option<f64> {
+        Some(self.spread()? / self.mid()? * 10_000.0)
+    }
+
+    /// Mid weighted by the opposite side's top-of-book size, so it moves towards the side that
+    /// is about to be consumed.
+    pub fn microprice(&self) -> Option<f64> {
+        let (bid, bid_sz) = self.best_bid()?;
+        let (ask, ask_sz) = self.best_ask()?;
+        let total = bid_sz + ask_sz;
+        if total <= 0.0 {
+            return self.mid();
+        }
+        Some((bid * ask_sz + ask * bid_sz) / total)
+    }
+
+    /// Resting size on each side within `bps` of mid.
+    pub fn depth_within_bps(&self, bps: f64) -> Option<Depth> {
+        let mid = self.mid()?;
+        let within = |levels: &[(f64, f64)]| -> f64 {
+            levels
+                .iter()
+                .take_while(|(px, _)| (px - mid).abs() / mid * 10_000.0 <= bps)
+                .map(|(_, sz)| sz)
+                .sum()
+        };
+        Some(Depth { bid_size: within(&self.bids), ask_size: within(&self.asks) })
+    }
+
+    /// Book pressure in `[-1, 1]` under the given depth limits and weighting.
+    pub fn pressure(&self, config: &ImbalanceConfig) -> f64 {
+        book_imbalance(&self.bids, &self.asks, config)
+    }
+
+    /// Price to enter a buy (`is_buy`) or sell at, or `None` if the book is empty.
+    pub fn entry_price(&self, mode: EntryPrice, is_buy: bool) -> Option<f64> {
+        match mode {
+            EntryPrice::Mid => self.mid(),
+            EntryPrice::Microprice => self.microprice(),
+            EntryPrice::BestPassive => {
+                if is_buy {
+                    self.best_bid().map(|(px, _)| px)
+                } else {
+                    self.best_ask().map(|(px, _)| px)
+                }
+            }
+            EntryPrice::BestAggressive => {
+                if is_buy {
+                    self.best_ask().map(|(px, _)| px)
+                } else {
+                    self.best_bid().map(|(px, _)| px)
+                }
+            }
+        }
+    }
+}
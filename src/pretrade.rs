@@ -0,0 +1,418 @@
+//! Pre-trade validation.
+//!
+//! Every order goes through [`PreTradeChecker::check`] before it is submitted, so that sizes that
+//! round to zero, orders below the exchange minimum, prices with too many digits or far from the
+//! mid, and reduce-only orders that don't match the known position are caught locally with a
+//! [`PreTradeRejection`] instead of being rejected (or, worse, filled) by the exchange. With a
+//! `budget_path`, accepted orders are also held against the limits shared with the other
+//! processes trading the account, see [`crate::budget`].
+#![allow(missing_docs)]
+
+use std::{collections::HashMap, sync::RwLock};
+
+use ethers::types::H160;
+use hyperliquid_rust_sdk::{InfoClient, OpenOrdersResponse, UserStateResponse};
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use crate::budget::{BudgetConfig, BudgetRejection, BudgetReservation, SharedBudget};
+
+/// Size decimals assumed for coins missing from the exchange meta (matches [`crate::get_size`])
+pub const DEFAULT_SZ_DECIMALS: u32 = 2;
+/// Decimals of perp prices: a price has at most `MAX_PRICE_DECIMALS - sz_decimals` of them
+pub const MAX_PRICE_DECIMALS: u32 = 6;
+/// Significant figures of a non-integer price
+pub const MAX_SIGNIFICANT_FIGURES: i32 = 5;
+
+#[derive(Debug, Clone, Deserialize, clap::Args)]
+#[serde(default)]
+pub struct PreTradeConfig {
+    /// Smallest order notional (USD) the exchange accepts; reduce-only orders are exempt
+    #[arg(long, default_value_t = 10.0)]
+    pub min_order_notional: f64,
+
+    /// Largest order size in coins (0 = no limit)
+    #[arg(long, default_value_t = 0.0)]
+    pub max_order_size: f64,
+
+    /// Largest open notional (USD) of one account, including the new order (0 = no limit)
+    #[arg(long, default_value_t = 0.0)]
+    pub max_account_notional: f64,
+
+    /// Farthest (bps) an order price may be from the mid (0 = no check)
+    #[arg(long, default_value_t = 1000.0)]
+    pub max_price_deviation_bps: f64,
+
+    /// Limits shared with the other processes trading the same accounts
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub budget: BudgetConfig,
+}
+
+impl Default for PreTradeConfig {
+    fn default() -> Self {
+        Self {
+            min_order_notional: 10.0,
+            max_order_size: 0.0,
+            max_account_notional: 0.0,
+            max_price_deviation_bps: 1000.0,
+            budget: BudgetConfig::default(),
+        }
+    }
+}
+
+/// An order about to be submitted.
+#[derive(Debug, Clone, Copy)]
+pub struct OrderCheck<'a> {
+    pub asset: &'a str,
+    pub is_buy: bool,
+    pub price: f64,
+    /// Size in coins, sign ignored
+    pub size: f64,
+    pub reduce_only: bool,
+    /// Current mid; `None` skips the price band check
+    pub mid: Option<f64>,
+    /// Position (signed coins) the caller holds on `asset` before the order
+    pub position: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PreTradeRejection {
+    /// Size is zero (or not a number) after rounding
+    ZeroSize,
+    InvalidPrice {
+        price: f64,
+    },
+    BelowMinNotional {
+        notional: f64,
+        min: f64,
+    },
+    SizePrecision {
+        size: f64,
+        sz_decimals: u32,
+    },
+    PricePrecision {
+        price: f64,
+        max_decimals: u32,
+    },
+    MaxOrderSize {
+        size: f64,
+        max: f64,
+    },
+    MaxAccountNotional {
+        notional: f64,
+        max: f64,
+    },
+    PriceBand {
+        price: f64,
+        mid: f64,
+        deviation_bps: f64,
+        max_bps: f64,
+    },
+    /// Reduce-only order while flat
+    ReduceOnlyWithoutPosition,
+    /// Reduce-only order on the same side as the position
+    ReduceOnlyWrongSide {
+        position: f64,
+    },
+    /// Reduce-only order larger than the position
+    ReduceOnlyExceedsPosition {
+        size: f64,
+        position: f64,
+    },
+    /// Refused by the limits shared with other processes
+    Budget(BudgetRejection),
+}
+
+impl std::fmt::Display for PreTradeRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PreTradeRejection::ZeroSize => write!(f, "size rounds to zero"),
+            PreTradeRejection::InvalidPrice { price } => write!(f, "invalid price {}", price),
+            PreTradeRejection::BelowMinNotional { notional, min } => {
+                write!(f, "notional ${:.2} below the ${:.2} minimum", notional, min)
+            }
+            PreTradeRejection::SizePrecision { size, sz_decimals } => {
+                write!(f, "size {} has more than {} decimals", size, sz_decimals)
+            }
+            PreTradeRejection::PricePrecision { price, max_decimals } => write!(
+                f,
+                "price {} has more than {} significant figures or {} decimals",
+                price, MAX_SIGNIFICANT_FIGURES, max_decimals
+            ),
+            PreTradeRejection::MaxOrderSize { size, max } => {
+                write!(f, "size {} exceeds the {} maximum", size, max)
+            }
+            PreTradeRejection::MaxAccountNotional { notional, max } => {
+                write!(f, "account notional ${:.2} would exceed ${:.2}", notional, max)
+            }
+            PreTradeRejection::PriceBand { price, mid, deviation_bps, max_bps } => write!(
+                f,
+                "price {} is {:.0}bps from mid {} (max {:.0}bps)",
+                price, deviation_bps, mid, max_bps
+            ),
+            PreTradeRejection::ReduceOnlyWithoutPosition => write!(f, "reduce-only while flat"),
+            PreTradeRejection::ReduceOnlyWrongSide { position } => {
+                write!(f, "reduce-only order would add to position {}", position)
+            }
+            PreTradeRejection::ReduceOnlyExceedsPosition { size, position } => {
+                write!(f, "reduce-only size {} exceeds position {}", size, position)
+            }
+            PreTradeRejection::Budget(rejection) => rejection.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for PreTradeRejection {}
+
+/// Whether `value` has at most `decimals` decimals, allowing for float noise.
+fn has_decimals(value: f64, decimals: u32) -> bool {
+    let scaled = value * 10f64.powi(decimals as i32);
+    (scaled - scaled.round()).abs() <= 1e-6 * scaled.abs().max(1.0)
+}
+
+#[derive(Debug)]
+pub struct PreTradeChecker {
+    config: PreTradeConfig,
+    sz_decimals: HashMap<String, u32>,
+    /// Open notional (USD) per account, as last synced plus the orders checked since
+    account_notional: RwLock<HashMap<H160, f64>>,
+    budget: Option<SharedBudget>,
+}
+
+impl PreTradeChecker {
+    /// A checker without exchange metadata: every coin gets [`DEFAULT_SZ_DECIMALS`].
+    pub fn new(config: PreTradeConfig) -> Self {
+        let budget = SharedBudget::new(config.budget.clone());
+        Self {
+            config,
+            sz_decimals: HashMap::new(),
+            account_notional: RwLock::new(HashMap::new()),
+            budget,
+        }
+    }
+
+    /// A checker with the size decimals of every coin listed by the exchange.
+    pub async fn load(config: PreTradeConfig, info_client: &InfoClient) -> eyre::Result<Self> {
+        let meta = info_client.meta().await?;
+        let mut checker = Self::new(config);
+        checker.sz_decimals =
+            meta.universe.into_iter().map(|asset| (asset.name, asset.sz_decimals)).collect();
+        debug!("Loaded size decimals of {} coins", checker.sz_decimals.len());
+        Ok(checker)
+    }
+
+    pub fn config(&self) -> &PreTradeConfig {
+        &self.config
+    }
+
+    /// The limits shared with other processes, if a `budget_path` is set.
+    pub fn budget(&self) -> Option<&SharedBudget> {
+        self.budget.as_ref()
+    }
+
+    pub fn sz_decimals(&self, asset: &str) -> u32 {
+        self.sz_decimals.get(asset).copied().unwrap_or(DEFAULT_SZ_DECIMALS)
+    }
+
+    /// Largest number of decimals a price of `asset` may have.
+    pub fn price_decimals(&self, asset: &str) -> u32 {
+        MAX_PRICE_DECIMALS.saturating_sub(self.sz_decimals(asset))
+    }
+
+    /// `size` rounded to the size decimals of `asset`.
+    pub fn round_size(&self, asset: &str, size: f64) -> f64 {
+        let factor = 10f64.powi(self.sz_decimals(asset) as i32);
+        (size * factor).round() / factor
+    }
+
+    /// `size` rounded down to the size decimals of `asset`.
+    pub fn floor_size(&self, asset: &str, size: f64) -> f64 {
+        let factor = 10f64.powi(self.sz_decimals(asset) as i32);
+        // Float noise mustn't take a whole size step off
+        (size * factor + 1e-9).floor() / factor
+    }
+
+    /// `price` rounded to five significant figures and the price decimals of `asset`.
+    pub fn round_price(&self, asset: &str, price: f64) -> f64 {
+        if price <= 0.0 || !price.is_finite() {
+            return price;
+        }
+        let magnitude = price.abs().log10().floor() as i32 + 1;
+        let decimals =
+            (MAX_SIGNIFICANT_FIGURES - magnitude).clamp(0, self.price_decimals(asset) as i32);
+        let factor = 10f64.powi(decimals);
+        (price * factor).round() / factor
+    }
+
+    /// Replace the known open notional of `user` with the exchange's view, and publish its
+    /// positions and resting orders to the shared budget.
+    pub fn sync_account(
+        &self,
+        user: H160,
+        state: &UserStateResponse,
+        open_orders: &[OpenOrdersResponse],
+    ) -> eyre::Result<()> {
+        let notional: f64 = state.margin_summary.total_ntl_pos.parse()?;
+        self.set_account_notional(user, notional);
+        if let Some(budget) = &self.budget {
+            budget.publish(user, state, open_orders)?;
+        }
+        Ok(())
+    }
+
+    /// Release the budget reserved for an order the exchange rejected.
+    pub fn release(&self, reservation: Option<BudgetReservation>) {
+        if let (Some(budget), Some(reservation)) = (&self.budget, reservation) {
+            if let Err(e) = budget.release(reservation) {
+                warn!("Failed to release a shared budget reservation: {}", e);
+            }
+        }
+    }
+
+    /// Tie the budget reserved for an order to its resting `oid`.
+    pub fn on_resting(&self, reservation: Option<BudgetReservation>, oid: u64) {
+        if let (Some(budget), Some(reservation)) = (&self.budget, reservation) {
+            if let Err(e) = budget.on_resting(reservation, oid) {
+                warn!("Failed to record resting order {} in the shared budget: {}", oid, e);
+            }
+        }
+    }
+
+    /// Release the budget reserved for a cancelled order of `user`.
+    pub fn on_cancelled(&self, user: H160, oid: u64) {
+        if let Some(budget) = &self.budget {
+            if let Err(e) = budget.on_cancelled(user, oid) {
+                warn!("Failed to release cancelled order {} in the shared budget: {}", oid, e);
+            }
+        }
+    }
+
+    pub fn set_account_notional(&self, user: H160, notional: f64) {
+        match self.account_notional.write() {
+            Ok(mut accounts) => {
+                accounts.insert(user, notional);
+            }
+            Err(e) => warn!("Account notional lock poisoned: {}", e),
+        }
+    }
+
+    fn known_notional(&self, user: H160) -> f64 {
+        self.account_notional
+            .read()
+            .map(|accounts| accounts.get(&user).copied().unwrap_or_default())
+            .unwrap_or_default()
+    }
+
+    fn validate(&self, user: H160, order: &OrderCheck<'_>) -> Result<f64, PreTradeRejection> {
+        let size = order.size.abs();
+        if !size.is_finite() || self.round_size(order.asset, size) <= 0.0 {
+            return Err(PreTradeRejection::ZeroSize);
+        }
+        if !order.price.is_finite() || order.price <= 0.0 {
+            return Err(PreTradeRejection::InvalidPrice { price: order.price });
+        }
+
+        let sz_decimals = self.sz_decimals(order.asset);
+        if !has_decimals(size, sz_decimals) {
+            return Err(PreTradeRejection::SizePrecision { size, sz_decimals });
+        }
+        let max_decimals = self.price_decimals(order.asset);
+        let rounded = self.round_price(order.asset, order.price);
+        let integer = has_decimals(order.price, 0);
+        if !integer && (order.price - rounded).abs() > 1e-9 * order.price {
+            return Err(PreTradeRejection::PricePrecision { price: order.price, max_decimals });
+        }
+
+        if let Some(mid) = order.mid.filter(|mid| *mid > 0.0) {
+            let deviation_bps = (order.price - mid).abs() / mid * 10_000.0;
+            let max_bps = self.config.max_price_deviation_bps;
+            if max_bps > 0.0 && deviation_bps > max_bps {
+                return Err(PreTradeRejection::PriceBand {
+                    price: order.price,
+                    mid,
+                    deviation_bps,
+                    max_bps,
+                });
+            }
+        }
+
+        let notional = size * order.price;
+        if order.reduce_only {
+            let position = order.position;
+            if position == 0.0 {
+                return Err(PreTradeRejection::ReduceOnlyWithoutPosition);
+            }
+            if (position > 0.0) == order.is_buy {
+                return Err(PreTradeRejection::ReduceOnlyWrongSide { position });
+            }
+            if size > position.abs() + 10f64.powi(-(sz_decimals as i32)) / 2.0 {
+                return Err(PreTradeRejection::ReduceOnlyExceedsPosition { size, position });
+            }
+            return Ok(-notional);
+        }
+
+        if notional < self.config.min_order_notional {
+            return Err(PreTradeRejection::BelowMinNotional {
+                notional,
+                min: self.config.min_order_notional,
+            });
+        }
+        if self.config.max_order_size > 0.0 && size > self.config.max_order_size {
+            return Err(PreTradeRejection::MaxOrderSize { size, max: self.config.max_order_size });
+        }
+        // Orders against the position shrink it; only orders adding to it count
+        let signed = if order.is_buy { size } else { -size };
+        let added = ((order.position + signed).abs() - order.position.abs()).max(0.0) * order.price;
+        let after = self.known_notional(user) + added;
+        if self.config.max_account_notional > 0.0 && after > self.config.max_account_notional {
+            return Err(PreTradeRejection::MaxAccountNotional {
+                notional: after,
+                max: self.config.max_account_notional,
+            });
+        }
+        Ok(added)
+    }
+
+    /// Validate an order of `user`. Accepted orders are counted towards the account's open
+    /// notional until the next [`PreTradeChecker::sync_account`], and reserved in the shared
+    /// budget unless reduce-only. The reservation, if any, is returned for the caller to hand
+    /// to [`PreTradeChecker::release`] or [`PreTradeChecker::on_resting`].
+    pub fn check(
+        &self,
+        user: H160,
+        order: &OrderCheck<'_>,
+    ) -> Result<Option<BudgetReservation>, PreTradeRejection> {
+        let checked = self.validate(user, order).and_then(|change| {
+            let reservation = match self.budget.as_ref().filter(|_| !order.reduce_only) {
+                Some(budget) => {
+                    let size = if order.is_buy { order.size.abs() } else { -order.size.abs() };
+                    let reservation = budget
+                        .reserve(user, order.asset, size * order.price)
+                        .map_err(PreTradeRejection::Budget)?;
+                    Some(reservation)
+                }
+                None => None,
+            };
+            Ok((change, reservation))
+        });
+        match checked {
+            Ok((change, reservation)) => {
+                let notional = (self.known_notional(user) + change).max(0.0);
+                self.set_account_notional(user, notional);
+                Ok(reservation)
+            }
+            Err(rejection) => {
+                warn!(
+                    "Pre-trade check rejected {} {} {} at {}: {}",
+                    if order.is_buy { "buy" } else { "sell" },
+                    order.size.abs(),
+                    order.asset,
+                    order.price,
+                    rejection
+                );
+                Err(rejection)
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Sanity check of incoming prices.
+//!
+//! A [`PriceFilter`] sits between parsing a mid and handing it to a strategy. A price more than
+//! `max_tick_jump_bps` away from the median of the last accepted prices is rejected unless the
+//! local order book agrees with it (it lies within the best bid/ask, widened by
+//! `tick_book_tolerance_bps`) or `tick_confirmations` consecutive ticks agree with each other, in
+//! which case the market really moved and the median restarts from the new level.
+#![allow(missing_docs)]
+
+use std::collections::VecDeque;
+
+use serde::Deserialize;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Deserialize, clap::Args)]
+#[serde(default)]
+pub struct PriceFilterConfig {
+    /// Largest move (bps) from the rolling median accepted without confirmation (0 = no filter)
+    #[arg(long, default_value_t = 200.0)]
+    pub max_tick_jump_bps: f64,
+
+    /// Accepted prices the median is taken over
+    #[arg(long, default_value_t = 20)]
+    pub tick_median_window: usize,
+
+    /// Consecutive agreeing ticks that confirm a jump (0 = only the book confirms)
+    #[arg(long, default_value_t = 3)]
+    pub tick_confirmations: usize,
+
+    /// Slack (bps) around the best bid/ask within which the book confirms a jump
+    /// (negative = no book check)
+    #[arg(long, default_value_t = 5.0, allow_negative_numbers = true)]
+    pub tick_book_tolerance_bps: f64,
+}
+
+impl Default for PriceFilterConfig {
+    fn default() -> Self {
+        Self {
+            max_tick_jump_bps: 200.0,
+            tick_median_window: 20,
+            tick_confirmations: 3,
+            tick_book_tolerance_bps: 5.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TickVerdict {
+    Accepted,
+    /// Accepted after a jump, confirmed by the book or by consecutive ticks
+    Confirmed {
+        jump_bps: f64,
+    },
+    Rejected {
+        jump_bps: f64,
+    },
+}
+
+impl TickVerdict {
+    pub fn is_accepted(&self) -> bool {
+        !matches!(self, TickVerdict::Rejected { .. })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PriceFilter {
+    label: String,
+    config: PriceFilterConfig,
+    /// Last accepted prices, oldest first
+    accepted: VecDeque<f64>,
+    /// Consecutive rejected prices that agree with each other
+    pending: Vec<f64>,
+    rejected: u64,
+}
+
+fn jump_bps(price: f64, reference: f64) -> f64 {
+    (price - reference).abs() / reference * 10_000.0
+}
+
+impl PriceFilter {
+    pub fn new(label: &str, config: PriceFilterConfig) -> Self {
+        Self {
+            label: label.to_string(),
+            config,
+            accepted: VecDeque::new(),
+            pending: Vec::new(),
+            rejected: 0,
+        }
+    }
+
+    /// Median of the last accepted prices.
+    pub fn median(&self) -> Option<f64> {
+        if self.accepted.is_empty() {
+            return None;
+        }
+        let mut prices: Vec<f64> = self.accepted.iter().copied().collect();
+        prices.sort_by(|a, b| a.total_cmp(b));
+        let mid = prices.len() / 2;
+        Some(if prices.len().is_multiple_of(2) {
+            (prices[mid - 1] + prices[mid]) / 2.0
+        } else {
+            prices[mid]
+        })
+    }
+
+    /// Ticks rejected so far.
+    pub fn rejected(&self) -> u64 {
+        self.rejected
+    }
+
+    fn accept(&mut self, price: f64) {
+        self.accepted.push_back(price);
+        while self.accepted.len() > self.config.tick_median_window.max(1) {
+            self.accepted.pop_front();
+        }
+    }
+
+    /// Check `price` against the recent median and, when given, the best `(bid, ask)` of the
+    /// book. Only accepted prices should reach the strategy and the tick cache.
+    pub fn check(&mut self, price: f64, book: Option<(f64, f64)>) -> TickVerdict {
+        if !price.is_finite() || price <= 0.0 {
+            self.rejected += 1;
+            warn!("[{}] Rejected invalid price {}", self.label, price);
+            return TickVerdict::Rejected { jump_bps: f64::INFINITY };
+        }
+        let Some(median) = self.median().filter(|_| self.config.max_tick_jump_bps > 0.0) else {
+            self.accept(price);
+            return TickVerdict::Accepted;
+        };
+        let jump = jump_bps(price, median);
+        if jump <= self.config.max_tick_jump_bps {
+            self.pending.clear();
+            self.accept(price);
+            return TickVerdict::Accepted;
+        }
+
+        let tolerance = self.config.tick_book_tolerance_bps;
+        let book_confirms = tolerance >= 0.0 &&
+            book.is_some_and(|(bid, ask)| {
+                price >= bid * (1.0 - tolerance / 10_000.0) &&
+                    price <= ask * (1.0 + tolerance / 10_000.0)
+            });
+
+        // Ticks confirm each other when they stay within the jump limit of the first of them
+        if self
+            .pending
+            .first()
+            .is_some_and(|first| jump_bps(price, *first) > self.config.max_tick_jump_bps)
+        {
+            self.pending.clear();
+        }
+        self.pending.push(price);
+        let ticks_confirm = self.config.tick_confirmations > 0 &&
+            self.pending.len() >= self.config.tick_confirmations;
+
+        if book_confirms || ticks_confirm {
+            info!(
+                "[{}] Price jump of {:.0}bps to {} confirmed by the {}",
+                self.label,
+                jump,
+                price,
+                if book_confirms { "book" } else { "next ticks" }
+            );
+            // The market moved: measure from the new level
+            self.accepted.clear();
+            for price in std::mem::take(&mut self.pending) {
+                self.accept(price);
+            }
+            return TickVerdict::Confirmed { jump_bps: jump };
+        }
+
+        self.rejected += 1;
+        warn!(
+            "[{}] Rejected price {} ({:.0}bps from median {}), {} rejected so far",
+            self.label, price, jump, median, self.rejected
+        );
+        TickVerdict::Rejected { jump_bps: jump }
+    }
+}
@@ -0,0 +1,221 @@
+//! Reconciliation of the bots' own bookkeeping with the exchange.
+//!
+//! The bots track their positions from fills and their own orders, and nothing tells them when
+//! that view goes wrong: a missed websocket message, a manual trade, a liquidation. Every
+//! `reconcile_secs` they hand their view and the account's clearinghouse state and open orders to
+//! a [`Reconciler`]. A disagreement that persists for `drift_confirmations` checks in a row
+//! (fills and order acks may just be in flight) becomes a [`DriftEvent`], which is logged and,
+//! depending on `drift_policy`, also corrects the bot's state or halts new entries.
+#![allow(missing_docs)]
+
+use std::collections::{HashMap, HashSet};
+
+use clap::ValueEnum;
+use hyperliquid_rust_sdk::{OpenOrdersResponse, UserStateResponse};
+use serde::Deserialize;
+use tracing::{debug, error, info, warn};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftPolicy {
+    /// Only log the drift
+    #[default]
+    Log,
+    /// Take the exchange's position and orders as the truth
+    Correct,
+    /// Stop opening new trades until restarted
+    Halt,
+}
+
+#[derive(Debug, Clone, Deserialize, clap::Args)]
+#[serde(default)]
+pub struct ReconcileConfig {
+    /// Seconds between reconciliations with the exchange (0 = never)
+    #[arg(long, default_value_t = 60)]
+    pub reconcile_secs: u64,
+
+    /// Position difference (coins) tolerated between the bot and the exchange
+    #[arg(long, default_value_t = 0.0)]
+    pub drift_tolerance: f64,
+
+    /// Consecutive disagreeing checks before a drift is reported
+    #[arg(long, default_value_t = 2)]
+    pub drift_confirmations: u32,
+
+    /// What to do once a drift is reported
+    #[arg(long, value_enum, default_value_t = DriftPolicy::Log)]
+    pub drift_policy: DriftPolicy,
+}
+
+impl Default for ReconcileConfig {
+    fn default() -> Self {
+        Self {
+            reconcile_secs: 60,
+            drift_tolerance: 0.0,
+            drift_confirmations: 2,
+            drift_policy: DriftPolicy::Log,
+        }
+    }
+}
+
+/// What a bot believes it holds on one coin of one account.
+#[derive(Debug, Clone, Copy)]
+pub struct InternalState<'a> {
+    pub coin: &'a str,
+    /// Signed size in coins
+    pub position: f64,
+    /// Resting orders the bot tracks; `None` when it doesn't track order ids
+    pub orders: Option<&'a HashSet<u64>>,
+}
+
+/// A confirmed disagreement between a bot and the exchange.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriftEvent {
+    pub label: String,
+    pub coin: String,
+    /// Signed position the bot tracks
+    pub internal: f64,
+    /// Signed position on the exchange
+    pub exchange: f64,
+    /// Entry price of the exchange position, if any
+    pub exchange_entry: Option<f64>,
+    /// Orders the bot tracks that aren't resting on the exchange
+    pub missing_orders: Vec<u64>,
+    /// Orders resting on the exchange the bot doesn't track
+    pub unknown_orders: Vec<u64>,
+    pub policy: DriftPolicy,
+}
+
+impl DriftEvent {
+    /// Exchange position minus the tracked one.
+    pub fn position_drift(&self) -> f64 {
+        self.exchange - self.internal
+    }
+}
+
+impl std::fmt::Display for DriftEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}] {} position {} tracked, {} on the exchange",
+            self.label, self.coin, self.internal, self.exchange
+        )?;
+        if !self.missing_orders.is_empty() {
+            write!(f, "; orders gone from the exchange: {:?}", self.missing_orders)?;
+        }
+        if !self.unknown_orders.is_empty() {
+            write!(f, "; untracked orders on the exchange: {:?}", self.unknown_orders)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Reconciler {
+    config: ReconcileConfig,
+    /// Consecutive disagreeing checks per `(label, coin)`
+    disagreements: HashMap<(String, String), u32>,
+    halted: bool,
+}
+
+impl Reconciler {
+    pub fn new(config: ReconcileConfig) -> Self {
+        Self { config, disagreements: HashMap::new(), halted: false }
+    }
+
+    pub fn config(&self) -> &ReconcileConfig {
+        &self.config
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.reconcile_secs > 0
+    }
+
+    /// Whether a drift halted new entries (`DriftPolicy::Halt`).
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Compare what account `label` tracks on `internal.coin` with its clearinghouse state and
+    /// open orders. Returns a drift once it persisted for `drift_confirmations` checks; the
+    /// caller applies [`DriftEvent::policy`].
+    pub fn check(
+        &mut self,
+        label: &str,
+        internal: &InternalState<'_>,
+        state: &UserStateResponse,
+        open_orders: &[OpenOrdersResponse],
+    ) -> eyre::Result<Option<DriftEvent>> {
+        let position =
+            state.asset_positions.iter().find(|asset| asset.position.coin == internal.coin);
+        let (exchange, exchange_entry) = match position {
+            Some(asset) => (
+                asset.position.szi.parse::<f64>()?,
+                asset.position.entry_px.as_deref().map(str::parse).transpose()?,
+            ),
+            None => (0.0, None),
+        };
+
+        let (mut missing_orders, mut unknown_orders) = (Vec::new(), Vec::new());
+        if let Some(orders) = internal.orders {
+            let resting: HashSet<u64> = open_orders
+                .iter()
+                .filter(|order| order.coin == internal.coin)
+                .map(|order| order.oid)
+                .collect();
+            missing_orders = orders.difference(&resting).copied().collect();
+            unknown_orders = resting.difference(orders).copied().collect();
+            missing_orders.sort_unstable();
+            unknown_orders.sort_unstable();
+        }
+
+        let key = (label.to_string(), internal.coin.to_string());
+        let drifting = (exchange - internal.position).abs() > self.config.drift_tolerance + 1e-9 ||
+            !missing_orders.is_empty() ||
+            !unknown_orders.is_empty();
+        if !drifting {
+            if self
+                .disagreements
+                .remove(&key)
+                .is_some_and(|count| count >= self.config.drift_confirmations.max(1))
+            {
+                info!("[{}] {} back in line with the exchange", label, internal.coin);
+            }
+            return Ok(None);
+        }
+
+        let count = self.disagreements.entry(key).or_default();
+        *count += 1;
+        if *count != self.config.drift_confirmations.max(1) {
+            debug!(
+                "[{}] {} disagrees with the exchange ({} check(s))",
+                label, internal.coin, count
+            );
+            return Ok(None);
+        }
+
+        let event = DriftEvent {
+            label: label.to_string(),
+            coin: internal.coin.to_string(),
+            internal: internal.position,
+            exchange,
+            exchange_entry,
+            missing_orders,
+            unknown_orders,
+            policy: self.config.drift_policy,
+        };
+        match event.policy {
+            DriftPolicy::Log => warn!("Drift: {}", event),
+            DriftPolicy::Correct => warn!("Drift: {}, correcting to the exchange", event),
+            DriftPolicy::Halt => {
+                self.halted = true;
+                error!("Drift: {}, halting new entries", event);
+            }
+        }
+        if event.policy == DriftPolicy::Correct {
+            // The corrected state is checked afresh
+            self.disagreements.remove(&(event.label.clone(), event.coin.clone()));
+        }
+        Ok(Some(event))
+    }
+}
@@ -0,0 +1,130 @@
+//! Re-entry throttling for a trading channel.
+//!
+//! After a channel closes a trade, [`ReEntryGuard`] decides when it may open the next one:
+//! a fixed cooldown after a stop-loss, a pause that doubles with every further `loss_streak`
+//! consecutive losses, and a cap on entries per rolling hour.
+#![allow(missing_docs)]
+
+use std::collections::VecDeque;
+
+use serde::Deserialize;
+use tracing::info;
+
+use crate::utils::CloseReason;
+
+const HOUR_SECS: i64 = 3600;
+
+#[derive(Debug, Clone, Deserialize, clap::Args)]
+#[serde(default)]
+pub struct ReEntryConfig {
+    /// Seconds to wait before re-entering after a stop-loss
+    #[arg(long, default_value_t = 0)]
+    pub stop_loss_cooldown_secs: u64,
+
+    /// Consecutive losing trades that trigger a pause (0 = never pause)
+    #[arg(long, default_value_t = 0)]
+    pub loss_streak: u32,
+
+    /// Pause after `loss_streak` consecutive losses; doubles for every further streak
+    #[arg(long, default_value_t = 900)]
+    pub loss_streak_pause_secs: u64,
+
+    /// Longest loss-streak pause
+    #[arg(long, default_value_t = 14_400)]
+    pub max_pause_secs: u64,
+
+    /// Entries allowed per channel in any rolling hour (0 = unlimited)
+    #[arg(long, default_value_t = 0)]
+    pub max_trades_per_hour: u32,
+}
+
+impl Default for ReEntryConfig {
+    fn default() -> Self {
+        Self {
+            stop_loss_cooldown_secs: 0,
+            loss_streak: 0,
+            loss_streak_pause_secs: 900,
+            max_pause_secs: 14_400,
+            max_trades_per_hour: 0,
+        }
+    }
+}
+
+/// Re-entry state of one channel. Timestamps are UTC seconds.
+#[derive(Debug, Clone)]
+pub struct ReEntryGuard {
+    config: ReEntryConfig,
+    consecutive_losses: u32,
+    blocked_until: i64,
+    /// Entry times within the last hour
+    entries: VecDeque<i64>,
+}
+
+impl ReEntryGuard {
+    pub fn new(config: ReEntryConfig) -> Self {
+        Self { config, consecutive_losses: 0, blocked_until: 0, entries: VecDeque::new() }
+    }
+
+    pub fn consecutive_losses(&self) -> u32 {
+        self.consecutive_losses
+    }
+
+    /// Record a closed trade and extend the block accordingly.
+    pub fn on_close(&mut self, pnl: f64, reason: CloseReason, now: i64) {
+        if pnl < 0.0 {
+            self.consecutive_losses += 1;
+        } else {
+            self.consecutive_losses = 0;
+        }
+
+        if reason == CloseReason::StopLoss {
+            self.block_for(self.config.stop_loss_cooldown_secs, now);
+        }
+
+        let streak = self.config.loss_streak;
+        if streak > 0 &&
+            self.consecutive_losses > 0 &&
+            self.consecutive_losses.is_multiple_of(streak)
+        {
+            let doublings = (self.consecutive_losses / streak - 1).min(16);
+            let pause = self
+                .config
+                .loss_streak_pause_secs
+                .saturating_mul(1 << doublings)
+                .min(self.config.max_pause_secs);
+            info!(
+                "{} consecutive losses, pausing re-entry for {}s",
+                self.consecutive_losses, pause
+            );
+            self.block_for(pause, now);
+        }
+    }
+
+    /// Record an entry.
+    pub fn on_entry(&mut self, now: i64) {
+        self.entries.push_back(now);
+    }
+
+    /// `None` if the channel may enter now, otherwise the time at which it may next enter.
+    pub fn blocked_until(&mut self, now: i64) -> Option<i64> {
+        while self.entries.front().is_some_and(|&time| time <= now - HOUR_SECS) {
+            self.entries.pop_front();
+        }
+
+        let mut until = self.blocked_until;
+        let max_trades = self.config.max_trades_per_hour as usize;
+        if max_trades > 0 && self.entries.len() >= max_trades {
+            // The oldest entry in the window has to age out first.
+            until = until.max(self.entries[self.entries.len() - max_trades] + HOUR_SECS);
+        }
+        (until > now).then_some(until)
+    }
+
+    pub fn can_enter(&mut self, now: i64) -> bool {
+        self.blocked_until(now).is_none()
+    }
+
+    fn block_for(&mut self, secs: u64, now: i64) {
+        self.blocked_until = self.blocked_until.max(now + secs as i64);
+    }
+}
@@ -0,0 +1,255 @@
+//! Market regime classification: trending or ranging.
+//!
+//! [`RegimeFilter`] is fed the candle stream and votes with up to three indicators: ADX (trend
+//! strength), the Hurst exponent (persistence of moves) and realized volatility (expansion).
+//! More trend than range votes means [`Regime::Trend`], the reverse [`Regime::Range`]; a tie
+//! keeps the previous regime. Each strategy then scales its size by the multiplier configured
+//! for the current regime, 0 turning it off.
+#![allow(missing_docs)]
+
+use chrono::Utc;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::indicators::{Adx, Candle, Hurst, Indicator, RealizedVol};
+
+const YEAR_SECS: f64 = 365.0 * 86_400.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Regime {
+    /// Not enough candles yet
+    Unknown,
+    Trend,
+    Range,
+}
+
+impl std::fmt::Display for Regime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Regime::Unknown => write!(f, "unknown"),
+            Regime::Trend => write!(f, "trend"),
+            Regime::Range => write!(f, "range"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RegimeConfig {
+    /// Candle interval the regime is computed on, e.g. "15m"
+    pub interval: String,
+    pub adx_period: usize,
+    /// ADX at or above which the market votes trend
+    pub trend_adx: f64,
+    /// ADX at or below which the market votes range
+    pub range_adx: f64,
+    /// Closes in the Hurst estimate (0 = no Hurst vote)
+    pub hurst_period: usize,
+    pub hurst_max_lag: usize,
+    pub trend_hurst: f64,
+    pub range_hurst: f64,
+    /// Candles in the realized volatility estimate
+    pub vol_period: usize,
+    /// Annualized volatility at or above which the market votes trend (0 = no volatility vote)
+    pub trend_vol: f64,
+    /// Size multiplier in each regime; 0 disables the strategy
+    pub trend_scale: f64,
+    pub range_scale: f64,
+    pub unknown_scale: f64,
+}
+
+impl Default for RegimeConfig {
+    fn default() -> Self {
+        Self {
+            interval: "15m".to_string(),
+            adx_period: 14,
+            trend_adx: 25.0,
+            range_adx: 20.0,
+            hurst_period: 100,
+            hurst_max_lag: 20,
+            trend_hurst: 0.55,
+            range_hurst: 0.45,
+            vol_period: 24,
+            trend_vol: 0.0,
+            trend_scale: 1.0,
+            range_scale: 1.0,
+            unknown_scale: 1.0,
+        }
+    }
+}
+
+impl RegimeConfig {
+    /// Length of `interval` in seconds.
+    pub fn interval_secs(&self) -> f64 {
+        let (value, unit) = self.interval.split_at(self.interval.len().saturating_sub(1));
+        let value: f64 = value.parse().unwrap_or(1.0);
+        value *
+            match unit {
+                "m" => 60.0,
+                "h" => 3600.0,
+                "d" => 86_400.0,
+                "w" => 7.0 * 86_400.0,
+                _ => 60.0,
+            }
+    }
+
+    pub fn scale(&self, regime: Regime) -> f64 {
+        match regime {
+            Regime::Unknown => self.unknown_scale,
+            Regime::Trend => self.trend_scale,
+            Regime::Range => self.range_scale,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RegimeFilter {
+    config: RegimeConfig,
+    adx: Adx,
+    hurst: Hurst,
+    vol: RealizedVol,
+    /// Latest update of the candle still forming
+    pending: Option<Candle>,
+    regime: Regime,
+    /// When the current regime started (UTC seconds)
+    since: i64,
+    changes: u32,
+    /// Seconds spent trending and ranging
+    trend_secs: i64,
+    range_secs: i64,
+}
+
+impl RegimeFilter {
+    pub fn new(config: RegimeConfig) -> Self {
+        Self {
+            adx: Adx::new(config.adx_period),
+            hurst: Hurst::new(config.hurst_period, config.hurst_max_lag),
+            vol: RealizedVol::new(config.vol_period),
+            config,
+            pending: None,
+            regime: Regime::Unknown,
+            since: Utc::now().timestamp(),
+            changes: 0,
+            trend_secs: 0,
+            range_secs: 0,
+        }
+    }
+
+    pub fn config(&self) -> &RegimeConfig {
+        &self.config
+    }
+
+    pub fn regime(&self) -> Regime {
+        self.regime
+    }
+
+    /// Size multiplier of the current regime.
+    pub fn scale(&self) -> f64 {
+        self.config.scale(self.regime)
+    }
+
+    /// Feed a candle update. Updates of the same candle replace each other; a candle counts once
+    /// the next one starts. Returns the new regime when it changes.
+    pub fn on_candle(&mut self, candle: &Candle) -> Option<Regime> {
+        let closed = match self.pending {
+            Some(pending) if pending.time_open != candle.time_open => Some(pending),
+            _ => None,
+        };
+        self.pending = Some(*candle);
+        let closed = closed?;
+
+        self.adx.update(&closed);
+        self.hurst.update(&closed);
+        self.vol.update(&closed);
+        let regime = self.classify()?;
+        if regime == self.regime {
+            return None;
+        }
+
+        let now = Utc::now().timestamp();
+        self.account_time(now);
+        info!(
+            "Regime changed: {} -> {} (ADX {:?}, Hurst {:?}, vol {:?})",
+            self.regime,
+            regime,
+            self.adx.value(),
+            self.hurst.value(),
+            self.annualized_vol()
+        );
+        self.regime = regime;
+        self.since = now;
+        self.changes += 1;
+        Some(regime)
+    }
+
+    fn annualized_vol(&self) -> Option<f64> {
+        self.vol.annualized(YEAR_SECS / self.config.interval_secs())
+    }
+
+    /// Regime by majority vote, `None` on a tie
+    fn classify(&self) -> Option<Regime> {
+        let mut votes = 0i32;
+        if let Some(adx) = self.adx.value() {
+            if adx >= self.config.trend_adx {
+                votes += 1;
+            } else if adx <= self.config.range_adx {
+                votes -= 1;
+            }
+        }
+        if self.config.hurst_period > 0 {
+            if let Some(hurst) = self.hurst.value() {
+                if hurst >= self.config.trend_hurst {
+                    votes += 1;
+                } else if hurst <= self.config.range_hurst {
+                    votes -= 1;
+                }
+            }
+        }
+        if self.config.trend_vol > 0.0 {
+            if let Some(vol) = self.annualized_vol() {
+                if vol >= self.config.trend_vol {
+                    votes += 1;
+                }
+            }
+        }
+        match votes.signum() {
+            1 => Some(Regime::Trend),
+            -1 => Some(Regime::Range),
+            _ => None,
+        }
+    }
+
+    fn account_time(&mut self, now: i64) {
+        let elapsed = now - self.since;
+        match self.regime {
+            Regime::Trend => self.trend_secs += elapsed,
+            Regime::Range => self.range_secs += elapsed,
+            Regime::Unknown => {}
+        }
+    }
+
+    /// Log the current regime, its indicators and the time spent in each regime.
+    pub fn print_statistics(&self, label: &str) {
+        let now = Utc::now().timestamp();
+        let current = now - self.since;
+        let (trend, range) = match self.regime {
+            Regime::Trend => (self.trend_secs + current, self.range_secs),
+            Regime::Range => (self.trend_secs, self.range_secs + current),
+            Regime::Unknown => (self.trend_secs, self.range_secs),
+        };
+        info!(
+            "[{}] Regime: {} for {}m (scale {:.2}), ADX {:?}, Hurst {:?}, vol {:?}; \
+             {} changes, trend {}m, range {}m",
+            label,
+            self.regime,
+            current / 60,
+            self.scale(),
+            self.adx.value(),
+            self.hurst.value(),
+            self.annualized_vol(),
+            self.changes,
+            trend / 60,
+            range / 60
+        );
+    }
+}
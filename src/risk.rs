@@ -0,0 +1,229 @@
+//! Kill switch on losses.
+//!
+//! [`RiskSupervisor`] watches the PnL (realized plus unrealized, since start) of every account
+//! and of their aggregate. When an account loses more than `max_daily_loss` since the start of
+//! the UTC day, or falls `max_drawdown` below its peak, entries are blocked for the whole bot
+//! until the next UTC day (`resume = "utc_day"`) or until `resume_file` is created
+//! (`resume = "manual"`). The bot is expected to cancel its resting orders on a breach and, with
+//! `flatten_on_breach`, close its positions.
+#![allow(missing_docs)]
+
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::ValueEnum;
+use serde::Deserialize;
+use tracing::{error, info, warn};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ResumePolicy {
+    /// Resume at the next 00:00 UTC
+    #[default]
+    UtcDay,
+    /// Resume once the resume file exists
+    Manual,
+}
+
+#[derive(Debug, Clone, Deserialize, clap::Args)]
+#[serde(default)]
+pub struct KillSwitchConfig {
+    /// Loss (USD) of one account since 00:00 UTC that halts trading (0 = no limit)
+    #[arg(long, default_value_t = 0.0)]
+    pub max_daily_loss: f64,
+
+    /// Drop (USD) of one account below its PnL peak that halts trading (0 = no limit)
+    #[arg(long, default_value_t = 0.0)]
+    pub max_drawdown: f64,
+
+    /// Loss (USD) of all accounts together since 00:00 UTC that halts trading (0 = no limit)
+    #[arg(long, default_value_t = 0.0)]
+    pub max_total_daily_loss: f64,
+
+    /// Drop (USD) of all accounts together below their PnL peak that halts trading
+    /// (0 = no limit)
+    #[arg(long, default_value_t = 0.0)]
+    pub max_total_drawdown: f64,
+
+    /// Close open positions when a limit is hit
+    #[arg(long, default_value_t = false)]
+    pub flatten_on_breach: bool,
+
+    /// When trading resumes after a breach
+    #[arg(long, value_enum, default_value_t = ResumePolicy::UtcDay)]
+    pub resume: ResumePolicy,
+
+    /// File whose creation resumes trading with `--resume manual`; removed on resume
+    #[arg(long, default_value = ".cache/resume")]
+    pub resume_file: String,
+}
+
+impl Default for KillSwitchConfig {
+    fn default() -> Self {
+        Self {
+            max_daily_loss: 0.0,
+            max_drawdown: 0.0,
+            max_total_daily_loss: 0.0,
+            max_total_drawdown: 0.0,
+            flatten_on_breach: false,
+            resume: ResumePolicy::UtcDay,
+            resume_file: ".cache/resume".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreachKind {
+    DailyLoss,
+    Drawdown,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breach {
+    /// Account (or aggregate) that hit the limit
+    pub label: String,
+    pub kind: BreachKind,
+    pub loss: f64,
+    pub limit: f64,
+}
+
+impl std::fmt::Display for Breach {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            BreachKind::DailyLoss => "daily loss",
+            BreachKind::Drawdown => "drawdown",
+        };
+        write!(f, "{} {} ${:.2} exceeds ${:.2}", self.label, kind, self.loss, self.limit)
+    }
+}
+
+/// PnL marks of one account or of the aggregate.
+#[derive(Debug, Clone)]
+struct Book {
+    label: String,
+    aggregate: bool,
+    day: NaiveDate,
+    day_start_pnl: f64,
+    peak_pnl: f64,
+}
+
+#[derive(Debug, Clone)]
+struct Halt {
+    breach: Breach,
+    day: NaiveDate,
+}
+
+#[derive(Debug, Clone)]
+pub struct RiskSupervisor {
+    config: KillSwitchConfig,
+    books: Vec<Book>,
+    halt: Option<Halt>,
+}
+
+impl RiskSupervisor {
+    pub fn new(config: KillSwitchConfig) -> Self {
+        Self { config, books: vec![], halt: None }
+    }
+
+    pub fn config(&self) -> &KillSwitchConfig {
+        &self.config
+    }
+
+    /// Record the PnL of account `label` (or of the aggregate) at `now`. Returns the breach
+    /// when this update halts trading.
+    pub fn update(
+        &mut self,
+        label: &str,
+        pnl: f64,
+        aggregate: bool,
+        now: DateTime<Utc>,
+    ) -> Option<Breach> {
+        let today = now.date_naive();
+        let index = match self.books.iter().position(|book| book.label == label) {
+            Some(index) => index,
+            None => {
+                self.books.push(Book {
+                    label: label.to_string(),
+                    aggregate,
+                    day: today,
+                    day_start_pnl: pnl,
+                    peak_pnl: pnl,
+                });
+                self.books.len() - 1
+            }
+        };
+        let book = &mut self.books[index];
+        if book.day != today {
+            book.day = today;
+            book.day_start_pnl = pnl;
+        }
+        book.peak_pnl = book.peak_pnl.max(pnl);
+
+        let (daily_limit, drawdown_limit) = if book.aggregate {
+            (self.config.max_total_daily_loss, self.config.max_total_drawdown)
+        } else {
+            (self.config.max_daily_loss, self.config.max_drawdown)
+        };
+        let daily_loss = book.day_start_pnl - pnl;
+        let drawdown = book.peak_pnl - pnl;
+        let breach = if daily_limit > 0.0 && daily_loss >= daily_limit {
+            Breach {
+                label: label.to_string(),
+                kind: BreachKind::DailyLoss,
+                loss: daily_loss,
+                limit: daily_limit,
+            }
+        } else if drawdown_limit > 0.0 && drawdown >= drawdown_limit {
+            Breach {
+                label: label.to_string(),
+                kind: BreachKind::Drawdown,
+                loss: drawdown,
+                limit: drawdown_limit,
+            }
+        } else {
+            return None;
+        };
+
+        if self.halt.is_some() {
+            return None;
+        }
+        error!(
+            "!!! KILL SWITCH: {} - new entries blocked ({:?} resume) !!!",
+            breach, self.config.resume
+        );
+        self.halt = Some(Halt { breach: breach.clone(), day: today });
+        Some(breach)
+    }
+
+    /// Whether new entries are blocked at `now`; lifts the halt once its resume condition holds.
+    pub fn is_halted(&mut self, now: DateTime<Utc>) -> bool {
+        let Some(halt) = &self.halt else { return false };
+        let resume = match self.config.resume {
+            ResumePolicy::UtcDay => now.date_naive() > halt.day,
+            ResumePolicy::Manual => Path::new(&self.config.resume_file).exists(),
+        };
+        if !resume {
+            return true;
+        }
+
+        info!("Kill switch released after {}", halt.breach);
+        if self.config.resume == ResumePolicy::Manual {
+            if let Err(e) = std::fs::remove_file(&self.config.resume_file) {
+                warn!("Failed to remove {}: {}", self.config.resume_file, e);
+            }
+        }
+        // Measure losses from here on, or the same breach would halt again immediately
+        self.books.clear();
+        self.halt = None;
+        false
+    }
+
+    pub fn can_enter(&mut self, now: DateTime<Utc>) -> bool {
+        !self.is_halted(now)
+    }
+
+    /// The breach currently halting trading.
+    pub fn breach(&self) -> Option<&Breach> {
+        self.halt.as_ref().map(|halt| &halt.breach)
+    }
+}
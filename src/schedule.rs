@@ -0,0 +1,189 @@
+//! Trading schedule: when new entries are allowed.
+//!
+//! Entries are allowed inside the configured UTC session windows, on the configured weekdays,
+//! and outside any blackout listed in the blackout file. The blackout file has one event per
+//! line, `#` starting a comment:
+//!
+//! ```text
+//! # start                 [end]                  label
+//! 2026-10-29T18:00:00Z    FOMC
+//! 2026-11-06T13:30:00Z    2026-11-06T14:30:00Z   NFP
+//! ```
+//!
+//! Events without an end are blocked for `blackout_margin_secs` on either side.
+#![allow(missing_docs)]
+
+use std::fs;
+
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc, Weekday};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SessionWindow {
+    /// UTC start, "HH:MM"
+    pub start: String,
+    /// UTC end, "HH:MM"; before `start` for windows spanning midnight
+    pub end: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    /// Windows in which new entries are allowed; empty means all day
+    pub windows: Vec<SessionWindow>,
+    /// UTC days on which new entries are allowed, e.g. `["Mon", "Tue"]`; empty means every day
+    pub weekdays: Vec<Weekday>,
+    /// File listing blackout events
+    pub blackout_file: Option<String>,
+    /// Blocked time before and after events that have no end
+    pub blackout_margin_secs: u64,
+    /// Close open trades when entries become blocked
+    pub flatten_at_window_end: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Blackout {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub label: String,
+}
+
+/// Why entries are currently blocked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryBlock {
+    OutsideWindow,
+    Weekday(Weekday),
+    Blackout(String),
+}
+
+impl std::fmt::Display for EntryBlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EntryBlock::OutsideWindow => write!(f, "outside trading windows"),
+            EntryBlock::Weekday(day) => write!(f, "no trading on {}", day),
+            EntryBlock::Blackout(label) => write!(f, "blackout ({})", label),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    windows: Vec<(NaiveTime, NaiveTime)>,
+    weekdays: Vec<Weekday>,
+    blackouts: Vec<Blackout>,
+    flatten_at_window_end: bool,
+    /// Whether entries were allowed at the last `window_ended` call
+    was_open: bool,
+}
+
+impl Default for Schedule {
+    /// A schedule that always allows entries.
+    fn default() -> Self {
+        Self {
+            windows: vec![],
+            weekdays: vec![],
+            blackouts: vec![],
+            flatten_at_window_end: false,
+            was_open: true,
+        }
+    }
+}
+
+fn parse_blackouts(content: &str, margin: Duration) -> eyre::Result<Vec<Blackout>> {
+    let mut blackouts = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut fields = line.split_whitespace().peekable();
+        let start: DateTime<Utc> = fields
+            .next()
+            .unwrap_or_default()
+            .parse()
+            .map_err(|e| eyre::eyre!("Blackout line {}: invalid start: {}", number + 1, e))?;
+        let end = fields.peek().and_then(|field| field.parse::<DateTime<Utc>>().ok());
+        let (start, end) = match end {
+            Some(end) => {
+                fields.next();
+                (start, end)
+            }
+            None => (start - margin, start + margin),
+        };
+        blackouts.push(Blackout { start, end, label: fields.collect::<Vec<_>>().join(" ") });
+    }
+    Ok(blackouts)
+}
+
+impl Schedule {
+    pub fn new(config: &ScheduleConfig) -> eyre::Result<Self> {
+        let parse_time = |time: &str| {
+            NaiveTime::parse_from_str(time, "%H:%M")
+                .map_err(|e| eyre::eyre!("Invalid session time {:?}: {}", time, e))
+        };
+        let windows = config
+            .windows
+            .iter()
+            .map(|window| Ok((parse_time(&window.start)?, parse_time(&window.end)?)))
+            .collect::<eyre::Result<Vec<_>>>()?;
+
+        let blackouts = match &config.blackout_file {
+            Some(path) => parse_blackouts(
+                &fs::read_to_string(path)?,
+                Duration::seconds(config.blackout_margin_secs as i64),
+            )?,
+            None => vec![],
+        };
+
+        Ok(Self {
+            windows,
+            weekdays: config.weekdays.clone(),
+            blackouts,
+            flatten_at_window_end: config.flatten_at_window_end,
+            was_open: true,
+        })
+    }
+
+    pub fn blackouts(&self) -> &[Blackout] {
+        &self.blackouts
+    }
+
+    /// `Ok` if new entries are allowed at `now`.
+    pub fn check(&self, now: DateTime<Utc>) -> Result<(), EntryBlock> {
+        if !self.weekdays.is_empty() && !self.weekdays.contains(&now.weekday()) {
+            return Err(EntryBlock::Weekday(now.weekday()));
+        }
+
+        let time = now.time();
+        let in_window = |&(start, end): &(NaiveTime, NaiveTime)| {
+            if start <= end {
+                start <= time && time < end
+            } else {
+                time >= start || time < end
+            }
+        };
+        if !self.windows.is_empty() && !self.windows.iter().any(in_window) {
+            return Err(EntryBlock::OutsideWindow);
+        }
+
+        if let Some(blackout) =
+            self.blackouts.iter().find(|blackout| blackout.start <= now && now < blackout.end)
+        {
+            return Err(EntryBlock::Blackout(blackout.label.clone()));
+        }
+        Ok(())
+    }
+
+    pub fn can_enter(&self, now: DateTime<Utc>) -> bool {
+        self.check(now).is_ok()
+    }
+
+    /// `true` once each time entries go from allowed to blocked, if open trades should then be
+    /// flattened.
+    pub fn window_ended(&mut self, now: DateTime<Utc>) -> bool {
+        let open = self.can_enter(now);
+        let ended = self.was_open && !open;
+        self.was_open = open;
+        ended && self.flatten_at_window_end
+    }
+}
@@ -0,0 +1,307 @@
+//! Graceful shutdown shared by the trading bots.
+//!
+//! On Ctrl+C or SIGTERM ([`ShutdownSignal`]) a bot stops its loop and hands the trades it still
+//! holds to [`close_out`], which applies the `close_policy`. The bot then prints its final
+//! statistics and writes a [`ShutdownState`] to `shutdown_state_path`, so the next run (or an
+//! operator) knows what was left behind.
+//!
+//! - `leave`: cancel the orders that would open or grow a position and place a reduce-only
+//!   stop-market order at the stop of every trade, so positions stay protected on the exchange
+//! - `cancel-entries`: only cancel the orders that would open or grow a position
+//! - `flatten`: cancel everything and close every position, see [`crate::flatten`]
+#![allow(missing_docs)]
+
+use std::{fs, path::Path};
+
+use chrono::Utc;
+use clap::ValueEnum;
+use hyperliquid_rust_sdk::{
+    ClientCancelRequest, ClientOrder, ClientOrderRequest, ClientTrigger, ExchangeDataStatus,
+    ExchangeResponseStatus, InfoClient,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::{
+    flatten::{flatten_all, FlattenAccount, FlattenConfig},
+    pretrade::PreTradeChecker,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClosePolicy {
+    /// Keep positions, protected by exchange-side stop orders
+    #[default]
+    Leave,
+    /// Only cancel orders that would open or grow a position
+    CancelEntries,
+    /// Cancel all orders and close all positions
+    Flatten,
+}
+
+#[derive(Debug, Clone, Deserialize, clap::Args)]
+#[serde(default)]
+pub struct ShutdownConfig {
+    /// What to do with open positions and orders on shutdown
+    #[arg(long, value_enum, default_value_t = ClosePolicy::Leave)]
+    pub close_policy: ClosePolicy,
+
+    /// File the state left behind is written to on shutdown [default: .cache/<bot>_shutdown.json]
+    #[arg(long)]
+    pub shutdown_state_path: Option<String>,
+
+    /// Slippage, timeout and retries of `--close-policy flatten`
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub flatten: FlattenConfig,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            close_policy: ClosePolicy::Leave,
+            shutdown_state_path: None,
+            flatten: FlattenConfig::default(),
+        }
+    }
+}
+
+impl ShutdownConfig {
+    pub fn state_path(&self, bot: &str) -> String {
+        self.shutdown_state_path.clone().unwrap_or_else(|| format!(".cache/{}_shutdown.json", bot))
+    }
+}
+
+/// Waits for Ctrl+C or, on Unix, SIGTERM (as sent by `docker stop` and Kubernetes).
+#[derive(Debug)]
+pub struct ShutdownSignal {
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+}
+
+impl ShutdownSignal {
+    /// Install the handlers; create this once, before the bot's loop.
+    pub fn new() -> eyre::Result<Self> {
+        Ok(Self {
+            #[cfg(unix)]
+            terminate: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?,
+        })
+    }
+
+    /// Resolves with the name of the signal received.
+    pub async fn recv(&mut self) -> &'static str {
+        #[cfg(unix)]
+        {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => "Ctrl+C",
+                _ = self.terminate.recv() => "SIGTERM",
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = tokio::signal::ctrl_c().await;
+            "Ctrl+C"
+        }
+    }
+}
+
+/// A trade a bot still holds when it shuts down.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpenTrade {
+    /// Label of the account holding it, as in [`FlattenAccount::label`]
+    pub label: String,
+    pub coin: String,
+    /// Signed size in coins
+    pub size: f64,
+    pub entry_price: f64,
+    /// Stop to protect the trade with under [`ClosePolicy::Leave`]; `None` when the bot has
+    /// none, or already keeps a stop order on the exchange
+    pub stop: Option<f64>,
+    pub take_profit: Option<f64>,
+}
+
+/// What a bot left behind, written on shutdown.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShutdownState {
+    pub bot: String,
+    /// UTC seconds
+    pub time: i64,
+    pub reason: String,
+    pub policy: ClosePolicy,
+    /// Whether the exchange-side close out went through without errors
+    pub completed: bool,
+    pub open_trades: Vec<OpenTrade>,
+    pub closed_trades: usize,
+    pub total_pnl: f64,
+}
+
+impl ShutdownState {
+    pub fn new(bot: &str, reason: &str, policy: ClosePolicy) -> Self {
+        Self {
+            bot: bot.to_string(),
+            time: Utc::now().timestamp(),
+            reason: reason.to_string(),
+            policy,
+            completed: false,
+            open_trades: Vec::new(),
+            closed_trades: 0,
+            total_pnl: 0.0,
+        }
+    }
+
+    /// Write the state atomically (temp file + rename).
+    pub fn save(&self, path: &str) -> eyre::Result<()> {
+        if let Some(dir) = Path::new(path).parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp_path = format!("{}.tmp", path);
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp_path, path)?;
+        info!("Shutdown state written to {}", path);
+        Ok(())
+    }
+}
+
+/// Cancel the orders of `account` on `coins` that would open or grow its position there.
+async fn cancel_entries(
+    info_client: &InfoClient,
+    account: &FlattenAccount<'_>,
+    coins: &[String],
+) -> eyre::Result<usize> {
+    let state = info_client.user_state(account.user).await?;
+    let mut cancelled = 0;
+    for order in info_client
+        .open_orders(account.user)
+        .await?
+        .into_iter()
+        .filter(|order| coins.is_empty() || coins.contains(&order.coin))
+    {
+        let position = match state.asset_positions.iter().find(|p| p.position.coin == order.coin) {
+            Some(asset) => asset.position.szi.parse()?,
+            None => 0.0,
+        };
+        let is_buy = order.side == "B";
+        if (is_buy && position < 0.0) || (!is_buy && position > 0.0) {
+            // Against the position: a take-profit or stop, kept
+            continue;
+        }
+        let cancel = ClientCancelRequest { asset: order.coin.clone(), oid: order.oid };
+        match account.exchange_client.cancel(cancel, None).await? {
+            ExchangeResponseStatus::Ok(_) => cancelled += 1,
+            ExchangeResponseStatus::Err(e) => {
+                error!("[{}] Cancel error for {} {}: {}", account.label, order.coin, order.oid, e)
+            }
+        }
+    }
+    Ok(cancelled)
+}
+
+/// Place a reduce-only stop-market order for `trade` on `account`. Returns whether it was
+/// accepted.
+async fn place_stop(
+    account: &FlattenAccount<'_>,
+    pre_trade: &PreTradeChecker,
+    trade: &OpenTrade,
+    stop: f64,
+    slippage_bps: f64,
+) -> eyre::Result<bool> {
+    let is_buy = trade.size < 0.0;
+    let trigger_px = pre_trade.round_price(&trade.coin, stop);
+    // Worst price the triggered market order may fill at
+    let slippage = slippage_bps / 10_000.0;
+    let limit_px = pre_trade.round_price(
+        &trade.coin,
+        if is_buy { stop * (1.0 + slippage) } else { stop * (1.0 - slippage) },
+    );
+    let order = ClientOrderRequest {
+        asset: trade.coin.clone(),
+        is_buy,
+        reduce_only: true,
+        limit_px,
+        sz: pre_trade.round_size(&trade.coin, trade.size.abs()),
+        cloid: None,
+        order_type: ClientOrder::Trigger(ClientTrigger {
+            is_market: true,
+            trigger_px,
+            tpsl: "sl".to_string(),
+        }),
+    };
+    let error = match account.exchange_client.order(order, None).await? {
+        ExchangeResponseStatus::Ok(response) => {
+            match response.data.as_ref().and_then(|data| data.statuses.first()) {
+                Some(ExchangeDataStatus::Error(e)) => Some(e.clone()),
+                _ => None,
+            }
+        }
+        ExchangeResponseStatus::Err(e) => Some(e),
+    };
+    match error {
+        Some(e) => {
+            error!("[{}] Stop order error for {} {}: {}", account.label, trade.size, trade.coin, e);
+            Ok(false)
+        }
+        None => {
+            info!(
+                "[{}] Left {} {} open with a stop at {}",
+                account.label, trade.size, trade.coin, trigger_px
+            );
+            Ok(true)
+        }
+    }
+}
+
+/// Apply `config.close_policy` to `accounts` on `coins` (every coin if empty), given the
+/// `open_trades` the bot still holds. Returns whether everything went through; failures are
+/// logged so the caller can still persist its state and exit.
+pub async fn close_out(
+    info_client: &InfoClient,
+    pre_trade: &PreTradeChecker,
+    accounts: &[FlattenAccount<'_>],
+    coins: &[String],
+    open_trades: &[OpenTrade],
+    config: &ShutdownConfig,
+) -> bool {
+    info!("Closing out with policy {:?}", config.close_policy);
+    if config.close_policy == ClosePolicy::Flatten {
+        return match flatten_all(info_client, pre_trade, accounts, coins, &config.flatten).await {
+            Ok(report) => report.is_flat(),
+            Err(e) => {
+                error!("Failed to flatten: {}", e);
+                false
+            }
+        };
+    }
+
+    let mut completed = true;
+    for account in accounts {
+        match cancel_entries(info_client, account, coins).await {
+            Ok(cancelled) => info!("[{}] Cancelled {} entry order(s)", account.label, cancelled),
+            Err(e) => {
+                error!("[{}] Failed to cancel entry orders: {}", account.label, e);
+                completed = false;
+            }
+        }
+        if config.close_policy != ClosePolicy::Leave {
+            continue;
+        }
+        for trade in open_trades.iter().filter(|trade| trade.label == account.label) {
+            let Some(stop) = trade.stop else {
+                warn!(
+                    "[{}] Leaving {} {} open without placing a stop",
+                    account.label, trade.size, trade.coin
+                );
+                continue;
+            };
+            match place_stop(account, pre_trade, trade, stop, config.flatten.flatten_slippage_bps)
+                .await
+            {
+                Ok(placed) => completed &= placed,
+                Err(e) => {
+                    error!("[{}] Failed to place stop for {}: {}", account.label, trade.coin, e);
+                    completed = false;
+                }
+            }
+        }
+    }
+    completed
+}
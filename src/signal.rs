@@ -0,0 +1,173 @@
+//! Order-book imbalance and the scored signal that combines it with the VWAP crossover.
+#![allow(missing_docs)]
+
+use serde::Deserialize;
+
+use crate::indicators::{Ema, Indicator};
+
+/// Which part of the book counts towards the imbalance and how it is smoothed.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ImbalanceConfig {
+    /// Only the best N levels per side (all levels if unset)
+    pub depth_levels: Option<usize>,
+    /// Only levels within this distance from mid, in basis points (no limit if unset)
+    pub max_distance_bps: Option<f64>,
+    /// Levels are weighted by `exp(-distance_bps / distance_decay_bps)`; unset means equal weight
+    pub distance_decay_bps: Option<f64>,
+    /// EMA period applied to successive imbalance readings
+    pub smoothing_periods: usize,
+}
+
+impl Default for ImbalanceConfig {
+    fn default() -> Self {
+        Self {
+            depth_levels: Some(10),
+            max_distance_bps: None,
+            distance_decay_bps: None,
+            smoothing_periods: 20,
+        }
+    }
+}
+
+/// Bid/ask volume imbalance in `[-1, 1]`; positive means more resting bids than asks.
+///
+/// `bids` and `asks` are `(price, size)` levels, best first.
+pub fn book_imbalance(bids: &[(f64, f64)], asks: &[(f64, f64)], config: &ImbalanceConfig) -> f64 {
+    let (Some(&(best_bid, _)), Some(&(best_ask, _))) = (bids.first(), asks.first()) else {
+        return 0.0;
+    };
+    let mid = (best_bid + best_ask) / 2.0;
+    if mid <= 0.0 {
+        return 0.0;
+    }
+
+    let side_volume = |levels: &[(f64, f64)]| -> f64 {
+        levels
+            .iter()
+            .take(config.depth_levels.unwrap_or(usize::MAX))
+            .map(|&(px, sz)| ((px - mid).abs() / mid * 10_000.0, sz))
+            .take_while(|&(distance_bps, _)| {
+                !config.max_distance_bps.is_some_and(|max| distance_bps > max)
+            })
+            .map(|(distance_bps, sz)| match config.distance_decay_bps {
+                Some(decay) if decay > 0.0 => sz * (-distance_bps / decay).exp(),
+                _ => sz,
+            })
+            .sum()
+    };
+
+    let bid_volume = side_volume(bids);
+    let ask_volume = side_volume(asks);
+    if (bid_volume + ask_volume).abs() < f64::EPSILON {
+        return 0.0;
+    }
+    (bid_volume - ask_volume) / (bid_volume + ask_volume)
+}
+
+/// Book imbalance smoothed over successive snapshots.
+#[derive(Debug, Clone)]
+pub struct ImbalanceTracker {
+    config: ImbalanceConfig,
+    ema: Ema,
+    last_raw: f64,
+}
+
+impl ImbalanceTracker {
+    pub fn new(config: ImbalanceConfig) -> Self {
+        let ema = Ema::new(config.smoothing_periods);
+        Self { config, ema, last_raw: 0.0 }
+    }
+
+    /// Feed a book snapshot and return the raw (unsmoothed) imbalance.
+    pub fn update(&mut self, bids: &[(f64, f64)], asks: &[(f64, f64)]) -> f64 {
+        self.last_raw = book_imbalance(bids, asks, &self.config);
+        self.ema.update_value(self.last_raw);
+        self.last_raw
+    }
+
+    /// Last raw imbalance reading.
+    pub fn raw(&self) -> f64 {
+        self.last_raw
+    }
+
+    /// Smoothed imbalance, once `smoothing_periods` snapshots have been seen.
+    pub fn smoothed(&self) -> Option<f64> {
+        self.ema.value()
+    }
+}
+
+/// Weights and thresholds for combining signal components into one score.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SignalConfig {
+    pub vwap_weight: f64,
+    pub imbalance_weight: f64,
+    /// Weight of the aggressor volume delta of the last completed tape bar
+    pub flow_weight: f64,
+    /// VWAP spread (5m vs 1h, in bps) that maps to a full +/-1 VWAP component
+    pub vwap_scale_bps: f64,
+    /// Minimum absolute score needed to enter a trade
+    pub entry_threshold: f64,
+}
+
+impl Default for SignalConfig {
+    fn default() -> Self {
+        Self {
+            vwap_weight: 1.0,
+            imbalance_weight: 0.0,
+            flow_weight: 0.0,
+            vwap_scale_bps: 10.0,
+            entry_threshold: 0.5,
+        }
+    }
+}
+
+/// Components of a trading decision, kept together so they can be logged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SignalScore {
+    /// Fast vs slow VWAP spread, scaled into `[-1, 1]`
+    pub vwap: f64,
+    /// Smoothed book imbalance in `[-1, 1]`
+    pub imbalance: f64,
+    /// Aggressor buy/sell volume delta ratio in `[-1, 1]`
+    pub flow: f64,
+    /// Weighted sum of the components
+    pub score: f64,
+}
+
+impl SignalScore {
+    /// Score a fast/slow VWAP pair, a book imbalance reading and a tape volume delta ratio.
+    pub fn compute(
+        fast_vwap: f64,
+        slow_vwap: f64,
+        imbalance: f64,
+        flow: f64,
+        config: &SignalConfig,
+    ) -> Self {
+        let spread_bps =
+            if slow_vwap > 0.0 { (fast_vwap - slow_vwap) / slow_vwap * 10_000.0 } else { 0.0 };
+        let vwap = if config.vwap_scale_bps > 0.0 {
+            (spread_bps / config.vwap_scale_bps).clamp(-1.0, 1.0)
+        } else {
+            spread_bps.signum()
+        };
+        let imbalance = imbalance.clamp(-1.0, 1.0);
+        let flow = flow.clamp(-1.0, 1.0);
+        let score = config.vwap_weight * vwap +
+            config.imbalance_weight * imbalance +
+            config.flow_weight * flow;
+        Self { vwap, imbalance, flow, score }
+    }
+
+    /// `1.0` for long, `-1.0` for short, `0.0` when the score is inside the threshold.
+    pub fn direction(&self, config: &SignalConfig) -> f64 {
+        if self.score >= config.entry_threshold {
+            1.0
+        } else if self.score <= -config.entry_threshold {
+            -1.0
+        } else {
+            0.0
+        }
+    }
+}
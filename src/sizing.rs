@@ -0,0 +1,164 @@
+//! Position sizing.
+//!
+//! A [`Sizer`] turns account equity, the entry price and, depending on the mode, the stop price
+//! or recent volatility into a position notional. Live bots feed it the account value reported by
+//! the exchange; the simulator feeds it its simulated equity, so both size trades the same way.
+#![allow(missing_docs)]
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::indicators::{Indicator, RealizedVol, TickBars};
+
+const YEAR_SECS: f64 = 365.0 * 86_400.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum Sizer {
+    /// The same notional (USD) for every trade.
+    FixedNotional { notional: f64 },
+    /// `fraction` of equity as margin, times `leverage`.
+    EquityFraction { fraction: f64, leverage: f64 },
+    /// Lose `risk_fraction` of equity if the stop is hit.
+    RiskToStop { risk_fraction: f64, max_leverage: f64 },
+    /// Notional such that the position's annualized volatility is `target_vol` of equity.
+    VolTarget { target_vol: f64, max_leverage: f64 },
+}
+
+/// What a [`Sizer`] knows about the trade being sized.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SizingInput {
+    pub price: f64,
+    pub equity: f64,
+    pub stop_price: Option<f64>,
+    /// Annualized volatility of returns
+    pub volatility: Option<f64>,
+}
+
+impl Sizer {
+    /// Position notional in USD.
+    pub fn notional(&self, input: &SizingInput) -> eyre::Result<f64> {
+        if input.price <= 0.0 {
+            return Err(eyre::eyre!("Cannot size at price {}", input.price));
+        }
+        let equity = |input: &SizingInput| {
+            if input.equity > 0.0 {
+                Ok(input.equity)
+            } else {
+                Err(eyre::eyre!("Cannot size with equity {:.2}", input.equity))
+            }
+        };
+
+        let (notional, max_leverage) = match *self {
+            Sizer::FixedNotional { notional } => return Ok(notional),
+            Sizer::EquityFraction { fraction, leverage } => {
+                (equity(input)? * fraction * leverage, leverage)
+            }
+            Sizer::RiskToStop { risk_fraction, max_leverage } => {
+                let stop = input
+                    .stop_price
+                    .ok_or_else(|| eyre::eyre!("Risk-to-stop needs a stop price"))?;
+                let distance = (input.price - stop).abs() / input.price;
+                if distance <= 0.0 {
+                    return Err(eyre::eyre!("Stop {} is at the entry price", stop));
+                }
+                (equity(input)? * risk_fraction / distance, max_leverage)
+            }
+            Sizer::VolTarget { target_vol, max_leverage } => {
+                let vol = input
+                    .volatility
+                    .filter(|vol| *vol > 0.0)
+                    .ok_or_else(|| eyre::eyre!("Volatility target needs a volatility estimate"))?;
+                (equity(input)? * target_vol / vol, max_leverage)
+            }
+        };
+        Ok(notional.min(input.equity * max_leverage))
+    }
+
+    /// Position size in coins, before rounding to the asset's size decimals.
+    pub fn size(&self, input: &SizingInput) -> eyre::Result<f64> {
+        Ok(self.notional(input)? / input.price)
+    }
+
+    /// Whether sizing needs a volatility estimate.
+    pub fn needs_volatility(&self) -> bool {
+        matches!(self, Sizer::VolTarget { .. })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SizingMode {
+    FixedNotional,
+    EquityFraction,
+    RiskToStop,
+    VolTarget,
+}
+
+/// Command-line selection of a [`Sizer`].
+#[derive(Debug, Clone, clap::Args)]
+pub struct SizerArgs {
+    /// How to size trades; without it the bot sizes by amount and leverage
+    #[arg(long, value_enum)]
+    pub sizing: Option<SizingMode>,
+
+    /// Notional (USD), equity fraction, risk fraction or annual volatility target,
+    /// depending on `--sizing`
+    #[arg(long)]
+    pub sizing_value: Option<f64>,
+
+    /// Leverage for equity-fraction sizing, notional cap (x equity) for the other modes
+    #[arg(long, default_value_t = 3.0)]
+    pub sizing_max_leverage: f64,
+}
+
+impl SizerArgs {
+    pub fn sizer(&self) -> eyre::Result<Option<Sizer>> {
+        let Some(mode) = self.sizing else { return Ok(None) };
+        let value = self
+            .sizing_value
+            .ok_or_else(|| eyre::eyre!("--sizing {:?} needs --sizing-value", mode))?;
+        let max_leverage = self.sizing_max_leverage;
+        Ok(Some(match mode {
+            SizingMode::FixedNotional => Sizer::FixedNotional { notional: value },
+            SizingMode::EquityFraction => {
+                Sizer::EquityFraction { fraction: value, leverage: max_leverage }
+            }
+            SizingMode::RiskToStop => Sizer::RiskToStop { risk_fraction: value, max_leverage },
+            SizingMode::VolTarget => Sizer::VolTarget { target_vol: value, max_leverage },
+        }))
+    }
+}
+
+/// Realized volatility of a price stream, sampled into fixed-length bars.
+///
+/// Works on raw mids, so live bots and the tick simulator need no candle feed.
+#[derive(Debug, Clone)]
+pub struct PriceVol {
+    bars: TickBars,
+    vol: RealizedVol,
+}
+
+impl PriceVol {
+    pub fn new(bar_secs: u64, periods: usize) -> Self {
+        Self { bars: TickBars::new(bar_secs), vol: RealizedVol::new(periods) }
+    }
+
+    /// Record a price seen at `ts` (UTC seconds).
+    pub fn update(&mut self, ts: i64, price: f64) {
+        if let Some(done) = self.bars.update(ts, price) {
+            self.vol.update(&done);
+        }
+    }
+
+    /// Annualized volatility once enough bars have closed.
+    pub fn annualized(&self) -> Option<f64> {
+        self.vol.annualized(YEAR_SECS / self.bars.bar_secs() as f64)
+    }
+}
+
+impl Default for PriceVol {
+    /// Five-minute bars over the last four hours.
+    fn default() -> Self {
+        Self::new(300, 48)
+    }
+}
@@ -0,0 +1,275 @@
+//! Trade tape: aggressor classification, cumulative volume delta (CVD), per-bar buy/sell volume
+//! and large-print detection from the `Trades` feed.
+#![allow(missing_docs)]
+
+use std::collections::{HashSet, VecDeque};
+
+use hyperliquid_rust_sdk::Trade;
+use serde::{Deserialize, Serialize};
+
+/// Trades remembered to drop repeats; more than a resubscription snapshot holds
+const RECENT_TRADES: usize = 2048;
+
+/// Side that crossed the spread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Aggressor {
+    Buy,
+    Sell,
+}
+
+/// A parsed public trade.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TapeTrade {
+    /// Exchange timestamp in milliseconds
+    pub time: u64,
+    pub tid: u64,
+    pub price: f64,
+    pub size: f64,
+    pub aggressor: Aggressor,
+}
+
+impl TapeTrade {
+    pub fn notional(&self) -> f64 {
+        self.price * self.size
+    }
+
+    /// Size signed by aggressor: positive for buys, negative for sells.
+    pub fn signed_size(&self) -> f64 {
+        match self.aggressor {
+            Aggressor::Buy => self.size,
+            Aggressor::Sell => -self.size,
+        }
+    }
+}
+
+impl TryFrom<&Trade> for TapeTrade {
+    type Error = eyre::Report;
+
+    fn try_from(trade: &Trade) -> eyre::Result<Self> {
+        // The trades feed reports the taker's side: "B" is a buy lifting the ask.
+        let aggressor = match trade.side.as_str() {
+            "B" => Aggressor::Buy,
+            "A" => Aggressor::Sell,
+            other => return Err(eyre::eyre!("Unknown trade side: {}", other)),
+        };
+        Ok(Self {
+            time: trade.time,
+            tid: trade.tid,
+            price: trade.px.parse()?,
+            size: trade.sz.parse()?,
+            aggressor,
+        })
+    }
+}
+
+/// Buy and sell aggressor volume over one time bar.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct VolumeBar {
+    /// Bar start in milliseconds
+    pub start: u64,
+    pub buy_volume: f64,
+    pub sell_volume: f64,
+    pub trades: u32,
+    pub large_buys: u32,
+    pub large_sells: u32,
+}
+
+impl VolumeBar {
+    pub fn delta(&self) -> f64 {
+        self.buy_volume - self.sell_volume
+    }
+
+    /// Volume delta normalised to `[-1, 1]`.
+    pub fn delta_ratio(&self) -> f64 {
+        let total = self.buy_volume + self.sell_volume;
+        if total > 0.0 {
+            self.delta() / total
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TapeConfig {
+    /// Length of a volume bar in seconds
+    pub bar_secs: u64,
+    /// Number of completed bars to keep
+    pub bars_kept: usize,
+    /// Trades with at least this notional (USD) count as large prints
+    pub large_print_notional: f64,
+}
+
+impl Default for TapeConfig {
+    fn default() -> Self {
+        Self { bar_secs: 60, bars_kept: 60, large_print_notional: 50_000.0 }
+    }
+}
+
+/// Running view of aggressor flow for one asset.
+#[derive(Debug, Clone)]
+pub struct TradeTape {
+    config: TapeConfig,
+    cvd: f64,
+    current: Option<VolumeBar>,
+    bars: VecDeque<VolumeBar>,
+    /// `(time, tid)` of the last [`RECENT_TRADES`] trades, oldest first, and the same as a set
+    recent: VecDeque<(u64, u64)>,
+    seen: HashSet<(u64, u64)>,
+}
+
+impl TradeTape {
+    pub fn new(config: TapeConfig) -> Self {
+        let bars = VecDeque::with_capacity(config.bars_kept + 1);
+        Self {
+            config,
+            cvd: 0.0,
+            current: None,
+            bars,
+            recent: VecDeque::with_capacity(RECENT_TRADES + 1),
+            seen: HashSet::with_capacity(RECENT_TRADES + 1),
+        }
+    }
+
+    /// Record a trade. Returns the bar that was completed by this trade, if any.
+    ///
+    /// A trade among the recent ones already seen is ignored, so that the snapshot sent on
+    /// (re)subscription doesn't double count. `tid`s are hashes, not sequence numbers, so trades
+    /// are matched on `(time, tid)`. A trade older than the current bar is counted in it.
+    pub fn update(&mut self, trade: &TapeTrade) -> Option<VolumeBar> {
+        let key = (trade.time, trade.tid);
+        if !self.seen.insert(key) {
+            return None;
+        }
+        self.recent.push_back(key);
+        if self.recent.len() > RECENT_TRADES {
+            if let Some(old) = self.recent.pop_front() {
+                self.seen.remove(&old);
+            }
+        }
+
+        let bar_ms = self.config.bar_secs.max(1) * 1000;
+        let mut bar_start = trade.time - trade.time % bar_ms;
+        if let Some(current) = self.current {
+            bar_start = bar_start.max(current.start);
+        }
+
+        let mut completed = None;
+        if self.current.is_some_and(|bar| bar.start != bar_start) {
+            completed = self.current.take();
+            if let Some(bar) = completed {
+                self.bars.push_back(bar);
+                if self.bars.len() > self.config.bars_kept {
+                    self.bars.pop_front();
+                }
+            }
+        }
+
+        let bar = self.current.get_or_insert(VolumeBar { start: bar_start, ..Default::default() });
+        let is_large = trade.notional() >= self.config.large_print_notional;
+        match trade.aggressor {
+            Aggressor::Buy => {
+                bar.buy_volume += trade.size;
+                bar.large_buys += is_large as u32;
+            }
+            Aggressor::Sell => {
+                bar.sell_volume += trade.size;
+                bar.large_sells += is_large as u32;
+            }
+        }
+        bar.trades += 1;
+        self.cvd += trade.signed_size();
+
+        completed
+    }
+
+    /// Whether `trade` is a large print under the configured threshold.
+    pub fn is_large_print(&self, trade: &TapeTrade) -> bool {
+        trade.notional() >= self.config.large_print_notional
+    }
+
+    /// Cumulative volume delta since the tape started.
+    pub fn cvd(&self) -> f64 {
+        self.cvd
+    }
+
+    /// The bar currently being filled.
+    pub fn current_bar(&self) -> Option<&VolumeBar> {
+        self.current.as_ref()
+    }
+
+    /// The most recently completed bar.
+    pub fn last_bar(&self) -> Option<&VolumeBar> {
+        self.bars.back()
+    }
+
+    /// Completed bars, oldest first.
+    pub fn bars(&self) -> impl Iterator<Item = &VolumeBar> {
+        self.bars.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(time: u64, tid: u64, size: f64, aggressor: Aggressor) -> TapeTrade {
+        TapeTrade { time, tid, price: 100.0, size, aggressor }
+    }
+
+    fn tape() -> TradeTape {
+        TradeTape::new(TapeConfig { bar_secs: 60, bars_kept: 10, large_print_notional: 1_000.0 })
+    }
+
+    #[test]
+    fn counts_trades_with_out_of_order_tids() {
+        let mut tape = tape();
+        tape.update(&trade(1_000, 900, 1.0, Aggressor::Buy));
+        tape.update(&trade(1_001, 15, 2.0, Aggressor::Sell));
+        tape.update(&trade(1_002, 500, 4.0, Aggressor::Buy));
+        let bar = tape.current_bar().unwrap();
+        assert_eq!(bar.trades, 3);
+        assert_eq!((bar.buy_volume, bar.sell_volume), (5.0, 2.0));
+        assert_eq!(tape.cvd(), 3.0);
+    }
+
+    #[test]
+    fn ignores_repeated_trades() {
+        let mut tape = tape();
+        let snapshot =
+            [trade(1_000, 7, 1.0, Aggressor::Buy), trade(1_500, 3, 2.0, Aggressor::Sell)];
+        for trade in snapshot.iter().chain(snapshot.iter()) {
+            tape.update(trade);
+        }
+        assert_eq!(tape.current_bar().unwrap().trades, 2);
+        assert_eq!(tape.cvd(), -1.0);
+        // Same tid at another time is another trade
+        tape.update(&trade(2_000, 7, 1.0, Aggressor::Buy));
+        assert_eq!(tape.cvd(), 0.0);
+    }
+
+    #[test]
+    fn forgets_old_trades() {
+        let mut tape = tape();
+        for tid in 0..(RECENT_TRADES as u64 + 10) {
+            tape.update(&trade(1_000, tid, 1.0, Aggressor::Buy));
+        }
+        assert_eq!(tape.seen.len(), RECENT_TRADES);
+        assert_eq!(tape.recent.len(), RECENT_TRADES);
+    }
+
+    #[test]
+    fn completes_bars_and_counts_large_prints() {
+        let mut tape = tape();
+        assert_eq!(tape.update(&trade(59_000, 1, 20.0, Aggressor::Sell)), None);
+        let completed = tape.update(&trade(61_000, 2, 1.0, Aggressor::Buy)).unwrap();
+        assert_eq!(completed.start, 0);
+        assert_eq!(completed.large_sells, 1);
+        assert_eq!(completed.delta_ratio(), -1.0);
+        // A late trade from the completed bar is counted in the current one
+        assert_eq!(tape.update(&trade(58_000, 3, 1.0, Aggressor::Buy)), None);
+        assert_eq!(tape.current_bar().unwrap().trades, 2);
+        assert_eq!(tape.last_bar().unwrap().trades, 1);
+    }
+}
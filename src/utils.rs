@@ -2,17 +2,24 @@
 #![allow(dead_code)]
 #![allow(missing_docs)]
 
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use chrono::Utc;
 use ethers::{signers::LocalWallet, types::H160};
 use hyperliquid_rust_sdk::{
-    ClientLimit, ClientOrder, ClientOrderRequest, ExchangeClient, ExchangeDataStatus,
-    ExchangeResponseStatus,
+    ClientCancelRequest, ClientLimit, ClientOrder, ClientOrderRequest, ExchangeClient,
+    ExchangeDataStatus, ExchangeResponseStatus, InfoClient,
 };
+use serde::{Deserialize, Serialize};
 use tokio::time::sleep;
 use tracing::{debug, error, info, trace};
 
+use crate::{
+    pretrade::{OrderCheck, PreTradeChecker},
+    reentry::ReEntryGuard,
+    schedule::Schedule,
+};
+
 /// A small delay before re-opening a position after closing one
 pub const SLEEP_BEFORE_OPENING_POSITION: u64 = 3;
 
@@ -48,14 +55,7 @@ pub fn print_statistics(closed_trades: &Vec<Trade>) {
 
     // Process each trade
     for (i, trade) in closed_trades.iter().enumerate() {
-        let trade_pnl = if let Some(close_price) = trade.close_price {
-            match trade.direction {
-                Direction::Long => (close_price - trade.entry_price) * trade.size,
-                Direction::Short => (trade.entry_price - close_price) * trade.size,
-            }
-        } else {
-            0.0 // Should not occur for closed trades
-        };
+        let trade_pnl = trade.pnl().unwrap_or(0.0); // `None` should not occur for closed trades
 
         // Update statistics
         total_pnl += trade_pnl;
@@ -104,13 +104,13 @@ pub fn print_statistics(closed_trades: &Vec<Trade>) {
     println!("-------------------------------------------------");
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Direction {
     Long,
     Short,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Trade {
     pub direction: Direction,
     pub entry_price: f64,
@@ -122,6 +122,27 @@ pub struct Trade {
 }
 
 impl Trade {
+    /// Realized PnL in USD, `None` while the trade is open
+    pub fn pnl(&self) -> Option<f64> {
+        Some(self.pnl_at(self.close_price?))
+    }
+
+    /// Size in coins, negative for shorts
+    pub fn signed_size(&self) -> f64 {
+        match self.direction {
+            Direction::Long => self.size.abs(),
+            Direction::Short => -self.size.abs(),
+        }
+    }
+
+    /// PnL in USD if the trade were closed at `price`
+    pub fn pnl_at(&self, price: f64) -> f64 {
+        match self.direction {
+            Direction::Long => (price - self.entry_price) * self.size,
+            Direction::Short => (self.entry_price - price) * self.size,
+        }
+    }
+
     /// Default constructor for a new trade
     pub fn default() -> Self {
         Trade {
@@ -136,35 +157,53 @@ impl Trade {
     }
 }
 
+/// Why a trade was closed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    TakeProfit,
+    StopLoss,
+    Timeout,
+}
+
+impl std::fmt::Display for CloseReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CloseReason::TakeProfit => "take-profit",
+            CloseReason::StopLoss => "stop-loss",
+            CloseReason::Timeout => "timeout",
+        })
+    }
+}
+
 pub fn check_should_close(
     trade: &Trade,
     current_price: f64,
     current_ts: i64,
     timeout: u64,
-) -> Option<String> {
+) -> Option<CloseReason> {
     let elapsed = (current_ts - trade.entry_time) as u64;
 
     // First check timeout as it's independent of price
     if elapsed >= timeout {
-        return Some(format!("timeout {}s", timeout));
+        return Some(CloseReason::Timeout);
     }
 
     // Then check price conditions
     match trade.direction {
         Direction::Long => {
             if current_price >= trade.tp_price {
-                Some(format!("take-profit at {:.4}", current_price))
+                Some(CloseReason::TakeProfit)
             } else if current_price <= trade.sl_price {
-                Some(format!("stop-loss at {:.4}", current_price))
+                Some(CloseReason::StopLoss)
             } else {
                 None
             }
         }
         Direction::Short => {
             if current_price <= trade.tp_price {
-                Some(format!("take-profit at {:.4}", current_price))
+                Some(CloseReason::TakeProfit)
             } else if current_price >= trade.sl_price {
-                Some(format!("stop-loss at {:.4}", current_price))
+                Some(CloseReason::StopLoss)
             } else {
                 None
             }
@@ -185,43 +224,74 @@ pub struct BotParams {
 #[derive(Debug)]
 pub struct TradingAccount {
     pub wallet: LocalWallet,
-    pub exchange_client: ExchangeClient,
+    /// Shared by every coin traded on the account
+    pub exchange_client: Arc<ExchangeClient>,
     pub user_address: H160,
     pub active_trade: Option<Trade>,
     pub is_long_account: bool,
     pub closed_trades: Vec<Trade>,
+    /// Validates every order before it is sent; shared by every account of the bot
+    pub pre_trade: Arc<PreTradeChecker>,
 }
 
-/// Creates a new `Trade` with the given direction, using your `BotParams`.
-pub fn create_trade(is_long: bool, latest_price: f64, params: &BotParams) -> Trade {
-    let size = get_size(params.amount * params.leverage, latest_price);
+/// Creates a new `Trade` of `asset` with the given direction, using your `BotParams`. Prices
+/// and size are rounded to what the exchange accepts for `asset`.
+pub fn create_trade(
+    is_long: bool,
+    latest_price: f64,
+    params: &BotParams,
+    pre_trade: &PreTradeChecker,
+    asset: &str,
+) -> Trade {
+    let entry_price = pre_trade.round_price(asset, latest_price);
+    let size = pre_trade.round_size(asset, params.amount * params.leverage / entry_price);
 
     let tp_price = if is_long {
-        latest_price * (1.0 + params.tp_percent)
+        entry_price * (1.0 + params.tp_percent)
     } else {
-        latest_price * (1.0 - params.tp_percent)
+        entry_price * (1.0 - params.tp_percent)
     };
 
     let sl_price = if is_long {
-        latest_price * (1.0 - params.sl_percent)
+        entry_price * (1.0 - params.sl_percent)
     } else {
-        latest_price * (1.0 + params.sl_percent)
+        entry_price * (1.0 + params.sl_percent)
     };
 
     Trade {
         direction: if is_long { Direction::Long } else { Direction::Short },
-        entry_price: latest_price,
+        entry_price,
         entry_time: Utc::now().timestamp(),
         size,
-        tp_price,
-        sl_price,
+        tp_price: pre_trade.round_price(asset, tp_price),
+        sl_price: pre_trade.round_price(asset, sl_price),
         close_price: None,
     }
 }
 
 impl TradingAccount {
-    /// Opens a new position on Hyperliquid with the given `Trade` details.
-    pub async fn open_position(&mut self, trade: Trade, asset: &str) -> eyre::Result<()> {
+    /// Position (signed coins) the account is known to hold.
+    pub fn position(&self) -> f64 {
+        self.active_trade.as_ref().map_or(0.0, Trade::signed_size)
+    }
+
+    /// Opens a new position on Hyperliquid with the given `Trade` details. Returns whether the
+    /// order was placed: `false` if it failed the pre-trade checks or the exchange rejected it,
+    /// in which case the account stays without an active trade.
+    pub async fn open_position(&mut self, trade: Trade, asset: &str) -> eyre::Result<bool> {
+        let check = OrderCheck {
+            asset,
+            is_buy: trade.direction == Direction::Long,
+            price: trade.entry_price,
+            size: trade.size,
+            reduce_only: false,
+            mid: None,
+            position: self.position(),
+        };
+        let Ok(reservation) = self.pre_trade.check(self.user_address, &check) else {
+            return Ok(false);
+        };
+
         let order = self
             .exchange_client
             .order(
@@ -239,38 +309,155 @@ impl TradingAccount {
             .await?;
         debug!("Order response: {:?}", order);
 
+        let placed = match order {
+            ExchangeResponseStatus::Ok(response) => match response.data {
+                Some(data) => match &data.statuses[0] {
+                    ExchangeDataStatus::Filled(_) => {
+                        info!(
+                            "Opened {} position at {:.3} (TP: {:.3}, SL: {:.3})",
+                            if trade.direction == Direction::Long { "LONG" } else { "SHORT" },
+                            trade.entry_price,
+                            trade.tp_price,
+                            trade.sl_price
+                        );
+                        true
+                    }
+                    ExchangeDataStatus::Error(e) => {
+                        error!("Order error: {}", e);
+                        false
+                    }
+                    _ => true,
+                },
+                None => true,
+            },
+            ExchangeResponseStatus::Err(e) => {
+                error!("Order error: {}", e);
+                false
+            }
+        };
+
         // Not doing inside the fill block to avoid case when its resting and then filled.
-        self.active_trade = Some(trade);
+        if placed {
+            self.active_trade = Some(trade);
+        } else {
+            self.pre_trade.release(reservation);
+        }
+        Ok(placed)
+    }
+
+    /// Places a limit order with the given time-in-force ("Gtc", "Alo" for post-only, "Ioc").
+    /// `mid`, when known, bounds how far the price may be from the market.
+    ///
+    /// Returns the order id if the order is resting on the book, `None` if it filled
+    /// immediately or was rejected, locally or by the exchange (rejections are logged).
+    #[allow(clippy::too_many_arguments)]
+    pub async fn place_limit_order(
+        &self,
+        asset: &str,
+        is_buy: bool,
+        price: f64,
+        size: f64,
+        tif: &str,
+        reduce_only: bool,
+        mid: Option<f64>,
+    ) -> eyre::Result<Option<u64>> {
+        let check =
+            OrderCheck { asset, is_buy, price, size, reduce_only, mid, position: self.position() };
+        let Ok(reservation) = self.pre_trade.check(self.user_address, &check) else {
+            return Ok(None);
+        };
+
+        let order = self
+            .exchange_client
+            .order(
+                ClientOrderRequest {
+                    asset: asset.to_string(),
+                    is_buy,
+                    reduce_only,
+                    limit_px: price,
+                    sz: size.abs(),
+                    cloid: None,
+                    order_type: ClientOrder::Limit(ClientLimit { tif: tif.to_string() }),
+                },
+                None,
+            )
+            .await?;
+        debug!("{} order response: {:?}", tif, order);
 
         match order {
             ExchangeResponseStatus::Ok(response) => {
-                if let Some(data) = response.data {
-                    match &data.statuses[0] {
-                        ExchangeDataStatus::Filled(_) => {
-                            info!(
-                                "Opened {} position at {:.3} (TP: {:.3}, SL: {:.3})",
-                                if trade.direction == Direction::Long { "LONG" } else { "SHORT" },
-                                trade.entry_price,
-                                trade.tp_price,
-                                trade.sl_price
-                            );
-                        }
-                        ExchangeDataStatus::Error(e) => {
-                            error!("Order error: {}", e);
-                        }
-                        _ => {}
+                match response.data.as_ref().and_then(|data| data.statuses.first()) {
+                    Some(ExchangeDataStatus::Resting(resting)) => {
+                        self.pre_trade.on_resting(reservation, resting.oid);
+                        return Ok(Some(resting.oid));
+                    }
+                    Some(ExchangeDataStatus::Error(e)) => {
+                        error!("Order error: {}", e);
+                        self.pre_trade.release(reservation);
                     }
+                    _ => {}
                 }
             }
             ExchangeResponseStatus::Err(e) => {
                 error!("Order error: {}", e);
+                self.pre_trade.release(reservation);
             }
         }
+        Ok(None)
+    }
 
-        Ok(())
+    /// Cancels a resting order. Returns `false` if the exchange rejected the cancel, which
+    /// usually means the order already filled.
+    pub async fn cancel_order(&self, asset: &str, oid: u64) -> eyre::Result<bool> {
+        let response = self
+            .exchange_client
+            .cancel(ClientCancelRequest { asset: asset.to_string(), oid }, None)
+            .await?;
+        debug!("Cancel response for {}: {:?}", oid, response);
+
+        let cancelled = match response {
+            ExchangeResponseStatus::Ok(response) => !matches!(
+                response.data.as_ref().and_then(|data| data.statuses.first()),
+                Some(ExchangeDataStatus::Error(_))
+            ),
+            ExchangeResponseStatus::Err(e) => {
+                error!("Cancel error: {}", e);
+                false
+            }
+        };
+        if cancelled {
+            self.pre_trade.on_cancelled(self.user_address, oid);
+        }
+        Ok(cancelled)
     }
 }
 
+/// Cancel every resting order of `user` on `asset`. Returns how many were cancelled.
+pub async fn cancel_open_orders(
+    info_client: &InfoClient,
+    exchange_client: &ExchangeClient,
+    user: H160,
+    asset: &str,
+) -> eyre::Result<usize> {
+    let mut cancelled = 0;
+    for order in info_client.open_orders(user).await?.into_iter().filter(|o| o.coin == asset) {
+        match exchange_client
+            .cancel(ClientCancelRequest { asset: asset.to_string(), oid: order.oid }, None)
+            .await?
+        {
+            ExchangeResponseStatus::Ok(_) => cancelled += 1,
+            ExchangeResponseStatus::Err(e) => error!("Cancel error for {}: {}", order.oid, e),
+        }
+    }
+    Ok(cancelled)
+}
+
+/// Account value (USD) of `user`, as reported by the exchange.
+pub async fn account_equity(info_client: &InfoClient, user: H160) -> eyre::Result<f64> {
+    let state = info_client.user_state(user).await?;
+    Ok(state.margin_summary.account_value.parse()?)
+}
+
 /// Closes the currently active position in `account`, updates PnL, logs info, etc.
 ///
 /// - `current_price`: The market price at which we are closing
@@ -282,21 +469,37 @@ pub async fn close_position(
     asset: &str,
     total_pnl: &mut f64,
 ) -> eyre::Result<()> {
+    // Mids can carry more digits than an order price may
+    let current_price = account.pre_trade.round_price(asset, current_price);
     if let Some(mut trade) = account.active_trade.take() {
+        let check = OrderCheck {
+            asset,
+            is_buy: trade.direction == Direction::Short,
+            price: current_price,
+            size: trade.size,
+            reduce_only: true,
+            mid: None,
+            position: trade.signed_size(),
+        };
+        if account.pre_trade.check(account.user_address, &check).is_err() {
+            // Keep tracking the position; the close is retried on the next price
+            account.active_trade = Some(trade);
+            return Ok(());
+        }
+
         // Mark trade as closed
         trade.close_price = Some(current_price);
 
-        // The close order size is the opposite of the open order
-        let close_size = -trade.size;
+        // The close order is on the opposite side of the open order
         let order = account
             .exchange_client
             .order(
                 ClientOrderRequest {
                     asset: asset.to_string(),
-                    is_buy: close_size > 0.0,
+                    is_buy: trade.direction == Direction::Short,
                     reduce_only: true,
                     limit_px: current_price,
-                    sz: close_size.abs(),
+                    sz: trade.size.abs(),
                     cloid: None,
                     order_type: ClientOrder::Limit(ClientLimit { tif: "Gtc".to_string() }),
                 },
@@ -307,12 +510,7 @@ pub async fn close_position(
 
         match order {
             ExchangeResponseStatus::Ok(_) => {
-                // Calculate PnL for this trade
-                let pnl = if trade.size > 0.0 {
-                    (current_price - trade.entry_price) / trade.entry_price * trade.size
-                } else {
-                    (trade.entry_price - current_price) / trade.entry_price * trade.size
-                };
+                let pnl = trade.pnl().unwrap_or_default();
 
                 *total_pnl += pnl;
                 account.closed_trades.push(trade);
@@ -333,8 +531,85 @@ pub async fn close_position(
     Ok(())
 }
 
+/// Closes `fraction` of the active position in `account` with a reduce-only order and books the
+/// PnL of the closed part; the rest stays open. Closes everything when the remainder would round
+/// to zero.
+pub async fn reduce_position(
+    account: &mut TradingAccount,
+    current_price: f64,
+    asset: &str,
+    fraction: f64,
+    total_pnl: &mut f64,
+) -> eyre::Result<()> {
+    let Some(trade) = account.active_trade else { return Ok(()) };
+    let reduce = account.pre_trade.round_size(asset, trade.size.abs() * fraction);
+    if reduce <= 0.0 {
+        return Ok(());
+    }
+    if account.pre_trade.round_size(asset, trade.size.abs() - reduce) <= 0.0 {
+        return close_position(account, current_price, asset, total_pnl).await;
+    }
+
+    let is_buy = trade.direction == Direction::Short;
+    let current_price = account.pre_trade.round_price(asset, current_price);
+    let check = OrderCheck {
+        asset,
+        is_buy,
+        price: current_price,
+        size: reduce,
+        reduce_only: true,
+        mid: None,
+        position: trade.signed_size(),
+    };
+    if account.pre_trade.check(account.user_address, &check).is_err() {
+        return Ok(());
+    }
+    let order = account
+        .exchange_client
+        .order(
+            ClientOrderRequest {
+                asset: asset.to_string(),
+                is_buy,
+                reduce_only: true,
+                limit_px: current_price,
+                sz: reduce,
+                cloid: None,
+                order_type: ClientOrder::Limit(ClientLimit { tif: "Gtc".to_string() }),
+            },
+            None,
+        )
+        .await?;
+    debug!("Reduce order response: {:?}", order);
+
+    match order {
+        ExchangeResponseStatus::Ok(_) => {
+            let closed = Trade { size: reduce, close_price: Some(current_price), ..trade };
+            let pnl = closed.pnl().unwrap_or_default();
+            *total_pnl += pnl;
+            account.closed_trades.push(closed);
+            if let Some(active) = account.active_trade.as_mut() {
+                active.size = trade.size.abs() - reduce;
+            }
+            info!(
+                "Reduced {} position by {} to {} at {:.3}, PnL: {:.2}",
+                if account.is_long_account { "LONG" } else { "SHORT" },
+                reduce,
+                trade.size.abs() - reduce,
+                current_price,
+                pnl
+            );
+        }
+        ExchangeResponseStatus::Err(e) => {
+            error!("Error reducing position: {}", e);
+        }
+    }
+    Ok(())
+}
+
 /// Checks whether to close an existing trade (due to TP, SL, or timeout).
-/// If close occurs, optionally re-opens a new trade after sleeping.
+/// If close occurs, re-opens a new trade with `params` after sleeping, unless `params` is
+/// `None` or `reentry` or `schedule` blocks it; the account is then left flat.
+#[allow(clippy::too_many_arguments)]
 pub async fn check_account_position(
     account: &mut TradingAccount,
     current_price: f64,
@@ -342,22 +617,57 @@ pub async fn check_account_position(
     is_long_account: bool,
     total_pnl: &mut f64,
     asset: &str,
-    params: &BotParams,
+    params: Option<&BotParams>,
+    reentry: &mut ReEntryGuard,
+    schedule: &Schedule,
 ) -> eyre::Result<()> {
     if let Some(trade) = &account.active_trade {
         if let Some(reason) =
             check_should_close(trade, current_price, Utc::now().timestamp(), timeout_sec)
         {
-            info!("Closing {} => {}", if is_long_account { "LONG" } else { "SHORT" }, reason);
+            info!(
+                "Closing {} => {} at {:.4}",
+                if is_long_account { "LONG" } else { "SHORT" },
+                reason,
+                current_price
+            );
             // 1) Close
+            let closed_before = account.closed_trades.len();
             close_position(account, current_price, asset, total_pnl).await?;
+            if account.closed_trades.len() > closed_before {
+                let pnl = account.closed_trades.last().and_then(Trade::pnl).unwrap_or_default();
+                reentry.on_close(pnl, reason, Utc::now().timestamp());
+            }
+
+            let Some(params) = params else {
+                return Ok(());
+            };
+            if let Some(until) = reentry.blocked_until(Utc::now().timestamp()) {
+                info!(
+                    "Re-entry of {} blocked for {}s",
+                    if is_long_account { "LONG" } else { "SHORT" },
+                    until - Utc::now().timestamp()
+                );
+                return Ok(());
+            }
+            if let Err(block) = schedule.check(Utc::now()) {
+                info!(
+                    "Re-entry of {} blocked: {}",
+                    if is_long_account { "LONG" } else { "SHORT" },
+                    block
+                );
+                return Ok(());
+            }
 
             // 2) Sleep briefly (optional)
             sleep(Duration::from_secs(SLEEP_BEFORE_OPENING_POSITION)).await;
 
             // 3) Create and open a new trade
-            let new_trade = create_trade(is_long_account, current_price, params);
-            account.open_position(new_trade, asset).await?;
+            let new_trade =
+                create_trade(is_long_account, current_price, params, &account.pre_trade, asset);
+            if account.open_position(new_trade, asset).await? {
+                reentry.on_entry(new_trade.entry_time);
+            }
         }
     }
     Ok(())
@@ -0,0 +1,138 @@
+//! Stale market data detection.
+//!
+//! Bots [`Watchdog::touch`] every feed they depend on, per asset, as messages arrive, and call
+//! [`Watchdog::check`] on a timer. Once a feed has been silent for `stale_secs` the asset is
+//! reported [`Staleness::Stale`]: the bot pauses entries and resubscribes. After
+//! `stale_flatten_secs` it is reported [`Staleness::Flatten`]: the bot closes its positions at a
+//! REST price snapshot, since its websocket-driven TP/SL and timeout checks are no longer running.
+#![allow(missing_docs)]
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use tracing::{info, warn};
+
+/// How often bots should call [`Watchdog::check`].
+pub const WATCHDOG_INTERVAL_SECS: u64 = 5;
+
+/// Feed names, as in the websocket channels
+pub const ALL_MIDS_FEED: &str = "allMids";
+pub const L2_BOOK_FEED: &str = "l2Book";
+
+#[derive(Debug, Clone, Deserialize, clap::Args)]
+#[serde(default)]
+pub struct WatchdogConfig {
+    /// Seconds without market data after which entries pause and feeds are resubscribed
+    /// (0 = no watchdog)
+    #[arg(long, default_value_t = 30)]
+    pub stale_secs: u64,
+
+    /// Seconds without market data after which positions are closed at a REST price
+    /// (0 = never)
+    #[arg(long, default_value_t = 120)]
+    pub stale_flatten_secs: u64,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self { stale_secs: 30, stale_flatten_secs: 120 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Staleness {
+    Fresh,
+    Stale,
+    Flatten,
+}
+
+/// A feed crossing a staleness threshold.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaleEvent {
+    pub feed: String,
+    pub asset: String,
+    pub level: Staleness,
+    /// Seconds since the last update
+    pub age: i64,
+}
+
+#[derive(Debug, Clone)]
+struct FeedState {
+    last_update: i64,
+    level: Staleness,
+}
+
+fn level_for(config: &WatchdogConfig, age: i64) -> Staleness {
+    if config.stale_secs == 0 {
+        Staleness::Fresh
+    } else if config.stale_flatten_secs > 0 && age >= config.stale_flatten_secs as i64 {
+        Staleness::Flatten
+    } else if age >= config.stale_secs as i64 {
+        Staleness::Stale
+    } else {
+        Staleness::Fresh
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Watchdog {
+    config: WatchdogConfig,
+    /// Keyed by (feed, asset)
+    feeds: HashMap<(String, String), FeedState>,
+}
+
+impl Watchdog {
+    pub fn new(config: WatchdogConfig) -> Self {
+        Self { config, feeds: HashMap::new() }
+    }
+
+    /// Record an update of `feed` for `asset` at `now` (UTC seconds).
+    pub fn touch(&mut self, feed: &str, asset: &str, now: i64) {
+        let state = self
+            .feeds
+            .entry((feed.to_string(), asset.to_string()))
+            .or_insert(FeedState { last_update: now, level: Staleness::Fresh });
+        if state.level != Staleness::Fresh {
+            info!("{} {} is live again after {}s", asset, feed, now - state.last_update);
+        }
+        state.last_update = now;
+        state.level = Staleness::Fresh;
+    }
+
+    /// Start watching `feed` for `asset` without an update yet, e.g. right after subscribing.
+    pub fn watch(&mut self, feed: &str, asset: &str, now: i64) {
+        self.feeds
+            .entry((feed.to_string(), asset.to_string()))
+            .or_insert(FeedState { last_update: now, level: Staleness::Fresh });
+    }
+
+    /// Feeds that crossed a threshold since the last check.
+    pub fn check(&mut self, now: i64) -> Vec<StaleEvent> {
+        let mut events = Vec::new();
+        for ((feed, asset), state) in &mut self.feeds {
+            let age = now - state.last_update;
+            let level = level_for(&self.config, age);
+            if level > state.level {
+                warn!("{} {} stale for {}s ({:?})", asset, feed, age, level);
+                state.level = level;
+                events.push(StaleEvent { feed: feed.clone(), asset: asset.clone(), level, age });
+            }
+        }
+        events
+    }
+
+    /// Worst staleness over the feeds of `asset`.
+    pub fn staleness(&self, asset: &str, now: i64) -> Staleness {
+        self.feeds
+            .iter()
+            .filter(|((_, feed_asset), _)| feed_asset == asset)
+            .map(|(_, state)| level_for(&self.config, now - state.last_update))
+            .max()
+            .unwrap_or(Staleness::Fresh)
+    }
+
+    /// Whether entries on `asset` are allowed: every feed of it is fresh.
+    pub fn can_enter(&self, asset: &str, now: i64) -> bool {
+        self.staleness(asset, now) == Staleness::Fresh
+    }
+}